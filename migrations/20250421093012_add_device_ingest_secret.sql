@@ -0,0 +1,5 @@
+ALTER TABLE devices
+    ADD COLUMN ingest_secret_hash VARCHAR(64),
+    ADD COLUMN ingest_secret_created_at TIMESTAMPTZ;
+
+CREATE INDEX idx_devices_ingest_secret_hash ON devices(ingest_secret_hash);
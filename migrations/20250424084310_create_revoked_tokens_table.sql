@@ -0,0 +1,13 @@
+-- Access tokens revoked before their natural expiry. Rows are only needed
+-- until `expires_at`, after which the JWT itself is rejected.
+CREATE TABLE revoked_tokens (
+    jti UUID PRIMARY KEY,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    expires_at TIMESTAMPTZ NOT NULL,
+    revoked_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_revoked_tokens_expires_at ON revoked_tokens(expires_at);
+
+-- "Log out everywhere": tokens issued before this instant are rejected
+ALTER TABLE users ADD COLUMN tokens_valid_after TIMESTAMPTZ;
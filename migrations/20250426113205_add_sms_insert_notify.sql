@@ -0,0 +1,21 @@
+-- Announce every stored SMS so each relay instance can push it to its own
+-- streaming clients. The payload stays small (NOTIFY caps it at 8000 bytes);
+-- listeners load the row themselves.
+CREATE OR REPLACE FUNCTION notify_sms_inserted()
+RETURNS TRIGGER AS $$
+BEGIN
+   PERFORM pg_notify(
+       'sms_inserted',
+       json_build_object(
+           'id', NEW.id,
+           'user_id', (SELECT user_id FROM devices WHERE id = NEW.device_id)
+       )::text
+   );
+   RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER notify_sms_inserted
+AFTER INSERT ON sms
+FOR EACH ROW
+EXECUTE FUNCTION notify_sms_inserted();
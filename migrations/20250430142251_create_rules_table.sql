@@ -0,0 +1,36 @@
+ALTER TABLE sms
+    ADD COLUMN tags TEXT[] NOT NULL DEFAULT '{}',
+    ADD COLUMN is_spam BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- When false, the webhook only receives messages routed to it by a rule
+ALTER TABLE webhooks ADD COLUMN forward_all BOOLEAN NOT NULL DEFAULT TRUE;
+
+CREATE TABLE rules (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    name VARCHAR(255) NOT NULL,
+    -- Lower values are evaluated first
+    priority INTEGER NOT NULL DEFAULT 0,
+    enabled BOOLEAN NOT NULL DEFAULT TRUE,
+    sender_pattern TEXT,
+    message_pattern TEXT,
+    device_id UUID REFERENCES devices(id) ON DELETE CASCADE,
+    -- Time-of-day window, interpreted at `utc_offset_minutes`; wraps past midnight when from > until
+    active_from TIME,
+    active_until TIME,
+    utc_offset_minutes INTEGER NOT NULL DEFAULT 0,
+    action VARCHAR(16) NOT NULL
+        CHECK (action IN ('forward', 'tag', 'discard', 'mark_spam')),
+    webhook_id UUID REFERENCES webhooks(id) ON DELETE CASCADE,
+    tag VARCHAR(64),
+    stop_processing BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_rules_user_id ON rules(user_id);
+
+CREATE TRIGGER update_rules_updated_at
+BEFORE UPDATE ON rules
+FOR EACH ROW
+EXECUTE FUNCTION update_updated_at_column();
@@ -0,0 +1,17 @@
+ALTER TABLE sms
+    ADD COLUMN otp_code VARCHAR(32),
+    ADD COLUMN otp_issuer VARCHAR(255),
+    ADD COLUMN otp_consumed_at TIMESTAMPTZ;
+
+CREATE INDEX idx_sms_otp_unused ON sms(device_id, received_at DESC)
+    WHERE otp_code IS NOT NULL AND otp_consumed_at IS NULL;
+
+CREATE TABLE otp_patterns (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    pattern TEXT NOT NULL,
+    issuer VARCHAR(255),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_otp_patterns_user_id ON otp_patterns(user_id);
@@ -0,0 +1,9 @@
+-- Punctuation is folded to spaces before indexing so "G-482913" and
+-- "user@example.com" are searchable by their parts. The 'simple' config
+-- skips stemming, which suits short multilingual messages.
+ALTER TABLE sms ADD COLUMN search_vector tsvector GENERATED ALWAYS AS (
+    setweight(to_tsvector('simple', regexp_replace(sender, '[^[:alnum:]]+', ' ', 'g')), 'A') ||
+    setweight(to_tsvector('simple', regexp_replace(message, '[^[:alnum:]]+', ' ', 'g')), 'B')
+) STORED;
+
+CREATE INDEX idx_sms_search_vector ON sms USING GIN (search_vector);
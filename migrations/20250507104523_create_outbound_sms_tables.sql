@@ -0,0 +1,32 @@
+CREATE TABLE outbound_sms (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    device_id UUID NOT NULL REFERENCES devices(id) ON DELETE CASCADE,
+    recipient TEXT NOT NULL,
+    message TEXT NOT NULL,
+    status VARCHAR(16) NOT NULL DEFAULT 'queued'
+        CHECK (status IN ('queued', 'claimed', 'sent', 'delivered', 'failed')),
+    error TEXT,
+    claimed_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_outbound_sms_device_id ON outbound_sms(device_id, created_at);
+CREATE INDEX idx_outbound_sms_claimable ON outbound_sms(device_id, created_at)
+    WHERE status IN ('queued', 'claimed');
+
+CREATE TRIGGER update_outbound_sms_updated_at
+BEFORE UPDATE ON outbound_sms
+FOR EACH ROW
+EXECUTE FUNCTION update_updated_at_column();
+
+-- Every status the message has passed through, as reported by the phone
+CREATE TABLE outbound_sms_events (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    outbound_sms_id UUID NOT NULL REFERENCES outbound_sms(id) ON DELETE CASCADE,
+    status VARCHAR(16) NOT NULL,
+    error TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_outbound_sms_events_outbound_sms_id ON outbound_sms_events(outbound_sms_id);
@@ -0,0 +1,20 @@
+-- Normalized sender used to group messages into threads
+ALTER TABLE sms
+    ADD COLUMN counterpart TEXT,
+    ADD COLUMN is_read BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- Backfill with the same rules the application applies on insert
+UPDATE sms SET counterpart = CASE
+    WHEN btrim(sender) ~ '^[0-9+ ().-]+$' AND sender ~ '[0-9]' THEN
+        CASE
+            WHEN btrim(sender) LIKE '+%' THEN '+' || regexp_replace(sender, '[^0-9]', '', 'g')
+            WHEN regexp_replace(sender, '[^0-9]', '', 'g') LIKE '00%'
+                THEN '+' || substr(regexp_replace(sender, '[^0-9]', '', 'g'), 3)
+            ELSE regexp_replace(sender, '[^0-9]', '', 'g')
+        END
+    ELSE lower(regexp_replace(btrim(sender), '\s+', ' ', 'g'))
+END;
+
+ALTER TABLE sms ALTER COLUMN counterpart SET NOT NULL;
+
+CREATE INDEX idx_sms_conversation ON sms(device_id, counterpart, received_at DESC);
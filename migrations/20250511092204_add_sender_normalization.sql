@@ -0,0 +1,20 @@
+-- ISO 3166-1 alpha-2 region used to interpret senders without a country code
+ALTER TABLE devices ADD COLUMN default_region VARCHAR(2);
+
+ALTER TABLE sms
+    ADD COLUMN raw_sender TEXT,
+    ADD COLUMN sender_type VARCHAR(16);
+
+-- Existing senders are kept as they were; only new messages are rewritten to E.164
+UPDATE sms SET
+    raw_sender = sender,
+    sender_type = CASE
+        WHEN counterpart ~ '^[0-9]{3,6}$' THEN 'short_code'
+        WHEN counterpart ~ '^\+?[0-9]+$' THEN 'phone'
+        ELSE 'alphanumeric'
+    END;
+
+ALTER TABLE sms
+    ALTER COLUMN raw_sender SET NOT NULL,
+    ALTER COLUMN sender_type SET NOT NULL,
+    ADD CONSTRAINT sms_sender_type_check CHECK (sender_type IN ('phone', 'short_code', 'alphanumeric'));
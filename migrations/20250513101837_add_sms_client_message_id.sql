@@ -0,0 +1,5 @@
+-- Client-generated id (or Idempotency-Key) used to recognize retried uploads
+ALTER TABLE sms ADD COLUMN client_message_id VARCHAR(255);
+
+CREATE UNIQUE INDEX idx_sms_device_client_message_id ON sms(device_id, client_message_id)
+    WHERE client_message_id IS NOT NULL;
@@ -0,0 +1,5 @@
+-- Receive time reported by the phone, e.g. for messages replayed from its buffer
+ALTER TABLE sms ADD COLUMN device_received_at TIMESTAMPTZ;
+
+-- Service-center timestamp carried in the SMS PDU, as reported by the phone
+ALTER TABLE sms ADD COLUMN smsc_timestamp TIMESTAMPTZ;
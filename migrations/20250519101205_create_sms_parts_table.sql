@@ -0,0 +1,27 @@
+-- Segments of concatenated messages, kept after reassembly for reference
+CREATE TABLE sms_parts (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    device_id UUID NOT NULL REFERENCES devices(id) ON DELETE CASCADE,
+    raw_sender TEXT NOT NULL,
+    counterpart TEXT NOT NULL,
+    reference INTEGER NOT NULL,
+    total_parts SMALLINT NOT NULL,
+    sequence SMALLINT NOT NULL,
+    message TEXT NOT NULL,
+    received_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    device_received_at TIMESTAMPTZ,
+    smsc_timestamp TIMESTAMPTZ,
+    -- Set when a request claims the group for reassembly
+    assembled_at TIMESTAMPTZ,
+    sms_id UUID REFERENCES sms(id) ON DELETE CASCADE,
+    CHECK (total_parts BETWEEN 2 AND 255),
+    CHECK (sequence BETWEEN 1 AND total_parts)
+);
+
+CREATE INDEX idx_sms_parts_group ON sms_parts(device_id, counterpart, reference, total_parts);
+CREATE INDEX idx_sms_parts_pending ON sms_parts(received_at) WHERE sms_id IS NULL;
+CREATE INDEX idx_sms_parts_sms_id ON sms_parts(sms_id);
+
+ALTER TABLE sms
+    ADD COLUMN part_count SMALLINT,
+    ADD COLUMN is_partial BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,15 @@
+-- MMS content; the bytes live in the blob store under storage_key
+CREATE TABLE sms_attachments (
+    id UUID PRIMARY KEY,
+    sms_id UUID NOT NULL REFERENCES sms(id) ON DELETE CASCADE,
+    -- Order within the upload
+    position SMALLINT NOT NULL,
+    -- Sniffed from the content, not taken from the upload
+    content_type TEXT NOT NULL,
+    size_bytes BIGINT NOT NULL,
+    filename TEXT,
+    storage_key TEXT NOT NULL UNIQUE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_sms_attachments_sms_id ON sms_attachments(sms_id, position);
@@ -0,0 +1,6 @@
+ALTER TABLE sms
+    ADD COLUMN is_starred BOOLEAN NOT NULL DEFAULT FALSE,
+    ADD COLUMN is_archived BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- Unread counts per device
+CREATE INDEX idx_sms_unread ON sms(device_id) WHERE NOT is_read AND NOT is_archived;
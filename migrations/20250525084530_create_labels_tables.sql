@@ -0,0 +1,31 @@
+CREATE TABLE labels (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    name VARCHAR(64) NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- "Banking" and "banking" are the same label
+CREATE UNIQUE INDEX idx_labels_user_name ON labels(user_id, LOWER(name));
+
+CREATE TRIGGER update_labels_updated_at
+BEFORE UPDATE ON labels
+FOR EACH ROW
+EXECUTE FUNCTION update_updated_at_column();
+
+CREATE TABLE sms_labels (
+    sms_id UUID NOT NULL REFERENCES sms(id) ON DELETE CASCADE,
+    label_id UUID NOT NULL REFERENCES labels(id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (sms_id, label_id)
+);
+
+CREATE INDEX idx_sms_labels_label_id ON sms_labels(label_id);
+
+-- Rules can apply a label to matching messages
+ALTER TABLE rules
+    ADD COLUMN label_id UUID REFERENCES labels(id) ON DELETE CASCADE,
+    DROP CONSTRAINT rules_action_check,
+    ADD CONSTRAINT rules_action_check
+        CHECK (action IN ('forward', 'tag', 'label', 'discard', 'mark_spam'));
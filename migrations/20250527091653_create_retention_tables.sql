@@ -0,0 +1,34 @@
+-- A user-wide policy has no device_id; a device policy overrides it field by field
+CREATE TABLE retention_policies (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    device_id UUID REFERENCES devices(id) ON DELETE CASCADE,
+    max_age_days INTEGER CHECK (max_age_days > 0),
+    max_count INTEGER CHECK (max_count > 0),
+    -- Messages with an extracted one-time code usually aren't worth keeping long
+    otp_max_age_hours INTEGER CHECK (otp_max_age_hours > 0),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE UNIQUE INDEX idx_retention_policies_user ON retention_policies(user_id) WHERE device_id IS NULL;
+CREATE UNIQUE INDEX idx_retention_policies_device ON retention_policies(device_id) WHERE device_id IS NOT NULL;
+
+CREATE TRIGGER update_retention_policies_updated_at
+BEFORE UPDATE ON retention_policies
+FOR EACH ROW
+EXECUTE FUNCTION update_updated_at_column();
+
+-- Outcome of the most recent purge of each user's messages
+CREATE TABLE retention_reports (
+    user_id UUID PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    started_at TIMESTAMPTZ NOT NULL,
+    finished_at TIMESTAMPTZ NOT NULL,
+    deleted_by_age BIGINT NOT NULL,
+    deleted_by_count BIGINT NOT NULL,
+    deleted_otp BIGINT NOT NULL,
+    deleted_attachments BIGINT NOT NULL
+);
+
+-- Purge scans by device in age order
+CREATE INDEX idx_sms_device_received_at ON sms(device_id, received_at);
@@ -0,0 +1,19 @@
+-- A retired device keeps its message history but can no longer ingest or send
+ALTER TABLE devices ADD COLUMN retired_at TIMESTAMPTZ;
+
+CREATE TABLE device_transfers (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    device_id UUID NOT NULL REFERENCES devices(id) ON DELETE CASCADE,
+    from_user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    to_user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    status VARCHAR(16) NOT NULL DEFAULT 'pending'
+        CHECK (status IN ('pending', 'accepted', 'declined', 'cancelled')),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    resolved_at TIMESTAMPTZ,
+    CHECK (from_user_id <> to_user_id)
+);
+
+-- A device can only be offered to one user at a time
+CREATE UNIQUE INDEX idx_device_transfers_pending ON device_transfers(device_id) WHERE status = 'pending';
+CREATE INDEX idx_device_transfers_from_user_id ON device_transfers(from_user_id, created_at);
+CREATE INDEX idx_device_transfers_to_user_id ON device_transfers(to_user_id, created_at);
@@ -0,0 +1,38 @@
+-- Runs ahead of scope_device_name_to_user, which trims device names and
+-- numbers a user's duplicates as " (2)", " (3)", ... without checking that
+-- the numbered name is free. Renaming the duplicates here first, to numbers
+-- no other name takes once trimmed, leaves that migration nothing to number.
+-- On databases that already ran it, names are unique per user and this does
+-- nothing.
+DO $$
+DECLARE
+    duplicate RECORD;
+    n INTEGER;
+    candidate TEXT;
+BEGIN
+    FOR duplicate IN
+        SELECT id, user_id, device_name
+        FROM (
+            SELECT id, user_id, BTRIM(device_name) AS device_name, created_at,
+                   ROW_NUMBER() OVER (PARTITION BY user_id, BTRIM(device_name) ORDER BY created_at, id) AS rank
+            FROM devices
+            WHERE retired_at IS NULL
+        ) ranked
+        WHERE rank > 1
+        ORDER BY user_id, device_name, created_at, id
+    LOOP
+        n := 2;
+        LOOP
+            candidate := LEFT(duplicate.device_name, 240) || ' (' || n || ')';
+            -- Names may still be unique across all users at this point
+            EXIT WHEN NOT EXISTS (
+                SELECT 1 FROM devices
+                WHERE device_name = candidate
+                OR (user_id = duplicate.user_id AND BTRIM(device_name) = candidate AND retired_at IS NULL)
+            );
+            n := n + 1;
+        END LOOP;
+
+        UPDATE devices SET device_name = candidate WHERE id = duplicate.id;
+    END LOOP;
+END $$;
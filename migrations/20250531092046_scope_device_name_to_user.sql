@@ -0,0 +1,20 @@
+-- Device names only need to be unique among one user's devices. Retired
+-- devices give up their name so it can be reused.
+ALTER TABLE devices DROP CONSTRAINT devices_device_name_key;
+
+-- Names used to be stored as sent; they are now trimmed
+UPDATE devices SET device_name = BTRIM(device_name) WHERE device_name <> BTRIM(device_name);
+
+-- Trimming can make two of a user's names collide, so number the later ones
+WITH numbered AS (
+    SELECT id, device_name,
+           ROW_NUMBER() OVER (PARTITION BY user_id, device_name ORDER BY created_at, id) AS n
+    FROM devices
+    WHERE retired_at IS NULL
+)
+UPDATE devices d
+SET device_name = LEFT(numbered.device_name, 240) || ' (' || numbered.n || ')'
+FROM numbered
+WHERE d.id = numbered.id AND numbered.n > 1;
+
+CREATE UNIQUE INDEX idx_devices_user_name ON devices(user_id, device_name) WHERE retired_at IS NULL;
@@ -0,0 +1,18 @@
+ALTER TABLE devices ADD COLUMN last_seen_at TIMESTAMPTZ;
+
+-- Health reports from the phone; only the most recent few are kept per device
+CREATE TABLE device_heartbeats (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    device_id UUID NOT NULL REFERENCES devices(id) ON DELETE CASCADE,
+    battery_level SMALLINT CHECK (battery_level BETWEEN 0 AND 100),
+    is_charging BOOLEAN,
+    -- dBm
+    signal_strength SMALLINT,
+    network_type VARCHAR(16),
+    app_version VARCHAR(64),
+    sim_operator VARCHAR(64),
+    sim_country VARCHAR(2),
+    received_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_device_heartbeats_device_received_at ON device_heartbeats(device_id, received_at DESC);
@@ -0,0 +1,50 @@
+-- Where a user's device alerts are sent
+CREATE TABLE alert_channels (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    kind VARCHAR(16) NOT NULL CHECK (kind IN ('webhook', 'email')),
+    -- A URL or an email address, depending on the kind
+    target TEXT NOT NULL,
+    -- HMAC key for signing webhook notifications
+    secret VARCHAR(64),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_alert_channels_user_id ON alert_channels(user_id);
+
+CREATE TRIGGER update_alert_channels_updated_at
+BEFORE UPDATE ON alert_channels
+FOR EACH ROW
+EXECUTE FUNCTION update_updated_at_column();
+
+-- A device problem, from detection until it clears
+CREATE TABLE device_alerts (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    device_id UUID NOT NULL REFERENCES devices(id) ON DELETE CASCADE,
+    kind VARCHAR(16) NOT NULL CHECK (kind IN ('heartbeat', 'sms_silence')),
+    -- The last heartbeat or SMS before the device went quiet
+    last_activity_at TIMESTAMPTZ,
+    opened_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    resolved_at TIMESTAMPTZ
+);
+
+-- At most one open alert of each kind per device
+CREATE UNIQUE INDEX idx_device_alerts_open ON device_alerts(device_id, kind) WHERE resolved_at IS NULL;
+CREATE INDEX idx_device_alerts_device_opened_at ON device_alerts(device_id, opened_at DESC);
+
+-- Every notification sent for an alert, successful or not
+CREATE TABLE alert_notifications (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    alert_id UUID NOT NULL REFERENCES device_alerts(id) ON DELETE CASCADE,
+    channel_id UUID REFERENCES alert_channels(id) ON DELETE SET NULL,
+    event VARCHAR(16) NOT NULL CHECK (event IN ('opened', 'resolved')),
+    -- Copied from the channel so the record survives its deletion
+    channel_kind VARCHAR(16) NOT NULL,
+    target TEXT NOT NULL,
+    -- NULL when the notification was delivered
+    error TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_alert_notifications_alert_id ON alert_notifications(alert_id);
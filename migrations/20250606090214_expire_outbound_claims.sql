@@ -0,0 +1,21 @@
+-- Claims the phone never reported back on are marked expired rather than
+-- handed out again, so a message is only re-sent when its owner asks
+ALTER TABLE outbound_sms
+    DROP CONSTRAINT outbound_sms_status_check,
+    ADD CONSTRAINT outbound_sms_status_check
+        CHECK (status IN ('queued', 'claimed', 'sent', 'delivered', 'failed', 'expired'));
+
+-- Wake phones long-polling for work as soon as something is queued for them
+CREATE OR REPLACE FUNCTION notify_outbound_queued()
+RETURNS TRIGGER AS $$
+BEGIN
+   PERFORM pg_notify('outbound_queued', json_build_object('device_id', NEW.device_id)::text);
+   RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER notify_outbound_queued
+AFTER INSERT OR UPDATE OF status ON outbound_sms
+FOR EACH ROW
+WHEN (NEW.status = 'queued')
+EXECUTE FUNCTION notify_outbound_queued();
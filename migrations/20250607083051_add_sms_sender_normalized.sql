@@ -0,0 +1,7 @@
+-- Messages stored before sender normalization still hold the sender as the
+-- phone sent it. They're flagged here and rewritten at startup by the
+-- sender backfill job; new rows are stored normalized.
+ALTER TABLE sms ADD COLUMN sender_normalized BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE sms ALTER COLUMN sender_normalized SET DEFAULT TRUE;
+
+CREATE INDEX idx_sms_sender_not_normalized ON sms(id) WHERE NOT sender_normalized;
@@ -0,0 +1,23 @@
+-- Concatenation references are only 8 or 16 bits and wrap around, so two
+-- messages from the same sender can share one. Each message's parts now get
+-- their own group; a part whose position is already taken with different
+-- text starts a new group instead of being mistaken for a retry.
+ALTER TABLE sms_parts
+    ADD COLUMN group_id UUID,
+    ADD COLUMN client_message_id TEXT;
+
+WITH groups AS (
+    SELECT device_id, counterpart, reference, total_parts, sms_id, uuid_generate_v4() AS group_id
+    FROM sms_parts
+    GROUP BY device_id, counterpart, reference, total_parts, sms_id
+)
+UPDATE sms_parts p SET group_id = g.group_id
+FROM groups g
+WHERE p.device_id = g.device_id AND p.counterpart = g.counterpart AND p.reference = g.reference
+AND p.total_parts = g.total_parts AND p.sms_id IS NOT DISTINCT FROM g.sms_id;
+
+ALTER TABLE sms_parts ALTER COLUMN group_id SET NOT NULL;
+
+CREATE INDEX idx_sms_parts_group_id ON sms_parts(group_id);
+CREATE UNIQUE INDEX idx_sms_parts_client_message_id ON sms_parts(device_id, client_message_id)
+    WHERE client_message_id IS NOT NULL;
@@ -0,0 +1,44 @@
+-- The messages a listing filter selects, shared by GET /sms and the bulk
+-- updates so they can't drift apart. Each NULL argument leaves its criterion
+-- out. `at` is the time the filter's clock reads: the device or SMSC time,
+-- falling back to when the relay stored the message. A single SELECT marked
+-- STABLE, so the planner inlines it into the calling query.
+CREATE FUNCTION user_sms_matching(
+    filter_user_id UUID,
+    filter_device_id UUID,
+    filter_tsquery TEXT,
+    filter_from TIMESTAMPTZ,
+    filter_to TIMESTAMPTZ,
+    filter_counterpart TEXT,
+    filter_sender_type TEXT,
+    filter_clock TEXT,
+    filter_is_read BOOLEAN,
+    filter_is_starred BOOLEAN,
+    filter_is_archived BOOLEAN,
+    filter_label_id UUID
+)
+RETURNS TABLE (id UUID, at TIMESTAMPTZ) AS $$
+    SELECT s.id, c.at
+    FROM sms s
+    JOIN devices d ON d.id = s.device_id
+    CROSS JOIN LATERAL (
+        SELECT CASE filter_clock
+            WHEN 'device' THEN COALESCE(s.device_received_at, s.received_at)
+            WHEN 'smsc' THEN COALESCE(s.smsc_timestamp, s.received_at)
+            ELSE s.received_at
+        END AS at
+    ) c
+    WHERE d.user_id = filter_user_id
+    AND (filter_device_id IS NULL OR s.device_id = filter_device_id)
+    AND (filter_tsquery IS NULL OR s.search_vector @@ to_tsquery('simple', filter_tsquery))
+    AND (filter_from IS NULL OR c.at >= filter_from)
+    AND (filter_to IS NULL OR c.at <= filter_to)
+    AND (filter_counterpart IS NULL OR s.counterpart = filter_counterpart)
+    AND (filter_sender_type IS NULL OR s.sender_type = filter_sender_type)
+    AND (filter_is_read IS NULL OR s.is_read = filter_is_read)
+    AND (filter_is_starred IS NULL OR s.is_starred = filter_is_starred)
+    AND (filter_is_archived IS NULL OR s.is_archived = filter_is_archived)
+    AND (filter_label_id IS NULL OR EXISTS (
+        SELECT 1 FROM sms_labels sl WHERE sl.sms_id = s.id AND sl.label_id = filter_label_id
+    ))
+$$ LANGUAGE sql STABLE;
@@ -0,0 +1,10 @@
+-- Deleting a label used to delete the rules applying it. They are kept now,
+-- without a label and disabled (see db::delete_user_label), for the owner to
+-- point at another label or remove.
+ALTER TABLE rules
+    DROP CONSTRAINT rules_label_id_fkey,
+    ADD CONSTRAINT rules_label_id_fkey
+        FOREIGN KEY (label_id) REFERENCES labels(id) ON DELETE SET NULL;
+
+-- Only label rules take a label
+UPDATE rules SET label_id = NULL WHERE action <> 'label' AND label_id IS NOT NULL;
@@ -0,0 +1,21 @@
+-- Messages waiting on a device that is retired or handed to someone else are
+-- cancelled rather than left for a phone their sender no longer controls
+ALTER TABLE outbound_sms
+    DROP CONSTRAINT outbound_sms_status_check,
+    ADD CONSTRAINT outbound_sms_status_check
+        CHECK (status IN ('queued', 'claimed', 'sent', 'delivered', 'failed', 'expired', 'cancelled'));
+
+-- The recipient is recorded as the name the sender typed, and only linked to
+-- an account when one has that name, so offers look the same either way
+ALTER TABLE device_transfers
+    ADD COLUMN to_username VARCHAR(255),
+    ALTER COLUMN to_user_id DROP NOT NULL;
+
+UPDATE device_transfers t SET to_username = u.username FROM users u WHERE u.id = t.to_user_id;
+
+ALTER TABLE device_transfers ALTER COLUMN to_username SET NOT NULL;
+
+-- Whether the message history goes with the device. Transfers offered before
+-- this always took it; new ones only do when the sender opts in.
+ALTER TABLE device_transfers ADD COLUMN include_history BOOLEAN NOT NULL DEFAULT TRUE;
+ALTER TABLE device_transfers ALTER COLUMN include_history SET DEFAULT FALSE;
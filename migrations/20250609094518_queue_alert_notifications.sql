@@ -0,0 +1,24 @@
+-- Alert notifications become a delivery queue like webhook_deliveries: a row
+-- is queued for each channel when an alert opens or resolves, and failed
+-- sends are retried with backoff. `error` holds the latest failure.
+ALTER TABLE alert_notifications
+    ADD COLUMN status VARCHAR(16) NOT NULL DEFAULT 'succeeded'
+        CHECK (status IN ('pending', 'succeeded', 'failed')),
+    ADD COLUMN attempts INTEGER NOT NULL DEFAULT 1,
+    ADD COLUMN next_attempt_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    ADD COLUMN updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW();
+
+-- Earlier notifications were sent once, when recorded
+UPDATE alert_notifications
+SET status = CASE WHEN error IS NULL THEN 'succeeded' ELSE 'failed' END, updated_at = created_at;
+
+ALTER TABLE alert_notifications
+    ALTER COLUMN status SET DEFAULT 'pending',
+    ALTER COLUMN attempts SET DEFAULT 0;
+
+CREATE INDEX idx_alert_notifications_due ON alert_notifications(next_attempt_at) WHERE status = 'pending';
+
+CREATE TRIGGER update_alert_notifications_updated_at
+BEFORE UPDATE ON alert_notifications
+FOR EACH ROW
+EXECUTE FUNCTION update_updated_at_column();
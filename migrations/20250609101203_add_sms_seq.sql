@@ -0,0 +1,21 @@
+-- Stream resumption pages on this instead of (received_at, id). received_at
+-- is taken at transaction start, so a message could commit behind a cursor a
+-- client had already passed. Inserts take seq under a per-user lock held to
+-- commit, so for each user it increases in commit order.
+CREATE SEQUENCE sms_seq_seq;
+
+ALTER TABLE sms ADD COLUMN seq BIGINT;
+
+UPDATE sms
+SET seq = numbered.n
+FROM (SELECT id, ROW_NUMBER() OVER (ORDER BY received_at, id) AS n FROM sms) numbered
+WHERE sms.id = numbered.id;
+
+SELECT setval('sms_seq_seq', COALESCE((SELECT MAX(seq) FROM sms), 0) + 1, false);
+
+ALTER TABLE sms
+    ALTER COLUMN seq SET DEFAULT nextval('sms_seq_seq'),
+    ALTER COLUMN seq SET NOT NULL;
+ALTER SEQUENCE sms_seq_seq OWNED BY sms.seq;
+
+CREATE UNIQUE INDEX idx_sms_seq ON sms(seq);
@@ -0,0 +1,114 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Duration, Utc};
+use hmac::Mac;
+use sqlx::PgPool;
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::config::AppConfig;
+use crate::db;
+use crate::errors::AppError;
+use crate::models::attachment::{Attachment, AttachmentInfo};
+use crate::models::sms::{Sms, SmsWithAttachments};
+use crate::signing::{self, HmacSha256};
+use crate::storage::BlobStore;
+
+pub const MAX_ATTACHMENTS_PER_MESSAGE: usize = 10;
+
+// The type is decided from the bytes; what the phone claims is ignored.
+// Only content MMS carries (pictures, audio, contact cards) is accepted.
+pub fn sniff_content_type(data: &[u8]) -> Option<&'static str> {
+    if let Some(kind) = infer::get(data) {
+        return match kind.matcher_type() {
+            infer::MatcherType::Image | infer::MatcherType::Audio => Some(kind.mime_type()),
+            _ => None,
+        };
+    }
+
+    let text = data.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(data).trim_ascii_start();
+    text.get(..11)
+        .filter(|start| start.eq_ignore_ascii_case(b"BEGIN:VCARD"))
+        .map(|_| "text/vcard")
+}
+
+pub fn storage_key(device_id: Uuid, attachment_id: Uuid) -> String {
+    format!("attachments/{}/{}", device_id, attachment_id)
+}
+
+// Download links carry their own short-lived credential, so they work in an
+// <img> tag or a plain browser tab without an Authorization header
+pub fn describe(config: &AppConfig, attachment: Attachment) -> AttachmentInfo {
+    let expires_at = Utc::now() + Duration::seconds(config.attachment_url_ttl_seconds);
+    let expires = expires_at.timestamp();
+    let signature = hex::encode(sign(config, attachment.id, expires).finalize().into_bytes());
+
+    AttachmentInfo {
+        id: attachment.id,
+        content_type: attachment.content_type,
+        size_bytes: attachment.size_bytes,
+        filename: attachment.filename,
+        created_at: attachment.created_at,
+        url: format!("/attachments/{}?expires={}&signature={}", attachment.id, expires, signature),
+        url_expires_at: DateTime::from_timestamp(expires, 0).unwrap_or(expires_at),
+    }
+}
+
+// Download links for the attachments of each of the given messages
+pub async fn describe_for(
+    pool: &PgPool,
+    config: &AppConfig,
+    sms_ids: &[Uuid],
+) -> Result<HashMap<Uuid, Vec<AttachmentInfo>>, AppError> {
+    let mut described: HashMap<Uuid, Vec<AttachmentInfo>> = HashMap::new();
+
+    for attachment in db::find_sms_attachments(pool, sms_ids).await? {
+        described.entry(attachment.sms_id).or_default().push(describe(config, attachment));
+    }
+
+    Ok(described)
+}
+
+// Pairs messages with their attachments, keeping their order
+pub async fn attach(pool: &PgPool, config: &AppConfig, messages: Vec<Sms>) -> Result<Vec<SmsWithAttachments>, AppError> {
+    let ids: Vec<Uuid> = messages.iter().map(|sms| sms.id).collect();
+    let mut described = describe_for(pool, config, &ids).await?;
+
+    Ok(messages
+        .into_iter()
+        .map(|sms| SmsWithAttachments {
+            attachments: described.remove(&sms.id).unwrap_or_default(),
+            sms,
+        })
+        .collect())
+}
+
+pub fn verify_download(config: &AppConfig, attachment_id: Uuid, expires: i64, signature: &str) -> bool {
+    if expires < Utc::now().timestamp() {
+        return false;
+    }
+
+    let Ok(signature) = hex::decode(signature) else {
+        return false;
+    };
+
+    sign(config, attachment_id, expires).verify_slice(&signature).is_ok()
+}
+
+// Removes blobs whose message was never stored. Failures only leave orphans behind.
+pub async fn discard(store: &dyn BlobStore, keys: &[String]) {
+    for key in keys {
+        if let Err(e) = store.delete(key).await {
+            warn!("Failed to delete orphaned attachment {}: {:?}", key, e);
+        }
+    }
+}
+
+fn sign(config: &AppConfig, attachment_id: Uuid, expires: i64) -> HmacSha256 {
+    let mut mac = signing::hmac(config.jwt_secret.as_bytes());
+    mac.update(b"attachment:");
+    mac.update(attachment_id.as_bytes());
+    mac.update(b".");
+    mac.update(expires.to_string().as_bytes());
+    mac
+}
@@ -0,0 +1,18 @@
+use uuid::Uuid;
+
+// The token handed to the phone is "<device_id>.<secret>"
+pub fn encode_token(device_id: Uuid, secret: &str) -> String {
+    format!("{}.{}", device_id, secret)
+}
+
+// Split a token back into the device id and the raw secret
+pub fn decode_token(token: &str) -> Option<(Uuid, &str)> {
+    let (device_id, secret) = token.split_once('.')?;
+    let device_id = Uuid::parse_str(device_id).ok()?;
+
+    if secret.is_empty() {
+        return None;
+    }
+
+    Some((device_id, secret))
+}
@@ -7,16 +7,20 @@ use crate::errors::AppError;
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
     pub sub: Uuid, // Subject (user ID)
+    pub sid: Uuid, // Session the token was issued for
+    pub jti: Uuid, // Unique token ID, used for revocation
     pub exp: i64,  // Expiration time (timestamp)
     pub iat: i64,  // Issued at (timestamp)
 }
 
-pub fn create_jwt(user_id: Uuid, secret: &str, expiration_seconds: i64) -> Result<String, AppError> {
+pub fn create_jwt(user_id: Uuid, session_id: Uuid, secret: &str, expiration_seconds: i64) -> Result<String, AppError> {
     let now = Utc::now();
     let expiration = now + Duration::seconds(expiration_seconds);
 
     let claims = Claims {
         sub: user_id,
+        sid: session_id,
+        jti: Uuid::new_v4(),
         exp: expiration.timestamp(),
         iat: now.timestamp(),
     };
@@ -25,7 +29,7 @@ pub fn create_jwt(user_id: Uuid, secret: &str, expiration_seconds: i64) -> Resul
     let encoding_key = EncodingKey::from_secret(secret.as_ref());
 
     encode(&header, &claims, &encoding_key)
-        .map_err(|e| AppError::JwtError(e.into()))
+        .map_err(AppError::JwtError)
 }
 
 pub fn validate_jwt(token: &str, secret: &str) -> Result<Claims, AppError> {
@@ -34,5 +38,5 @@ pub fn validate_jwt(token: &str, secret: &str) -> Result<Claims, AppError> {
 
     decode::<Claims>(token, &decoding_key, &validation)
         .map(|data| data.claims)
-        .map_err(|e| AppError::JwtError(e.into()))
+        .map_err(AppError::JwtError)
 }
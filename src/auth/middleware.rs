@@ -7,9 +7,11 @@ use axum_extra::{
     headers::{authorization::Bearer, Authorization},
     TypedHeader
 };
+use chrono::DateTime;
 
 use crate::errors::AppError;
-use crate::auth::jwt;
+use crate::auth::{device_token, jwt, secret};
+use crate::models::device::AuthenticatedDevice;
 use crate::models::user::AuthenticatedUser;
 use crate::AppState;
 use crate::db;
@@ -33,14 +35,53 @@ impl FromRequestParts<AppState> for AuthRequired
         let claims = jwt::validate_jwt(bearer.token(), &state.config.jwt_secret)
             .map_err(|_| AppError::Unauthorized)?;
 
-        // Check if user still exists in DB - adds overhead but increases security
-        if db::find_user_by_id(&state.db_pool, claims.sub).await?.is_none() {
+        // Check the user still exists and that neither the token, its session,
+        // nor all of the user's tokens have been revoked
+        if !db::is_access_token_valid(&state.db_pool, &claims).await? {
             return Err(AppError::Unauthorized);
         }
 
+        let token_expires_at = DateTime::from_timestamp(claims.exp, 0)
+            .ok_or(AppError::Unauthorized)?;
+
         // Construct the authenticated user representation
-        let auth_user = AuthenticatedUser { user_id: claims.sub };
+        let auth_user = AuthenticatedUser {
+            user_id: claims.sub,
+            session_id: claims.sid,
+            token_id: claims.jti,
+            token_expires_at,
+        };
 
         Ok(AuthRequired(auth_user))
     }
 }
+
+// Extractor that validates a device ingest token and provides AuthenticatedDevice
+#[derive(Debug, Clone)]
+pub struct DeviceAuthRequired(pub AuthenticatedDevice);
+
+impl FromRequestParts<AppState> for DeviceAuthRequired
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        let TypedHeader(Authorization(bearer)) = parts
+            .extract::<TypedHeader<Authorization<Bearer>>>()
+            .await
+            .map_err(|_| AppError::Unauthorized)?;
+
+        let (device_id, raw_secret) = device_token::decode_token(bearer.token())
+            .ok_or(AppError::Unauthorized)?;
+
+        // Revoked devices have no secret hash, so they never match here
+        let device = db::find_device_by_ingest_secret(
+            &state.db_pool,
+            device_id,
+            &secret::hash_secret(raw_secret),
+        )
+        .await?
+        .ok_or(AppError::Unauthorized)?;
+
+        Ok(DeviceAuthRequired(device))
+    }
+}
@@ -1,3 +1,5 @@
 pub mod jwt;
 pub mod password;
-pub mod middleware;
\ No newline at end of file
+pub mod middleware;
+pub mod secret;
+pub mod device_token;
\ No newline at end of file
@@ -0,0 +1,18 @@
+use rand::{distributions::Alphanumeric, Rng};
+use sha2::{Digest, Sha256};
+
+const SECRET_LENGTH: usize = 48;
+
+// Generate a new random opaque secret (device ingest secrets, refresh tokens)
+pub fn generate_secret() -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(SECRET_LENGTH)
+        .map(char::from)
+        .collect()
+}
+
+// Secrets are long and random, so a fast digest is enough (unlike user passwords)
+pub fn hash_secret(secret: &str) -> String {
+    hex::encode(Sha256::digest(secret.as_bytes()))
+}
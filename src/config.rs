@@ -1,4 +1,5 @@
 use std::env;
+use std::path::PathBuf;
 use dotenvy::dotenv;
 use sqlx::postgres::PgPoolOptions;
 use sqlx::PgPool;
@@ -9,6 +10,57 @@ pub struct AppConfig {
     pub database_url: String,
     pub jwt_secret: String,
     pub jwt_expiration_seconds: i64,
+    pub refresh_token_expiration_seconds: i64,
+    pub webhook_max_attempts: i32,
+    pub webhook_timeout_seconds: u64,
+    pub outbound_claim_timeout_seconds: i64,
+    pub sms_dedup_window_seconds: i64,
+    pub sms_reassembly_timeout_seconds: i64,
+    pub attachment_max_bytes: usize,
+    pub attachment_url_ttl_seconds: i64,
+    pub blob_store: BlobStoreConfig,
+    pub retention_interval_seconds: u64,
+    pub device_offline_after_seconds: i64,
+    pub alert_check_interval_seconds: u64,
+    pub alert_sms_silence_seconds: i64,
+    pub smtp: Option<SmtpConfig>,
+}
+
+// Where MMS attachment bytes are kept
+#[derive(Debug, Clone)]
+pub enum BlobStoreConfig {
+    Local { path: PathBuf },
+    S3(S3Config),
+}
+
+// Any S3-compatible service; objects are addressed path-style (endpoint/bucket/key)
+#[derive(Debug, Clone)]
+pub struct S3Config {
+    pub endpoint: String,
+    pub bucket: String,
+    pub region: String,
+    pub access_key_id: String,
+    pub secret_access_key: String,
+}
+
+// Outgoing mail server for email alerts
+#[derive(Debug, Clone)]
+pub struct SmtpConfig {
+    pub host: String,
+    pub port: u16,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub from: String,
+    pub tls: SmtpTls,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum SmtpTls {
+    // Plain text, for a local relay or test server
+    None,
+    StartTls,
+    // TLS from the start of the connection (usually port 465)
+    Tls,
 }
 
 #[derive(Debug, Error)]
@@ -33,15 +85,145 @@ impl AppConfig {
             .unwrap_or_else(|_| "3600".to_string()) // Default to 1 hour
             .parse::<i64>()
             .map_err(|e| ConfigError::InvalidValue("JWT_EXPIRATION_SECONDS".to_string(), e.to_string()))?;
+        let refresh_token_expiration_seconds = env::var("REFRESH_TOKEN_EXPIRATION_SECONDS")
+            .unwrap_or_else(|_| "2592000".to_string()) // Default to 30 days
+            .parse::<i64>()
+            .map_err(|e| ConfigError::InvalidValue("REFRESH_TOKEN_EXPIRATION_SECONDS".to_string(), e.to_string()))?;
+        let webhook_max_attempts = env::var("WEBHOOK_MAX_ATTEMPTS")
+            .unwrap_or_else(|_| "8".to_string())
+            .parse::<i32>()
+            .map_err(|e| ConfigError::InvalidValue("WEBHOOK_MAX_ATTEMPTS".to_string(), e.to_string()))?;
+        let webhook_timeout_seconds = env::var("WEBHOOK_TIMEOUT_SECONDS")
+            .unwrap_or_else(|_| "10".to_string())
+            .parse::<u64>()
+            .map_err(|e| ConfigError::InvalidValue("WEBHOOK_TIMEOUT_SECONDS".to_string(), e.to_string()))?;
+        let outbound_claim_timeout_seconds = env::var("OUTBOUND_CLAIM_TIMEOUT_SECONDS")
+            .unwrap_or_else(|_| "300".to_string()) // Default to 5 minutes
+            .parse::<i64>()
+            .map_err(|e| ConfigError::InvalidValue("OUTBOUND_CLAIM_TIMEOUT_SECONDS".to_string(), e.to_string()))?;
+        let sms_dedup_window_seconds = env::var("SMS_DEDUP_WINDOW_SECONDS")
+            .unwrap_or_else(|_| "60".to_string()) // 0 disables near-duplicate detection
+            .parse::<i64>()
+            .map_err(|e| ConfigError::InvalidValue("SMS_DEDUP_WINDOW_SECONDS".to_string(), e.to_string()))?;
+        let sms_reassembly_timeout_seconds = env::var("SMS_REASSEMBLY_TIMEOUT_SECONDS")
+            .unwrap_or_else(|_| "300".to_string()) // Default to 5 minutes
+            .parse::<i64>()
+            .map_err(|e| ConfigError::InvalidValue("SMS_REASSEMBLY_TIMEOUT_SECONDS".to_string(), e.to_string()))?;
+        let attachment_max_bytes = env::var("ATTACHMENT_MAX_BYTES")
+            .unwrap_or_else(|_| "5242880".to_string()) // Default to 5 MiB per attachment
+            .parse::<usize>()
+            .map_err(|e| ConfigError::InvalidValue("ATTACHMENT_MAX_BYTES".to_string(), e.to_string()))?;
+        let attachment_url_ttl_seconds = env::var("ATTACHMENT_URL_TTL_SECONDS")
+            .unwrap_or_else(|_| "3600".to_string()) // Default to 1 hour
+            .parse::<i64>()
+            .map_err(|e| ConfigError::InvalidValue("ATTACHMENT_URL_TTL_SECONDS".to_string(), e.to_string()))?;
+        let blob_store = blob_store_from_env()?;
+        let retention_interval_seconds = env::var("RETENTION_INTERVAL_SECONDS")
+            .unwrap_or_else(|_| "3600".to_string()) // Default to hourly purges
+            .parse::<u64>()
+            .map_err(|e| ConfigError::InvalidValue("RETENTION_INTERVAL_SECONDS".to_string(), e.to_string()))?;
+        let device_offline_after_seconds = env::var("DEVICE_OFFLINE_AFTER_SECONDS")
+            .unwrap_or_else(|_| "600".to_string()) // Default to 10 minutes without a heartbeat
+            .parse::<i64>()
+            .map_err(|e| ConfigError::InvalidValue("DEVICE_OFFLINE_AFTER_SECONDS".to_string(), e.to_string()))?;
+        let alert_check_interval_seconds = env::var("ALERT_CHECK_INTERVAL_SECONDS")
+            .unwrap_or_else(|_| "60".to_string()) // Default to checking every minute
+            .parse::<u64>()
+            .map_err(|e| ConfigError::InvalidValue("ALERT_CHECK_INTERVAL_SECONDS".to_string(), e.to_string()))?;
+        let alert_sms_silence_seconds = env::var("ALERT_SMS_SILENCE_SECONDS")
+            .unwrap_or_else(|_| "0".to_string()) // Default to not alerting on quiet devices
+            .parse::<i64>()
+            .map_err(|e| ConfigError::InvalidValue("ALERT_SMS_SILENCE_SECONDS".to_string(), e.to_string()))?;
+        let smtp = smtp_from_env()?;
 
         Ok(AppConfig {
             database_url,
             jwt_secret,
             jwt_expiration_seconds,
+            refresh_token_expiration_seconds,
+            webhook_max_attempts,
+            webhook_timeout_seconds,
+            outbound_claim_timeout_seconds,
+            sms_dedup_window_seconds,
+            sms_reassembly_timeout_seconds,
+            attachment_max_bytes,
+            attachment_url_ttl_seconds,
+            blob_store,
+            retention_interval_seconds,
+            device_offline_after_seconds,
+            alert_check_interval_seconds,
+            alert_sms_silence_seconds,
+            smtp,
         })
     }
 }
 
+fn blob_store_from_env() -> Result<BlobStoreConfig, ConfigError> {
+    let kind = env::var("BLOB_STORE").unwrap_or_else(|_| "local".to_string());
+
+    match kind.as_str() {
+        "local" => Ok(BlobStoreConfig::Local {
+            path: env::var("BLOB_STORE_PATH")
+                .unwrap_or_else(|_| "data/attachments".to_string())
+                .into(),
+        }),
+        "s3" => {
+            let required = |name: &str| env::var(name).map_err(|_| ConfigError::MissingVar(name.to_string()));
+
+            Ok(BlobStoreConfig::S3(S3Config {
+                endpoint: required("S3_ENDPOINT")?.trim_end_matches('/').to_string(),
+                bucket: required("S3_BUCKET")?,
+                region: env::var("S3_REGION").unwrap_or_else(|_| "us-east-1".to_string()),
+                access_key_id: required("S3_ACCESS_KEY_ID")?,
+                secret_access_key: required("S3_SECRET_ACCESS_KEY")?,
+            }))
+        }
+        other => Err(ConfigError::InvalidValue(
+            "BLOB_STORE".to_string(),
+            format!("expected local or s3, got {}", other),
+        )),
+    }
+}
+
+// Email alerts are only available when SMTP_HOST is set
+fn smtp_from_env() -> Result<Option<SmtpConfig>, ConfigError> {
+    let Some(host) = env::var("SMTP_HOST").ok().filter(|host| !host.is_empty()) else {
+        return Ok(None);
+    };
+
+    let tls = match env::var("SMTP_TLS").unwrap_or_else(|_| "starttls".to_string()).as_str() {
+        "none" => SmtpTls::None,
+        "starttls" => SmtpTls::StartTls,
+        "tls" => SmtpTls::Tls,
+        other => {
+            return Err(ConfigError::InvalidValue(
+                "SMTP_TLS".to_string(),
+                format!("expected none, starttls or tls, got {}", other),
+            ));
+        }
+    };
+    let default_port = match tls {
+        SmtpTls::None => 25,
+        SmtpTls::StartTls => 587,
+        SmtpTls::Tls => 465,
+    };
+    let port = match env::var("SMTP_PORT") {
+        Ok(port) => port
+            .parse::<u16>()
+            .map_err(|e| ConfigError::InvalidValue("SMTP_PORT".to_string(), e.to_string()))?,
+        Err(_) => default_port,
+    };
+
+    Ok(Some(SmtpConfig {
+        host,
+        port,
+        username: env::var("SMTP_USERNAME").ok(),
+        password: env::var("SMTP_PASSWORD").ok(),
+        from: env::var("SMTP_FROM").map_err(|_| ConfigError::MissingVar("SMTP_FROM".to_string()))?,
+        tls,
+    }))
+}
+
 pub async fn create_db_pool(database_url: &str) -> Result<PgPool, ConfigError> {
     PgPoolOptions::new()
         .max_connections(10)
@@ -1,12 +1,32 @@
 use chrono::{DateTime, Utc};
-use sqlx::PgPool;
+use sqlx::{PgConnection, PgPool};
 use tracing::error;
 use uuid::Uuid;
 
+use crate::auth::jwt::Claims;
 use crate::errors::AppError;
-use crate::models::sms::{NewSms, Sms};
+use crate::models::alert::{AlertChannel, AlertEvent, AlertNotification, DeviceAlert, PendingAlertNotification};
+use crate::models::attachment::Attachment;
+use crate::models::conversation::Conversation;
+use crate::models::label::{Label, SmsLabel};
+use crate::models::sms::{
+    NewSms, NewSmsPart, PartOutcome, Sms, SmsFilter, SmsFlags, SmsListItem, SmsPart, StaleSmsPartGroup,
+    StoredSms, UnnormalizedSender,
+};
 use crate::models::user::{User, NewUser};
-use crate::models::device::{NewDevice, Device};
+use crate::models::device::{AuthenticatedDevice, NewDevice, Device};
+use crate::models::heartbeat::{Heartbeat, NewHeartbeat};
+use crate::models::otp::{NewOtpPattern, OtpPattern, OtpResponse};
+use crate::models::outbound::{NewOutboundSms, OutboundSms, OutboundSmsEvent, ReportedStatus, RETRYABLE_STATUSES};
+use crate::models::retention::{
+    EffectiveRetention, PurgeBatch, RetentionPolicy, RetentionPolicyPayload, RetentionReport,
+};
+use crate::models::rule::{Rule, RuleOutcome, RulePayload};
+use crate::models::session::{NewSession, RefreshOutcome, Session};
+use crate::models::transfer::DeviceTransfer;
+use crate::models::webhook::{
+    DeliveryAttemptResult, NewWebhook, PendingDelivery, Webhook, WebhookDelivery, WebhookDeliveryAttempt,
+};
 
 pub async fn create_user(pool: &PgPool, new_user: &NewUser<'_>) -> Result<User, AppError> {
     let user = sqlx::query_as!(
@@ -22,10 +42,8 @@ pub async fn create_user(pool: &PgPool, new_user: &NewUser<'_>) -> Result<User,
     .fetch_one(pool)
     .await
     .map_err(|e| {
-         if let Some(db_err) = e.as_database_error() {
-            if db_err.is_unique_violation() {
-                return AppError::UserAlreadyExists;
-            }
+        if e.as_database_error().is_some_and(|db_err| db_err.is_unique_violation()) {
+            return AppError::UserAlreadyExists;
         }
         AppError::DatabaseError(e)
     })?;
@@ -50,42 +68,259 @@ pub async fn find_user_by_name(pool: &PgPool, username: &str) -> Result<Option<U
     Ok(user)
 }
 
-pub async fn find_user_by_id(pool: &PgPool, user_id: Uuid) -> Result<Option<User>, AppError> {
-     let user = sqlx::query_as!(
-        User,
+pub async fn create_session(pool: &PgPool, new_session: &NewSession<'_>) -> Result<Session, AppError> {
+    let mut tx = pool.begin().await?;
+
+    let session = sqlx::query_as!(
+        Session,
         r#"
-        SELECT id, username, password_hash, created_at, updated_at
-        FROM users
+        INSERT INTO sessions (user_id, user_agent, expires_at)
+        VALUES ($1, $2, $3)
+        RETURNING id, user_id, user_agent, created_at, last_used_at, expires_at
+        "#,
+        new_session.user_id,
+        new_session.user_agent,
+        new_session.expires_at,
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO session_refresh_tokens (token_hash, session_id)
+        VALUES ($1, $2)
+        "#,
+        new_session.refresh_token_hash,
+        session.id,
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(session)
+}
+
+// Exchange a refresh token for a new one. Presenting a token that was already
+// rotated revokes the session, since it means the token has leaked.
+pub async fn rotate_refresh_token(
+    pool: &PgPool,
+    token_hash: &str,
+    new_token_hash: &str,
+    expires_at: DateTime<Utc>,
+) -> Result<RefreshOutcome, AppError> {
+    let mut tx = pool.begin().await?;
+
+    let token = sqlx::query!(
+        r#"
+        SELECT t.session_id, t.used_at, s.revoked_at, s.expires_at
+        FROM session_refresh_tokens t
+        JOIN sessions s ON s.id = t.session_id
+        WHERE t.token_hash = $1
+        FOR UPDATE OF t, s
+        "#,
+        token_hash,
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    let Some(token) = token else {
+        return Ok(RefreshOutcome::Invalid);
+    };
+
+    if token.revoked_at.is_some() || token.expires_at <= Utc::now() {
+        return Ok(RefreshOutcome::Invalid);
+    }
+
+    if token.used_at.is_some() {
+        sqlx::query!(
+            "UPDATE sessions SET revoked_at = NOW() WHERE id = $1",
+            token.session_id,
+        )
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        return Ok(RefreshOutcome::Reused);
+    }
+
+    sqlx::query!(
+        "UPDATE session_refresh_tokens SET used_at = NOW() WHERE token_hash = $1",
+        token_hash,
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO session_refresh_tokens (token_hash, session_id)
+        VALUES ($1, $2)
+        "#,
+        new_token_hash,
+        token.session_id,
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    let session = sqlx::query_as!(
+        Session,
+        r#"
+        UPDATE sessions
+        SET last_used_at = NOW(), expires_at = $2
         WHERE id = $1
+        RETURNING id, user_id, user_agent, created_at, last_used_at, expires_at
+        "#,
+        token.session_id,
+        expires_at,
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(RefreshOutcome::Rotated(session))
+}
+
+pub async fn find_active_user_sessions(pool: &PgPool, user_id: Uuid) -> Result<Vec<Session>, AppError> {
+    let sessions = sqlx::query_as!(
+        Session,
+        r#"
+        SELECT id, user_id, user_agent, created_at, last_used_at, expires_at
+        FROM sessions
+        WHERE user_id = $1 AND revoked_at IS NULL AND expires_at > NOW()
+        ORDER BY last_used_at DESC
         "#,
         user_id
     )
-    .fetch_optional(pool)
+    .fetch_all(pool)
     .await
     .map_err(AppError::DatabaseError)?;
 
-    Ok(user)
+    Ok(sessions)
+}
+
+// Single round trip covering user existence, session state, the per-user
+// "valid after" cutoff and the jti denylist
+pub async fn is_access_token_valid(pool: &PgPool, claims: &Claims) -> Result<bool, AppError> {
+    let valid = sqlx::query_scalar!(
+        r#"
+        SELECT EXISTS(
+            SELECT 1
+            FROM users u
+            JOIN sessions s ON s.user_id = u.id
+            WHERE u.id = $1
+            AND s.id = $2
+            AND s.revoked_at IS NULL
+            AND s.expires_at > NOW()
+            AND (u.tokens_valid_after IS NULL OR to_timestamp($4) >= u.tokens_valid_after)
+            AND NOT EXISTS (SELECT 1 FROM revoked_tokens r WHERE r.jti = $3)
+        ) AS "valid!"
+        "#,
+        claims.sub,
+        claims.sid,
+        claims.jti,
+        claims.iat as f64,
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(AppError::DatabaseError)?;
+
+    Ok(valid)
+}
+
+pub async fn revoke_access_token(
+    pool: &PgPool,
+    jti: Uuid,
+    user_id: Uuid,
+    expires_at: DateTime<Utc>,
+) -> Result<(), AppError> {
+    sqlx::query!(
+        r#"
+        INSERT INTO revoked_tokens (jti, user_id, expires_at)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (jti) DO NOTHING
+        "#,
+        jti,
+        user_id,
+        expires_at,
+    )
+    .execute(pool)
+    .await
+    .map_err(AppError::DatabaseError)?;
+
+    Ok(())
+}
+
+// Invalidate every token issued so far and end all of the user's sessions.
+// The cutoff is truncated to whole seconds to match the JWT `iat` resolution.
+pub async fn revoke_all_user_tokens(pool: &PgPool, user_id: Uuid) -> Result<(), AppError> {
+    let mut tx = pool.begin().await?;
+
+    sqlx::query!(
+        "UPDATE users SET tokens_valid_after = date_trunc('second', NOW()) WHERE id = $1",
+        user_id,
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query!(
+        "UPDATE sessions SET revoked_at = NOW() WHERE user_id = $1 AND revoked_at IS NULL",
+        user_id,
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(())
+}
+
+pub async fn delete_expired_revoked_tokens(pool: &PgPool) -> Result<u64, AppError> {
+    let result = sqlx::query!("DELETE FROM revoked_tokens WHERE expires_at <= NOW()")
+        .execute(pool)
+        .await
+        .map_err(AppError::DatabaseError)?;
+
+    Ok(result.rows_affected())
+}
+
+// Returns false if the session doesn't exist, isn't the user's, or is already revoked
+pub async fn revoke_user_session(pool: &PgPool, session_id: Uuid, user_id: Uuid) -> Result<bool, AppError> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE sessions
+        SET revoked_at = NOW()
+        WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL
+        "#,
+        session_id,
+        user_id,
+    )
+    .execute(pool)
+    .await
+    .map_err(AppError::DatabaseError)?;
+
+    Ok(result.rows_affected() > 0)
 }
 
 pub async fn create_device(pool: &PgPool, new_device: &NewDevice<'_>) -> Result<Device, AppError> {
     let device = sqlx::query_as!(
         Device,
         r#"
-        INSERT INTO devices (user_id, device_name)
-        VALUES ($1, $2)
-        RETURNING id, user_id, device_name, created_at, updated_at
+        INSERT INTO devices (user_id, device_name, default_region, ingest_secret_hash, ingest_secret_created_at)
+        VALUES ($1, $2, $3, $4, NOW())
+        RETURNING id, user_id, device_name, default_region, retired_at, last_seen_at, created_at, updated_at
         "#,
         new_device.user_id,
         new_device.device_name,
+        new_device.default_region,
+        new_device.ingest_secret_hash,
     )
     .fetch_one(pool)
     .await
     .map_err(|e| {
         error!("Failed to insert device: {:?}", e);
-        if let Some(db_err) = e.as_database_error() {
-            if db_err.is_unique_violation() {
-                return AppError::DeviceAlreadyExists;
-            }
+        if e.as_database_error().is_some_and(|db_err| db_err.is_unique_violation()) {
+            return AppError::DeviceAlreadyExists;
         }
         AppError::DatabaseError(e)
     })?;
@@ -97,7 +332,7 @@ pub async fn find_user_devices(pool: &PgPool, user_id: Uuid) -> Result<Vec<Devic
     let devices = sqlx::query_as!(
        Device,
        r#"
-       SELECT id, device_name, user_id, created_at, updated_at
+       SELECT id, device_name, user_id, default_region, retired_at, last_seen_at, created_at, updated_at
        FROM devices
        WHERE user_id = $1
        "#,
@@ -110,66 +345,3149 @@ pub async fn find_user_devices(pool: &PgPool, user_id: Uuid) -> Result<Vec<Devic
    Ok(devices)
 }
 
-pub async fn create_sms(pool: &PgPool, sms: &NewSms<'_>) -> Result<Sms, AppError> {
-    let sms = sqlx::query_as!(
-        Sms,
+// Unread messages per device; devices with none are left out. Like the
+// conversation list, archived messages and spam don't count as unread.
+pub async fn count_user_unread_sms(pool: &PgPool, user_id: Uuid) -> Result<Vec<(Uuid, i64)>, AppError> {
+    let rows = sqlx::query!(
         r#"
-        INSERT INTO sms (device_id, sender, message)
-        VALUES ($1, $2, $3)
-        RETURNING id, device_id, sender, message, received_at
+        SELECT s.device_id, COUNT(*) AS "unread_count!"
+        FROM sms s
+        JOIN devices d ON d.id = s.device_id
+        WHERE d.user_id = $1 AND NOT s.is_read AND NOT s.is_archived AND NOT s.is_spam
+        GROUP BY s.device_id
         "#,
-        sms.device_id,
-        sms.sender,
-        sms.message
+        user_id
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(AppError::DatabaseError)?;
+
+    Ok(rows.into_iter().map(|row| (row.device_id, row.unread_count)).collect())
+}
+
+pub async fn find_user_device(pool: &PgPool, device_id: Uuid, user_id: Uuid) -> Result<Option<Device>, AppError> {
+    let device = sqlx::query_as!(
+        Device,
+        r#"
+        SELECT id, device_name, user_id, default_region, retired_at, last_seen_at, created_at, updated_at
+        FROM devices
+        WHERE id = $1 AND user_id = $2
+        "#,
+        device_id,
+        user_id
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(AppError::DatabaseError)?;
+
+    Ok(device)
+}
+
+// Fields left as None keep their current value. Retired devices can't be changed.
+pub async fn update_user_device(
+    pool: &PgPool,
+    device_id: Uuid,
+    user_id: Uuid,
+    device_name: Option<&str>,
+    default_region: Option<&str>,
+) -> Result<Option<Device>, AppError> {
+    let device = sqlx::query_as!(
+        Device,
+        r#"
+        UPDATE devices
+        SET device_name = COALESCE($3, device_name),
+            default_region = COALESCE($4, default_region)
+        WHERE id = $1 AND user_id = $2 AND retired_at IS NULL
+        RETURNING id, device_name, user_id, default_region, retired_at, last_seen_at, created_at, updated_at
+        "#,
+        device_id,
+        user_id,
+        device_name,
+        default_region,
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| {
+        if e.as_database_error().is_some_and(|db_err| db_err.is_unique_violation()) {
+            return AppError::DeviceAlreadyExists;
+        }
+        AppError::DatabaseError(e)
+    })?;
+
+    Ok(device)
+}
+
+// Replace (or clear, when `secret_hash` is None) a device's ingest secret.
+// Returns false if the device doesn't exist, isn't owned by the user or is retired.
+pub async fn set_device_ingest_secret(
+    pool: &PgPool,
+    device_id: Uuid,
+    user_id: Uuid,
+    secret_hash: Option<&str>,
+) -> Result<bool, AppError> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE devices
+        SET ingest_secret_hash = $3,
+            ingest_secret_created_at = CASE WHEN $3::varchar IS NULL THEN NULL ELSE NOW() END
+        WHERE id = $1 AND user_id = $2 AND retired_at IS NULL
+        "#,
+        device_id,
+        user_id,
+        secret_hash,
+    )
+    .execute(pool)
+    .await
+    .map_err(AppError::DatabaseError)?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+// Active devices that can't ingest because they were registered before
+// ingest secrets existed (or had theirs revoked).
+pub async fn count_devices_without_ingest_secret(pool: &PgPool) -> Result<i64, AppError> {
+    let count = sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(*) AS "count!"
+        FROM devices
+        WHERE ingest_secret_hash IS NULL AND retired_at IS NULL
+        "#
     )
     .fetch_one(pool)
     .await
     .map_err(AppError::DatabaseError)?;
 
-    Ok(sms)
+    Ok(count)
 }
 
-pub async fn get_sms_by_device_with_filters(
+pub async fn find_device_by_ingest_secret(
     pool: &PgPool,
     device_id: Uuid,
-    from: Option<DateTime<Utc>>,
-    to: Option<DateTime<Utc>>,
-    limit: i64,
-    offset: i64,
-) -> Result<(Vec<Sms>, i64), AppError> {
-    let rows = sqlx::query_as!(
-        Sms,
+    secret_hash: &str,
+) -> Result<Option<AuthenticatedDevice>, AppError> {
+    let device = sqlx::query_as!(
+        AuthenticatedDevice,
         r#"
-        SELECT id, device_id, sender, message, received_at
-        FROM sms
+        SELECT id AS device_id, user_id, default_region
+        FROM devices
+        WHERE id = $1 AND ingest_secret_hash = $2 AND retired_at IS NULL
+        "#,
+        device_id,
+        secret_hash
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(AppError::DatabaseError)?;
+
+    Ok(device)
+}
+
+// Stores a heartbeat, marks the device as seen and drops all but its newest
+// `keep` heartbeats
+pub async fn record_heartbeat(pool: &PgPool, heartbeat: &NewHeartbeat<'_>, keep: i64) -> Result<Heartbeat, AppError> {
+    let mut tx = pool.begin().await?;
+
+    let stored = sqlx::query_as!(
+        Heartbeat,
+        r#"
+        INSERT INTO device_heartbeats (device_id, battery_level, is_charging, signal_strength, network_type,
+                                       app_version, sim_operator, sim_country)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        RETURNING device_id, battery_level, is_charging, signal_strength, network_type,
+                  app_version, sim_operator, sim_country, received_at
+        "#,
+        heartbeat.device_id,
+        heartbeat.battery_level,
+        heartbeat.is_charging,
+        heartbeat.signal_strength,
+        heartbeat.network_type,
+        heartbeat.app_version,
+        heartbeat.sim_operator,
+        heartbeat.sim_country,
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    sqlx::query!(
+        "UPDATE devices SET last_seen_at = $2 WHERE id = $1",
+        heartbeat.device_id,
+        stored.received_at,
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query!(
+        r#"
+        DELETE FROM device_heartbeats
+        WHERE device_id = $1 AND id NOT IN (
+            SELECT id FROM device_heartbeats
+            WHERE device_id = $1
+            ORDER BY received_at DESC
+            LIMIT $2
+        )
+        "#,
+        heartbeat.device_id,
+        keep,
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(stored)
+}
+
+// The newest heartbeat of each of the user's devices that has sent one
+pub async fn find_latest_heartbeats(pool: &PgPool, user_id: Uuid) -> Result<Vec<Heartbeat>, AppError> {
+    let heartbeats = sqlx::query_as!(
+        Heartbeat,
+        r#"
+        SELECT DISTINCT ON (h.device_id)
+               h.device_id, h.battery_level, h.is_charging, h.signal_strength, h.network_type,
+               h.app_version, h.sim_operator, h.sim_country, h.received_at
+        FROM device_heartbeats h
+        JOIN devices d ON d.id = h.device_id
+        WHERE d.user_id = $1
+        ORDER BY h.device_id, h.received_at DESC
+        "#,
+        user_id
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(AppError::DatabaseError)?;
+
+    Ok(heartbeats)
+}
+
+pub async fn find_device_heartbeats(pool: &PgPool, device_id: Uuid, limit: i64) -> Result<Vec<Heartbeat>, AppError> {
+    let heartbeats = sqlx::query_as!(
+        Heartbeat,
+        r#"
+        SELECT device_id, battery_level, is_charging, signal_strength, network_type,
+               app_version, sim_operator, sim_country, received_at
+        FROM device_heartbeats
         WHERE device_id = $1
-        AND ($2::timestamptz IS NULL OR received_at >= $2)
-        AND ($3::timestamptz IS NULL OR received_at <= $3)
         ORDER BY received_at DESC
-        LIMIT $4 OFFSET $5
+        LIMIT $2
         "#,
         device_id,
-        from,
-        to,
-        limit,
-        offset,
+        limit
     )
     .fetch_all(pool)
-    .await?;
+    .await
+    .map_err(AppError::DatabaseError)?;
 
-    let total = sqlx::query_scalar!(
+    Ok(heartbeats)
+}
+
+// Deletes a device along with its messages, returning the storage keys of
+// their attachments, or None if the user has no such device
+pub async fn delete_user_device(pool: &PgPool, device_id: Uuid, user_id: Uuid) -> Result<Option<Vec<String>>, AppError> {
+    let row = sqlx::query!(
         r#"
-        SELECT COUNT(*) FROM sms
-        WHERE device_id = $1
-        AND ($2::timestamptz IS NULL OR received_at >= $2)
-        AND ($3::timestamptz IS NULL OR received_at <= $3)
+        WITH keys AS (
+            SELECT a.storage_key
+            FROM sms_attachments a
+            JOIN sms s ON s.id = a.sms_id
+            WHERE s.device_id = $1
+        ),
+        gone AS (
+            DELETE FROM devices WHERE id = $1 AND user_id = $2 RETURNING id
+        )
+        SELECT EXISTS (SELECT 1 FROM gone) AS "deleted!",
+               ARRAY(SELECT storage_key FROM keys) AS "storage_keys!"
         "#,
         device_id,
-        from,
-        to,
+        user_id,
     )
     .fetch_one(pool)
+    .await
+    .map_err(AppError::DatabaseError)?;
+
+    Ok(row.deleted.then_some(row.storage_keys))
+}
+
+// Retires a device: its messages stay, its ingest secret is revoked and any
+// pending transfer or unsent outbound message is cancelled
+pub async fn retire_user_device(pool: &PgPool, device_id: Uuid, user_id: Uuid) -> Result<bool, AppError> {
+    let mut tx = pool.begin().await?;
+
+    let result = sqlx::query!(
+        r#"
+        UPDATE devices
+        SET retired_at = NOW(), ingest_secret_hash = NULL, ingest_secret_created_at = NULL
+        WHERE id = $1 AND user_id = $2 AND retired_at IS NULL
+        "#,
+        device_id,
+        user_id,
+    )
+    .execute(&mut *tx)
     .await?;
 
-    Ok((rows, total.unwrap_or(0)))
+    if result.rows_affected() == 0 {
+        return Ok(false);
+    }
+
+    sqlx::query!(
+        r#"
+        UPDATE device_transfers
+        SET status = 'cancelled', resolved_at = NOW()
+        WHERE device_id = $1 AND status = 'pending'
+        "#,
+        device_id,
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    cancel_pending_outbound(&mut tx, device_id, "Device was retired").await?;
+
+    tx.commit().await?;
+
+    Ok(true)
+}
+
+// Cancels whatever the device was still due to send, claimed or not, so it
+// isn't sent by a phone its sender no longer controls
+async fn cancel_pending_outbound(conn: &mut PgConnection, device_id: Uuid, reason: &str) -> Result<(), AppError> {
+    sqlx::query!(
+        r#"
+        WITH cancelled AS (
+            UPDATE outbound_sms
+            SET status = 'cancelled', error = $2
+            WHERE device_id = $1 AND status IN ('queued', 'claimed')
+            RETURNING id, error
+        )
+        INSERT INTO outbound_sms_events (outbound_sms_id, status, error)
+        SELECT id, 'cancelled', error FROM cancelled
+        "#,
+        device_id,
+        reason,
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+// `to_user_id` is None when no account has the name the device was offered
+// to; the offer is recorded all the same, but nobody can accept it
+pub async fn create_device_transfer(
+    pool: &PgPool,
+    device_id: Uuid,
+    from_user_id: Uuid,
+    to_user_id: Option<Uuid>,
+    to_username: &str,
+    include_history: bool,
+) -> Result<DeviceTransfer, AppError> {
+    let transfer = sqlx::query_as!(
+        DeviceTransfer,
+        r#"
+        WITH t AS (
+            INSERT INTO device_transfers (device_id, from_user_id, to_user_id, to_username, include_history)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING *
+        )
+        SELECT t.id, t.device_id, d.device_name, f.username AS from_username,
+               t.to_user_id, t.to_username, t.include_history, t.status, t.created_at, t.resolved_at
+        FROM t
+        JOIN devices d ON d.id = t.device_id
+        JOIN users f ON f.id = t.from_user_id
+        "#,
+        device_id,
+        from_user_id,
+        to_user_id,
+        to_username,
+        include_history,
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(|e| {
+        if e.as_database_error().is_some_and(|db_err| db_err.is_unique_violation()) {
+            return AppError::Conflict("Device already has a pending transfer".to_string());
+        }
+        AppError::DatabaseError(e)
+    })?;
+
+    Ok(transfer)
+}
+
+// Transfers the user has sent or received, newest first
+pub async fn find_user_transfers(pool: &PgPool, user_id: Uuid) -> Result<Vec<DeviceTransfer>, AppError> {
+    let transfers = sqlx::query_as!(
+        DeviceTransfer,
+        r#"
+        SELECT t.id, t.device_id, d.device_name, f.username AS from_username,
+               t.to_user_id, t.to_username, t.include_history, t.status, t.created_at, t.resolved_at
+        FROM device_transfers t
+        JOIN devices d ON d.id = t.device_id
+        JOIN users f ON f.id = t.from_user_id
+        WHERE t.from_user_id = $1 OR t.to_user_id = $1
+        ORDER BY t.created_at DESC
+        "#,
+        user_id
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(AppError::DatabaseError)?;
+
+    Ok(transfers)
+}
+
+// Hands the device to the receiving user with a fresh ingest secret (and new
+// name, if given), and cancels what it was due to send. The previous owner's rules, retention
+// override and alert history for the device are dropped. Messages the
+// previous owner sent always stay with them on a retired copy of the device,
+// so the new owner can't retry them. Unless the transfer includes the history,
+// received messages go to that copy too; if it does, their labels and webhook
+// deliveries are dropped instead. Returns None unless `user_id` is the
+// recipient of a transfer that is still pending.
+pub async fn accept_device_transfer(
+    pool: &PgPool,
+    transfer_id: Uuid,
+    user_id: Uuid,
+    secret_hash: &str,
+    device_name: Option<&str>,
+) -> Result<Option<DeviceTransfer>, AppError> {
+    let mut tx = pool.begin().await?;
+
+    let Some(transfer) = sqlx::query!(
+        r#"
+        UPDATE device_transfers
+        SET status = 'accepted', resolved_at = NOW()
+        WHERE id = $1 AND to_user_id = $2 AND status = 'pending'
+        RETURNING device_id, from_user_id, include_history
+        "#,
+        transfer_id,
+        user_id,
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    else {
+        return Ok(None);
+    };
+
+    // Copied before the device moves, so the copy keeps the sender's name for
+    // it. Not needed when the history goes along and nothing was sent.
+    let archive_id = sqlx::query_scalar!(
+        r#"
+        INSERT INTO devices (user_id, device_name, default_region, created_at, retired_at)
+        SELECT user_id, device_name, default_region, created_at, NOW()
+        FROM devices
+        WHERE id = $1 AND user_id = $2 AND retired_at IS NULL
+        AND (NOT $3 OR EXISTS (SELECT 1 FROM outbound_sms WHERE device_id = $1))
+        RETURNING id
+        "#,
+        transfer.device_id,
+        transfer.from_user_id,
+        transfer.include_history,
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    let moved = sqlx::query!(
+        r#"
+        UPDATE devices
+        SET user_id = $3, ingest_secret_hash = $4, ingest_secret_created_at = NOW(),
+            device_name = COALESCE($5, device_name)
+        WHERE id = $1 AND user_id = $2 AND retired_at IS NULL
+        "#,
+        transfer.device_id,
+        transfer.from_user_id,
+        user_id,
+        secret_hash,
+        device_name,
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| {
+        if e.as_database_error().is_some_and(|db_err| db_err.is_unique_violation()) {
+            return AppError::Conflict(
+                "You already have a device with this name; give a device_name to rename it".to_string(),
+            );
+        }
+        AppError::DatabaseError(e)
+    })?;
+
+    if moved.rows_affected() == 0 {
+        return Ok(None);
+    }
+
+    sqlx::query!(
+        "DELETE FROM rules WHERE device_id = $1 AND user_id = $2",
+        transfer.device_id,
+        transfer.from_user_id,
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query!("DELETE FROM retention_policies WHERE device_id = $1", transfer.device_id)
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query!("DELETE FROM device_alerts WHERE device_id = $1", transfer.device_id)
+        .execute(&mut *tx)
+        .await?;
+
+    cancel_pending_outbound(&mut tx, transfer.device_id, "Device was transferred to another user").await?;
+
+    if let Some(archive_id) = archive_id {
+        sqlx::query!("UPDATE outbound_sms SET device_id = $2 WHERE device_id = $1", transfer.device_id, archive_id)
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    if transfer.include_history {
+        sqlx::query!(
+            "DELETE FROM sms_labels WHERE sms_id IN (SELECT id FROM sms WHERE device_id = $1)",
+            transfer.device_id,
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            "DELETE FROM webhook_deliveries WHERE sms_id IN (SELECT id FROM sms WHERE device_id = $1)",
+            transfer.device_id,
+        )
+        .execute(&mut *tx)
+        .await?;
+    } else if let Some(archive_id) = archive_id {
+        sqlx::query!("UPDATE sms SET device_id = $2 WHERE device_id = $1", transfer.device_id, archive_id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query!("UPDATE sms_parts SET device_id = $2 WHERE device_id = $1", transfer.device_id, archive_id)
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    let transfer = sqlx::query_as!(
+        DeviceTransfer,
+        r#"
+        SELECT t.id, t.device_id, d.device_name, f.username AS from_username,
+               t.to_user_id, t.to_username, t.include_history, t.status, t.created_at, t.resolved_at
+        FROM device_transfers t
+        JOIN devices d ON d.id = t.device_id
+        JOIN users f ON f.id = t.from_user_id
+        WHERE t.id = $1
+        "#,
+        transfer_id
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(Some(transfer))
+}
+
+// Closes a pending transfer without moving the device: declined by the
+// recipient, or cancelled by the sender
+pub async fn close_device_transfer(
+    pool: &PgPool,
+    transfer_id: Uuid,
+    user_id: Uuid,
+    status: &str,
+) -> Result<bool, AppError> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE device_transfers
+        SET status = $3, resolved_at = NOW()
+        WHERE id = $1 AND status = 'pending'
+        AND CASE WHEN $3 = 'declined' THEN to_user_id ELSE from_user_id END = $2
+        "#,
+        transfer_id,
+        user_id,
+        status,
+    )
+    .execute(pool)
+    .await
+    .map_err(AppError::DatabaseError)?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+// Stores the SMS with the effects of the owner's rules applied, and queues it
+// for delivery: to rule-selected webhooks, plus catch-all webhooks unless the
+// message was marked as spam. Messages a rule discards never get here.
+//
+// Uploads are deduplicated per device: by client message id when the phone
+// sends one, otherwise by identical sender and body within `dedup_window_seconds`
+// (of the device-reported receive time when given, so a replayed backlog
+// keeps repeated messages that arrived far apart).
+pub async fn create_sms(
+    pool: &PgPool,
+    new_sms: &NewSms<'_>,
+    routing: &RuleOutcome,
+    dedup_window_seconds: f64,
+) -> Result<StoredSms, AppError> {
+    let mut tx = pool.begin().await?;
+
+    // Reassembled messages were already deduplicated part by part, and MMS
+    // with the same (often empty) text can still differ in their attachments
+    let check_recent = new_sms.client_message_id.is_none()
+        && new_sms.part_ids.is_empty()
+        && new_sms.attachments.is_empty();
+
+    if check_recent && dedup_window_seconds > 0.0 {
+        // Serialize uploads from this device so concurrent retries can't both pass the check
+        sqlx::query!(
+            "SELECT pg_advisory_xact_lock(hashtextextended($1::uuid::text, 0))",
+            new_sms.device_id,
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        let recent = sqlx::query_scalar!(
+            r#"
+            SELECT id FROM sms
+            WHERE device_id = $1 AND counterpart = $2 AND message = $3
+            AND CASE
+                WHEN $5::timestamptz IS NULL THEN received_at > NOW() - make_interval(secs => $4)
+                ELSE device_received_at BETWEEN $5 - make_interval(secs => $4) AND $5 + make_interval(secs => $4)
+            END
+            ORDER BY received_at DESC
+            LIMIT 1
+            "#,
+            new_sms.device_id,
+            new_sms.counterpart,
+            new_sms.message,
+            dedup_window_seconds,
+            new_sms.device_received_at,
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        if let Some(id) = recent {
+            return Ok(StoredSms::Duplicate(id));
+        }
+    }
+
+    // Held until commit, so the owner's messages take their seq in commit
+    // order and a stream cursor never skips one that commits late
+    sqlx::query!(
+        "SELECT pg_advisory_xact_lock(hashtextextended(user_id::text, 1)) FROM devices WHERE id = $1",
+        new_sms.device_id,
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    let inserted = sqlx::query_as!(
+        Sms,
+        r#"
+        INSERT INTO sms (device_id, sender, raw_sender, sender_type, counterpart, message, tags, is_spam,
+                         otp_code, otp_issuer, client_message_id, device_received_at, smsc_timestamp,
+                         part_count, is_partial)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15)
+        ON CONFLICT (device_id, client_message_id) WHERE client_message_id IS NOT NULL DO NOTHING
+        RETURNING id, device_id, sender, raw_sender, sender_type, counterpart, message,
+                  received_at, tags, is_spam, is_read, is_starred, is_archived, otp_code, otp_issuer,
+                  device_received_at, smsc_timestamp, part_count, is_partial
+        "#,
+        new_sms.device_id,
+        new_sms.sender,
+        new_sms.raw_sender,
+        new_sms.sender_type,
+        new_sms.counterpart,
+        new_sms.message,
+        &routing.tags,
+        routing.spam,
+        new_sms.otp_code,
+        new_sms.otp_issuer,
+        new_sms.client_message_id,
+        new_sms.device_received_at,
+        new_sms.smsc_timestamp,
+        new_sms.part_count,
+        new_sms.is_partial,
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(AppError::DatabaseError)?;
+
+    let Some(sms) = inserted else {
+        let original = sqlx::query_scalar!(
+            "SELECT id FROM sms WHERE device_id = $1 AND client_message_id = $2",
+            new_sms.device_id,
+            new_sms.client_message_id,
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        // A message re-sent in parts is the original; its parts are done with
+        if !new_sms.part_ids.is_empty() {
+            sqlx::query!(
+                "UPDATE sms_parts SET sms_id = $1 WHERE id = ANY($2)",
+                original,
+                new_sms.part_ids,
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+
+        return Ok(StoredSms::Duplicate(original));
+    };
+
+    if !new_sms.part_ids.is_empty() {
+        sqlx::query!(
+            "UPDATE sms_parts SET sms_id = $1 WHERE id = ANY($2)",
+            sms.id,
+            new_sms.part_ids,
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    if !routing.labels.is_empty() {
+        sqlx::query!(
+            "INSERT INTO sms_labels (sms_id, label_id) SELECT $1, UNNEST($2::uuid[]) ON CONFLICT DO NOTHING",
+            sms.id,
+            &routing.labels,
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    for attachment in new_sms.attachments {
+        sqlx::query!(
+            r#"
+            INSERT INTO sms_attachments (id, sms_id, position, content_type, size_bytes, filename, storage_key)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            "#,
+            attachment.id,
+            sms.id,
+            attachment.position,
+            attachment.content_type,
+            attachment.size_bytes,
+            attachment.filename,
+            attachment.storage_key,
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    sqlx::query!(
+        r#"
+        INSERT INTO webhook_deliveries (webhook_id, sms_id)
+        SELECT w.id, $1
+        FROM webhooks w
+        JOIN devices d ON d.user_id = w.user_id
+        WHERE d.id = $2
+        AND w.enabled
+        AND ((w.forward_all AND NOT $4) OR w.id = ANY($3))
+        "#,
+        sms.id,
+        sms.device_id,
+        &routing.forward_to,
+        sms.is_spam,
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(StoredSms::Created(Box::new(sms)))
+}
+
+// Messages stored before senders were normalized, with what's needed to redo it
+pub async fn find_unnormalized_senders(pool: &PgPool, limit: i64) -> Result<Vec<UnnormalizedSender>, AppError> {
+    let rows = sqlx::query_as!(
+        UnnormalizedSender,
+        r#"
+        SELECT s.id, s.raw_sender, d.default_region
+        FROM sms s
+        JOIN devices d ON d.id = s.device_id
+        WHERE NOT s.sender_normalized
+        LIMIT $1
+        "#,
+        limit,
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(AppError::DatabaseError)?;
+
+    Ok(rows)
+}
+
+pub async fn update_normalized_senders(
+    pool: &PgPool,
+    ids: &[Uuid],
+    senders: &[String],
+    sender_types: &[String],
+    counterparts: &[String],
+) -> Result<u64, AppError> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE sms s
+        SET sender = n.sender, sender_type = n.sender_type, counterpart = n.counterpart, sender_normalized = TRUE
+        FROM UNNEST($1::uuid[], $2::text[], $3::text[], $4::text[]) AS n(id, sender, sender_type, counterpart)
+        WHERE s.id = n.id
+        "#,
+        ids,
+        senders,
+        sender_types,
+        counterparts,
+    )
+    .execute(pool)
+    .await
+    .map_err(AppError::DatabaseError)?;
+
+    Ok(result.rows_affected())
+}
+
+pub async fn find_sms_attachments(pool: &PgPool, sms_ids: &[Uuid]) -> Result<Vec<Attachment>, AppError> {
+    let attachments = sqlx::query_as!(
+        Attachment,
+        r#"
+        SELECT id, sms_id, content_type, size_bytes, filename, storage_key, created_at
+        FROM sms_attachments
+        WHERE sms_id = ANY($1)
+        ORDER BY sms_id, position
+        "#,
+        sms_ids,
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(AppError::DatabaseError)?;
+
+    Ok(attachments)
+}
+
+pub async fn find_attachment_by_id(pool: &PgPool, attachment_id: Uuid) -> Result<Option<Attachment>, AppError> {
+    let attachment = sqlx::query_as!(
+        Attachment,
+        r#"
+        SELECT id, sms_id, content_type, size_bytes, filename, storage_key, created_at
+        FROM sms_attachments
+        WHERE id = $1
+        "#,
+        attachment_id,
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(AppError::DatabaseError)?;
+
+    Ok(attachment)
+}
+
+pub async fn find_sms_by_id(pool: &PgPool, sms_id: Uuid) -> Result<Option<Sms>, AppError> {
+    let sms = sqlx::query_as!(
+        Sms,
+        r#"
+        SELECT id, device_id, sender, raw_sender, sender_type, counterpart, message,
+               received_at, tags, is_spam, is_read, is_starred, is_archived, otp_code, otp_issuer,
+               device_received_at, smsc_timestamp, part_count, is_partial
+        FROM sms
+        WHERE id = $1
+        "#,
+        sms_id
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(AppError::DatabaseError)?;
+
+    Ok(sms)
+}
+
+// Lists messages across the user's devices (or one device). When the filter
+// has a tsquery, rows are ranked and get a snippet. `from`/`to` and the
+// ordering use the filter's clock; messages lacking a device or SMSC
+// timestamp fall back to the server's receive time.
+pub async fn get_user_sms_with_filters(
+    pool: &PgPool,
+    user_id: Uuid,
+    filter: &SmsFilter,
+    limit: i64,
+    offset: i64,
+) -> Result<(Vec<SmsListItem>, i64), AppError> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT s.id, s.device_id, s.sender, s.raw_sender, s.sender_type, s.counterpart, s.message,
+               s.received_at, s.tags, s.is_spam, s.is_read, s.is_starred, s.is_archived, s.otp_code, s.otp_issuer,
+               s.device_received_at, s.smsc_timestamp, s.part_count, s.is_partial,
+               CASE WHEN $3::text IS NULL THEN NULL
+                    ELSE ts_rank_cd(s.search_vector, to_tsquery('simple', $3)) END AS rank,
+               CASE WHEN $3::text IS NULL THEN NULL
+                    ELSE ts_headline('simple', s.message, to_tsquery('simple', $3),
+                                     'StartSel=<mark>, StopSel=</mark>, MaxFragments=2, MaxWords=20, MinWords=5') END AS snippet
+        FROM user_sms_matching($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12) m
+        JOIN sms s ON s.id = m.id
+        ORDER BY rank DESC NULLS LAST, m.at DESC
+        LIMIT $13 OFFSET $14
+        "#,
+        user_id,
+        filter.device_id,
+        filter.tsquery,
+        filter.from,
+        filter.to,
+        filter.counterpart,
+        filter.sender_type.map(|t| t.as_str()),
+        filter.clock.as_str(),
+        filter.is_read,
+        filter.is_starred,
+        filter.is_archived,
+        filter.label_id,
+        limit,
+        offset,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let items = rows
+        .into_iter()
+        .map(|row| SmsListItem {
+            sms: Sms {
+                id: row.id,
+                device_id: row.device_id,
+                sender: row.sender,
+                raw_sender: row.raw_sender,
+                sender_type: row.sender_type,
+                counterpart: row.counterpart,
+                message: row.message,
+                received_at: row.received_at,
+                tags: row.tags,
+                is_spam: row.is_spam,
+                is_read: row.is_read,
+                is_starred: row.is_starred,
+                is_archived: row.is_archived,
+                otp_code: row.otp_code,
+                otp_issuer: row.otp_issuer,
+                device_received_at: row.device_received_at,
+                smsc_timestamp: row.smsc_timestamp,
+                part_count: row.part_count,
+                is_partial: row.is_partial,
+            },
+            rank: row.rank,
+            snippet: row.snippet,
+            attachments: Vec::new(),
+            labels: Vec::new(),
+        })
+        .collect();
+
+    let total = sqlx::query_scalar!(
+        "SELECT COUNT(*) FROM user_sms_matching($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)",
+        user_id,
+        filter.device_id,
+        filter.tsquery,
+        filter.from,
+        filter.to,
+        filter.counterpart,
+        filter.sender_type.map(|t| t.as_str()),
+        filter.clock.as_str(),
+        filter.is_read,
+        filter.is_starred,
+        filter.is_archived,
+        filter.label_id,
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok((items, total.unwrap_or(0)))
+}
+
+// Sets flags on the listed messages the user owns; ids of other users'
+// messages are ignored. Returns how many messages changed.
+pub async fn update_user_sms_flags_by_ids(
+    pool: &PgPool,
+    user_id: Uuid,
+    ids: &[Uuid],
+    flags: &SmsFlags,
+) -> Result<u64, AppError> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE sms s
+        SET is_read = COALESCE($3, s.is_read),
+            is_starred = COALESCE($4, s.is_starred),
+            is_archived = COALESCE($5, s.is_archived)
+        FROM devices d
+        WHERE d.id = s.device_id AND d.user_id = $1
+        AND s.id = ANY($2)
+        AND (s.is_read <> COALESCE($3, s.is_read)
+             OR s.is_starred <> COALESCE($4, s.is_starred)
+             OR s.is_archived <> COALESCE($5, s.is_archived))
+        "#,
+        user_id,
+        ids,
+        flags.is_read,
+        flags.is_starred,
+        flags.is_archived,
+    )
+    .execute(pool)
+    .await
+    .map_err(AppError::DatabaseError)?;
+
+    Ok(result.rows_affected())
+}
+
+// Sets flags on every message matching the filter, as `get_user_sms_with_filters` would list them
+pub async fn update_user_sms_flags_by_filter(
+    pool: &PgPool,
+    user_id: Uuid,
+    filter: &SmsFilter,
+    flags: &SmsFlags,
+) -> Result<u64, AppError> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE sms
+        SET is_read = COALESCE($13, is_read),
+            is_starred = COALESCE($14, is_starred),
+            is_archived = COALESCE($15, is_archived)
+        WHERE id IN (SELECT id FROM user_sms_matching($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12))
+        AND (is_read <> COALESCE($13, is_read)
+             OR is_starred <> COALESCE($14, is_starred)
+             OR is_archived <> COALESCE($15, is_archived))
+        "#,
+        user_id,
+        filter.device_id,
+        filter.tsquery,
+        filter.from,
+        filter.to,
+        filter.counterpart,
+        filter.sender_type.map(|t| t.as_str()),
+        filter.clock.as_str(),
+        filter.is_read,
+        filter.is_starred,
+        filter.is_archived,
+        filter.label_id,
+        flags.is_read,
+        flags.is_starred,
+        flags.is_archived,
+    )
+    .execute(pool)
+    .await
+    .map_err(AppError::DatabaseError)?;
+
+    Ok(result.rows_affected())
+}
+
+// Messages stored after the `after` cursor, in the order they were committed,
+// for stream resumption
+pub async fn get_user_sms_after(
+    pool: &PgPool,
+    user_id: Uuid,
+    device_id: Option<Uuid>,
+    after: Uuid,
+    limit: i64,
+) -> Result<Option<Vec<Sms>>, AppError> {
+    // The cursor may have been purged, or never belonged to this user
+    let cursor_known = sqlx::query_scalar!(
+        r#"
+        SELECT EXISTS (
+            SELECT 1 FROM sms s
+            JOIN devices d ON d.id = s.device_id
+            WHERE s.id = $1 AND d.user_id = $2
+        ) AS "exists!"
+        "#,
+        after,
+        user_id,
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(AppError::DatabaseError)?;
+
+    if !cursor_known {
+        return Ok(None);
+    }
+
+    let rows = sqlx::query_as!(
+        Sms,
+        r#"
+        SELECT s.id, s.device_id, s.sender, s.raw_sender, s.sender_type, s.counterpart, s.message,
+               s.received_at, s.tags, s.is_spam, s.is_read, s.is_starred, s.is_archived, s.otp_code, s.otp_issuer,
+               s.device_received_at, s.smsc_timestamp, s.part_count, s.is_partial
+        FROM sms s
+        JOIN devices d ON d.id = s.device_id
+        WHERE d.user_id = $1
+        AND ($2::uuid IS NULL OR s.device_id = $2)
+        AND s.seq > (SELECT c.seq FROM sms c WHERE c.id = $3)
+        ORDER BY s.seq
+        LIMIT $4
+        "#,
+        user_id,
+        device_id,
+        after,
+        limit,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(Some(rows))
+}
+
+pub async fn create_webhook(pool: &PgPool, new_webhook: &NewWebhook<'_>) -> Result<Webhook, AppError> {
+    let webhook = sqlx::query_as!(
+        Webhook,
+        r#"
+        INSERT INTO webhooks (user_id, url, description, secret, forward_all)
+        VALUES ($1, $2, $3, $4, $5)
+        RETURNING id, user_id, url, description, enabled, forward_all, created_at, updated_at
+        "#,
+        new_webhook.user_id,
+        new_webhook.url,
+        new_webhook.description,
+        new_webhook.secret,
+        new_webhook.forward_all,
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(AppError::DatabaseError)?;
+
+    Ok(webhook)
+}
+
+pub async fn find_user_webhooks(pool: &PgPool, user_id: Uuid) -> Result<Vec<Webhook>, AppError> {
+    let webhooks = sqlx::query_as!(
+        Webhook,
+        r#"
+        SELECT id, user_id, url, description, enabled, forward_all, created_at, updated_at
+        FROM webhooks
+        WHERE user_id = $1
+        ORDER BY created_at
+        "#,
+        user_id
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(AppError::DatabaseError)?;
+
+    Ok(webhooks)
+}
+
+pub async fn find_user_webhook(pool: &PgPool, webhook_id: Uuid, user_id: Uuid) -> Result<Option<Webhook>, AppError> {
+    let webhook = sqlx::query_as!(
+        Webhook,
+        r#"
+        SELECT id, user_id, url, description, enabled, forward_all, created_at, updated_at
+        FROM webhooks
+        WHERE id = $1 AND user_id = $2
+        "#,
+        webhook_id,
+        user_id
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(AppError::DatabaseError)?;
+
+    Ok(webhook)
+}
+
+// Fields left as None keep their current value
+pub async fn update_user_webhook(
+    pool: &PgPool,
+    webhook_id: Uuid,
+    user_id: Uuid,
+    url: Option<&str>,
+    description: Option<&str>,
+    enabled: Option<bool>,
+    forward_all: Option<bool>,
+) -> Result<Option<Webhook>, AppError> {
+    let webhook = sqlx::query_as!(
+        Webhook,
+        r#"
+        UPDATE webhooks
+        SET url = COALESCE($3, url),
+            description = COALESCE($4, description),
+            enabled = COALESCE($5, enabled),
+            forward_all = COALESCE($6, forward_all)
+        WHERE id = $1 AND user_id = $2
+        RETURNING id, user_id, url, description, enabled, forward_all, created_at, updated_at
+        "#,
+        webhook_id,
+        user_id,
+        url,
+        description,
+        enabled,
+        forward_all,
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(AppError::DatabaseError)?;
+
+    Ok(webhook)
+}
+
+pub async fn delete_user_webhook(pool: &PgPool, webhook_id: Uuid, user_id: Uuid) -> Result<bool, AppError> {
+    let result = sqlx::query!(
+        "DELETE FROM webhooks WHERE id = $1 AND user_id = $2",
+        webhook_id,
+        user_id,
+    )
+    .execute(pool)
+    .await
+    .map_err(AppError::DatabaseError)?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn get_webhook_deliveries(
+    pool: &PgPool,
+    webhook_id: Uuid,
+    limit: i64,
+    offset: i64,
+) -> Result<(Vec<WebhookDelivery>, i64), AppError> {
+    let rows = sqlx::query_as!(
+        WebhookDelivery,
+        r#"
+        SELECT id, webhook_id, sms_id, status, attempts, next_attempt_at, last_error, created_at, updated_at
+        FROM webhook_deliveries
+        WHERE webhook_id = $1
+        ORDER BY created_at DESC
+        LIMIT $2 OFFSET $3
+        "#,
+        webhook_id,
+        limit,
+        offset,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let total = sqlx::query_scalar!(
+        "SELECT COUNT(*) FROM webhook_deliveries WHERE webhook_id = $1",
+        webhook_id,
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok((rows, total.unwrap_or(0)))
+}
+
+pub async fn get_webhook_attempts(
+    pool: &PgPool,
+    webhook_id: Uuid,
+    limit: i64,
+    offset: i64,
+) -> Result<(Vec<WebhookDeliveryAttempt>, i64), AppError> {
+    let rows = sqlx::query_as!(
+        WebhookDeliveryAttempt,
+        r#"
+        SELECT a.id, a.delivery_id, d.sms_id, a.attempt_number, a.response_status, a.error, a.duration_ms, a.attempted_at
+        FROM webhook_delivery_attempts a
+        JOIN webhook_deliveries d ON d.id = a.delivery_id
+        WHERE d.webhook_id = $1
+        ORDER BY a.attempted_at DESC
+        LIMIT $2 OFFSET $3
+        "#,
+        webhook_id,
+        limit,
+        offset,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let total = sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(*) FROM webhook_delivery_attempts a
+        JOIN webhook_deliveries d ON d.id = a.delivery_id
+        WHERE d.webhook_id = $1
+        "#,
+        webhook_id,
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok((rows, total.unwrap_or(0)))
+}
+
+// Claim due deliveries for this dispatcher. Pushing `next_attempt_at` out by
+// `lease_seconds` keeps other replicas from picking them up in the meantime.
+pub async fn claim_due_webhook_deliveries(
+    pool: &PgPool,
+    limit: i64,
+    lease_seconds: f64,
+) -> Result<Vec<PendingDelivery>, AppError> {
+    let deliveries = sqlx::query_as!(
+        PendingDelivery,
+        r#"
+        WITH due AS (
+            SELECT d.id
+            FROM webhook_deliveries d
+            JOIN webhooks w ON w.id = d.webhook_id
+            WHERE d.status = 'pending' AND d.next_attempt_at <= NOW() AND w.enabled
+            ORDER BY d.next_attempt_at
+            LIMIT $1
+            FOR UPDATE OF d SKIP LOCKED
+        )
+        UPDATE webhook_deliveries d
+        SET next_attempt_at = NOW() + make_interval(secs => $2)
+        FROM due, webhooks w
+        WHERE d.id = due.id AND w.id = d.webhook_id
+        RETURNING d.id, d.attempts, w.url, w.secret, d.sms_id
+        "#,
+        limit,
+        lease_seconds,
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(AppError::DatabaseError)?;
+
+    Ok(deliveries)
+}
+
+// Log an attempt and move the delivery to its next state
+pub async fn record_webhook_attempt(
+    pool: &PgPool,
+    delivery_id: Uuid,
+    attempt_number: i32,
+    result: &DeliveryAttemptResult,
+    status: &str,
+    next_attempt_at: DateTime<Utc>,
+) -> Result<(), AppError> {
+    let mut tx = pool.begin().await?;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO webhook_delivery_attempts (delivery_id, attempt_number, response_status, error, duration_ms)
+        VALUES ($1, $2, $3, $4, $5)
+        "#,
+        delivery_id,
+        attempt_number,
+        result.response_status,
+        result.error,
+        result.duration_ms,
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query!(
+        r#"
+        UPDATE webhook_deliveries
+        SET status = $2, attempts = $3, next_attempt_at = $4, last_error = $5
+        WHERE id = $1
+        "#,
+        delivery_id,
+        status,
+        attempt_number,
+        next_attempt_at,
+        result.error,
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(())
+}
+
+pub async fn create_rule(pool: &PgPool, user_id: Uuid, rule: &RulePayload) -> Result<Rule, AppError> {
+    let rule = sqlx::query_as!(
+        Rule,
+        r#"
+        INSERT INTO rules (
+            user_id, name, priority, enabled, sender_pattern, message_pattern, device_id,
+            active_from, active_until, utc_offset_minutes, action, webhook_id, tag, label_id, stop_processing
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15)
+        RETURNING id, user_id, name, priority, enabled, sender_pattern, message_pattern, device_id,
+               active_from, active_until, utc_offset_minutes, action, webhook_id, tag, label_id,
+               stop_processing, created_at, updated_at
+        "#,
+        user_id,
+        rule.name,
+        rule.priority,
+        rule.enabled,
+        rule.sender_pattern,
+        rule.message_pattern,
+        rule.device_id,
+        rule.active_from,
+        rule.active_until,
+        rule.utc_offset_minutes,
+        rule.action.as_str(),
+        rule.webhook_id,
+        rule.tag,
+        rule.label_id,
+        rule.stop_processing,
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(AppError::DatabaseError)?;
+
+    Ok(rule)
+}
+
+// Rules in evaluation order
+pub async fn find_user_rules(pool: &PgPool, user_id: Uuid) -> Result<Vec<Rule>, AppError> {
+    let rules = sqlx::query_as!(
+        Rule,
+        r#"
+        SELECT id, user_id, name, priority, enabled, sender_pattern, message_pattern, device_id,
+               active_from, active_until, utc_offset_minutes, action, webhook_id, tag, label_id,
+               stop_processing, created_at, updated_at
+        FROM rules
+        WHERE user_id = $1
+        ORDER BY priority, created_at
+        "#,
+        user_id
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(AppError::DatabaseError)?;
+
+    Ok(rules)
+}
+
+pub async fn update_user_rule(
+    pool: &PgPool,
+    rule_id: Uuid,
+    user_id: Uuid,
+    rule: &RulePayload,
+) -> Result<Option<Rule>, AppError> {
+    let rule = sqlx::query_as!(
+        Rule,
+        r#"
+        UPDATE rules
+        SET name = $3,
+            priority = $4,
+            enabled = $5,
+            sender_pattern = $6,
+            message_pattern = $7,
+            device_id = $8,
+            active_from = $9,
+            active_until = $10,
+            utc_offset_minutes = $11,
+            action = $12,
+            webhook_id = $13,
+            tag = $14,
+            label_id = $15,
+            stop_processing = $16
+        WHERE id = $1 AND user_id = $2
+        RETURNING id, user_id, name, priority, enabled, sender_pattern, message_pattern, device_id,
+               active_from, active_until, utc_offset_minutes, action, webhook_id, tag, label_id,
+               stop_processing, created_at, updated_at
+        "#,
+        rule_id,
+        user_id,
+        rule.name,
+        rule.priority,
+        rule.enabled,
+        rule.sender_pattern,
+        rule.message_pattern,
+        rule.device_id,
+        rule.active_from,
+        rule.active_until,
+        rule.utc_offset_minutes,
+        rule.action.as_str(),
+        rule.webhook_id,
+        rule.tag,
+        rule.label_id,
+        rule.stop_processing,
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(AppError::DatabaseError)?;
+
+    Ok(rule)
+}
+
+pub async fn delete_user_rule(pool: &PgPool, rule_id: Uuid, user_id: Uuid) -> Result<bool, AppError> {
+    let result = sqlx::query!(
+        "DELETE FROM rules WHERE id = $1 AND user_id = $2",
+        rule_id,
+        user_id,
+    )
+    .execute(pool)
+    .await
+    .map_err(AppError::DatabaseError)?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn create_label(pool: &PgPool, user_id: Uuid, name: &str) -> Result<Label, AppError> {
+    let label = sqlx::query_as!(
+        Label,
+        r#"
+        INSERT INTO labels (user_id, name)
+        VALUES ($1, $2)
+        RETURNING id, user_id, name, created_at, updated_at
+        "#,
+        user_id,
+        name,
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(label_name_conflict)?;
+
+    Ok(label)
+}
+
+pub async fn find_user_labels(pool: &PgPool, user_id: Uuid) -> Result<Vec<Label>, AppError> {
+    let labels = sqlx::query_as!(
+        Label,
+        r#"
+        SELECT id, user_id, name, created_at, updated_at
+        FROM labels
+        WHERE user_id = $1
+        ORDER BY LOWER(name)
+        "#,
+        user_id
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(AppError::DatabaseError)?;
+
+    Ok(labels)
+}
+
+pub async fn find_user_label(pool: &PgPool, label_id: Uuid, user_id: Uuid) -> Result<Option<Label>, AppError> {
+    let label = sqlx::query_as!(
+        Label,
+        r#"
+        SELECT id, user_id, name, created_at, updated_at
+        FROM labels
+        WHERE id = $1 AND user_id = $2
+        "#,
+        label_id,
+        user_id,
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(AppError::DatabaseError)?;
+
+    Ok(label)
+}
+
+pub async fn rename_user_label(
+    pool: &PgPool,
+    label_id: Uuid,
+    user_id: Uuid,
+    name: &str,
+) -> Result<Option<Label>, AppError> {
+    let label = sqlx::query_as!(
+        Label,
+        r#"
+        UPDATE labels SET name = $3
+        WHERE id = $1 AND user_id = $2
+        RETURNING id, user_id, name, created_at, updated_at
+        "#,
+        label_id,
+        user_id,
+        name,
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(label_name_conflict)?;
+
+    Ok(label)
+}
+
+// Also removes the label from its messages. Rules that applied it stay
+// behind, without it and disabled.
+pub async fn delete_user_label(pool: &PgPool, label_id: Uuid, user_id: Uuid) -> Result<bool, AppError> {
+    let mut tx = pool.begin().await?;
+
+    sqlx::query!(
+        "UPDATE rules SET enabled = FALSE WHERE label_id = $1 AND user_id = $2",
+        label_id,
+        user_id,
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(AppError::DatabaseError)?;
+
+    let result = sqlx::query!(
+        "DELETE FROM labels WHERE id = $1 AND user_id = $2",
+        label_id,
+        user_id,
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(AppError::DatabaseError)?;
+
+    tx.commit().await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+fn label_name_conflict(e: sqlx::Error) -> AppError {
+    if e.as_database_error().is_some_and(|db_err| db_err.is_unique_violation()) {
+        return AppError::Conflict("Label name is already in use".to_string());
+    }
+    AppError::DatabaseError(e)
+}
+
+// Labels the user's listed messages; returns how many weren't labeled already
+pub async fn apply_label_by_ids(pool: &PgPool, user_id: Uuid, label_id: Uuid, ids: &[Uuid]) -> Result<u64, AppError> {
+    let result = sqlx::query!(
+        r#"
+        INSERT INTO sms_labels (sms_id, label_id)
+        SELECT s.id, $2::uuid
+        FROM sms s
+        JOIN devices d ON d.id = s.device_id
+        WHERE d.user_id = $1 AND s.id = ANY($3)
+        ON CONFLICT DO NOTHING
+        "#,
+        user_id,
+        label_id,
+        ids,
+    )
+    .execute(pool)
+    .await
+    .map_err(AppError::DatabaseError)?;
+
+    Ok(result.rows_affected())
+}
+
+pub async fn apply_label_by_filter(
+    pool: &PgPool,
+    user_id: Uuid,
+    label_id: Uuid,
+    filter: &SmsFilter,
+) -> Result<u64, AppError> {
+    let result = sqlx::query!(
+        r#"
+        INSERT INTO sms_labels (sms_id, label_id)
+        SELECT id, $13::uuid
+        FROM user_sms_matching($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
+        ON CONFLICT DO NOTHING
+        "#,
+        user_id,
+        filter.device_id,
+        filter.tsquery,
+        filter.from,
+        filter.to,
+        filter.counterpart,
+        filter.sender_type.map(|t| t.as_str()),
+        filter.clock.as_str(),
+        filter.is_read,
+        filter.is_starred,
+        filter.is_archived,
+        filter.label_id,
+        label_id,
+    )
+    .execute(pool)
+    .await
+    .map_err(AppError::DatabaseError)?;
+
+    Ok(result.rows_affected())
+}
+
+pub async fn remove_label_by_ids(pool: &PgPool, user_id: Uuid, label_id: Uuid, ids: &[Uuid]) -> Result<u64, AppError> {
+    let result = sqlx::query!(
+        r#"
+        DELETE FROM sms_labels sl
+        USING sms s, devices d
+        WHERE sl.sms_id = s.id AND d.id = s.device_id
+        AND d.user_id = $1 AND sl.label_id = $2 AND s.id = ANY($3)
+        "#,
+        user_id,
+        label_id,
+        ids,
+    )
+    .execute(pool)
+    .await
+    .map_err(AppError::DatabaseError)?;
+
+    Ok(result.rows_affected())
+}
+
+pub async fn remove_label_by_filter(
+    pool: &PgPool,
+    user_id: Uuid,
+    label_id: Uuid,
+    filter: &SmsFilter,
+) -> Result<u64, AppError> {
+    let result = sqlx::query!(
+        r#"
+        DELETE FROM sms_labels
+        WHERE label_id = $13
+        AND sms_id IN (SELECT id FROM user_sms_matching($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12))
+        "#,
+        user_id,
+        filter.device_id,
+        filter.tsquery,
+        filter.from,
+        filter.to,
+        filter.counterpart,
+        filter.sender_type.map(|t| t.as_str()),
+        filter.clock.as_str(),
+        filter.is_read,
+        filter.is_starred,
+        filter.is_archived,
+        filter.label_id,
+        label_id,
+    )
+    .execute(pool)
+    .await
+    .map_err(AppError::DatabaseError)?;
+
+    Ok(result.rows_affected())
+}
+
+// Labels on the given messages, for decorating a listing
+pub async fn find_sms_labels(pool: &PgPool, sms_ids: &[Uuid]) -> Result<Vec<SmsLabel>, AppError> {
+    let labels = sqlx::query_as!(
+        SmsLabel,
+        r#"
+        SELECT sl.sms_id, l.id, l.name
+        FROM sms_labels sl
+        JOIN labels l ON l.id = sl.label_id
+        WHERE sl.sms_id = ANY($1)
+        ORDER BY LOWER(l.name)
+        "#,
+        sms_ids,
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(AppError::DatabaseError)?;
+
+    Ok(labels)
+}
+
+// Most recent messages across the user's devices (or one device), newest first
+pub async fn get_recent_user_sms(
+    pool: &PgPool,
+    user_id: Uuid,
+    device_id: Option<Uuid>,
+    limit: i64,
+) -> Result<Vec<Sms>, AppError> {
+    let rows = sqlx::query_as!(
+        Sms,
+        r#"
+        SELECT s.id, s.device_id, s.sender, s.raw_sender, s.sender_type, s.counterpart, s.message,
+               s.received_at, s.tags, s.is_spam, s.is_read, s.is_starred, s.is_archived, s.otp_code, s.otp_issuer,
+               s.device_received_at, s.smsc_timestamp, s.part_count, s.is_partial
+        FROM sms s
+        JOIN devices d ON d.id = s.device_id
+        WHERE d.user_id = $1
+        AND ($2::uuid IS NULL OR s.device_id = $2)
+        ORDER BY s.received_at DESC
+        LIMIT $3
+        "#,
+        user_id,
+        device_id,
+        limit,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows)
+}
+
+pub async fn create_otp_pattern(pool: &PgPool, new_pattern: &NewOtpPattern<'_>) -> Result<OtpPattern, AppError> {
+    let pattern = sqlx::query_as!(
+        OtpPattern,
+        r#"
+        INSERT INTO otp_patterns (user_id, pattern, issuer)
+        VALUES ($1, $2, $3)
+        RETURNING id, user_id, pattern, issuer, created_at
+        "#,
+        new_pattern.user_id,
+        new_pattern.pattern,
+        new_pattern.issuer,
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(AppError::DatabaseError)?;
+
+    Ok(pattern)
+}
+
+pub async fn find_user_otp_patterns(pool: &PgPool, user_id: Uuid) -> Result<Vec<OtpPattern>, AppError> {
+    let patterns = sqlx::query_as!(
+        OtpPattern,
+        r#"
+        SELECT id, user_id, pattern, issuer, created_at
+        FROM otp_patterns
+        WHERE user_id = $1
+        ORDER BY created_at
+        "#,
+        user_id
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(AppError::DatabaseError)?;
+
+    Ok(patterns)
+}
+
+pub async fn delete_user_otp_pattern(pool: &PgPool, pattern_id: Uuid, user_id: Uuid) -> Result<bool, AppError> {
+    let result = sqlx::query!(
+        "DELETE FROM otp_patterns WHERE id = $1 AND user_id = $2",
+        pattern_id,
+        user_id,
+    )
+    .execute(pool)
+    .await
+    .map_err(AppError::DatabaseError)?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+// Newest unused code on the user's devices. With `consume`, the code is marked
+// used in the same statement so concurrent callers never get the same one.
+pub async fn find_latest_otp(
+    pool: &PgPool,
+    user_id: Uuid,
+    device_id: Option<Uuid>,
+    counterpart: Option<&str>,
+    since: Option<DateTime<Utc>>,
+    consume: bool,
+) -> Result<Option<OtpResponse>, AppError> {
+    let otp = if consume {
+        sqlx::query_as!(
+            OtpResponse,
+            r#"
+            UPDATE sms
+            SET otp_consumed_at = NOW()
+            WHERE id = (
+                SELECT s.id
+                FROM sms s
+                JOIN devices d ON d.id = s.device_id
+                WHERE d.user_id = $1
+                AND s.otp_code IS NOT NULL
+                AND s.otp_consumed_at IS NULL
+                AND ($2::uuid IS NULL OR s.device_id = $2)
+                AND ($3::text IS NULL OR s.counterpart = $3)
+                AND ($4::timestamptz IS NULL OR s.received_at >= $4)
+                ORDER BY s.received_at DESC
+                LIMIT 1
+                FOR UPDATE OF s SKIP LOCKED
+            )
+            RETURNING id AS sms_id, device_id, sender, otp_code AS "code!", otp_issuer AS issuer,
+                      received_at, otp_consumed_at AS consumed_at
+            "#,
+            user_id,
+            device_id,
+            counterpart,
+            since,
+        )
+        .fetch_optional(pool)
+        .await?
+    } else {
+        sqlx::query_as!(
+            OtpResponse,
+            r#"
+            SELECT s.id AS sms_id, s.device_id, s.sender, s.otp_code AS "code!", s.otp_issuer AS issuer,
+                   s.received_at, s.otp_consumed_at AS consumed_at
+            FROM sms s
+            JOIN devices d ON d.id = s.device_id
+            WHERE d.user_id = $1
+            AND s.otp_code IS NOT NULL
+            AND s.otp_consumed_at IS NULL
+            AND ($2::uuid IS NULL OR s.device_id = $2)
+            AND ($3::text IS NULL OR s.counterpart = $3)
+            AND ($4::timestamptz IS NULL OR s.received_at >= $4)
+            ORDER BY s.received_at DESC
+            LIMIT 1
+            "#,
+            user_id,
+            device_id,
+            counterpart,
+            since,
+        )
+        .fetch_optional(pool)
+        .await?
+    };
+
+    Ok(otp)
+}
+
+pub async fn create_outbound_sms(pool: &PgPool, new_outbound: &NewOutboundSms<'_>) -> Result<OutboundSms, AppError> {
+    let mut tx = pool.begin().await?;
+
+    let outbound = sqlx::query_as!(
+        OutboundSms,
+        r#"
+        INSERT INTO outbound_sms (device_id, recipient, message)
+        VALUES ($1, $2, $3)
+        RETURNING id, device_id, recipient, message, status, error, claimed_at, created_at, updated_at
+        "#,
+        new_outbound.device_id,
+        new_outbound.recipient,
+        new_outbound.message,
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    sqlx::query!(
+        "INSERT INTO outbound_sms_events (outbound_sms_id, status) VALUES ($1, $2)",
+        outbound.id,
+        outbound.status,
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(outbound)
+}
+
+pub async fn find_user_outbound_sms(
+    pool: &PgPool,
+    outbound_id: Uuid,
+    user_id: Uuid,
+) -> Result<Option<OutboundSms>, AppError> {
+    let outbound = sqlx::query_as!(
+        OutboundSms,
+        r#"
+        SELECT o.id, o.device_id, o.recipient, o.message, o.status, o.error, o.claimed_at, o.created_at, o.updated_at
+        FROM outbound_sms o
+        JOIN devices d ON d.id = o.device_id
+        WHERE o.id = $1 AND d.user_id = $2
+        "#,
+        outbound_id,
+        user_id,
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(AppError::DatabaseError)?;
+
+    Ok(outbound)
+}
+
+pub async fn get_outbound_sms_events(pool: &PgPool, outbound_id: Uuid) -> Result<Vec<OutboundSmsEvent>, AppError> {
+    let events = sqlx::query_as!(
+        OutboundSmsEvent,
+        r#"
+        SELECT status, error, created_at
+        FROM outbound_sms_events
+        WHERE outbound_sms_id = $1
+        ORDER BY created_at
+        "#,
+        outbound_id,
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(AppError::DatabaseError)?;
+
+    Ok(events)
+}
+
+pub async fn get_user_outbound_sms_with_filters(
+    pool: &PgPool,
+    user_id: Uuid,
+    device_id: Option<Uuid>,
+    status: Option<&str>,
+    limit: i64,
+    offset: i64,
+) -> Result<(Vec<OutboundSms>, i64), AppError> {
+    let rows = sqlx::query_as!(
+        OutboundSms,
+        r#"
+        SELECT o.id, o.device_id, o.recipient, o.message, o.status, o.error, o.claimed_at, o.created_at, o.updated_at
+        FROM outbound_sms o
+        JOIN devices d ON d.id = o.device_id
+        WHERE d.user_id = $1
+        AND ($2::uuid IS NULL OR o.device_id = $2)
+        AND ($3::text IS NULL OR o.status = $3)
+        ORDER BY o.created_at DESC
+        LIMIT $4 OFFSET $5
+        "#,
+        user_id,
+        device_id,
+        status,
+        limit,
+        offset,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let total = sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(*)
+        FROM outbound_sms o
+        JOIN devices d ON d.id = o.device_id
+        WHERE d.user_id = $1
+        AND ($2::uuid IS NULL OR o.device_id = $2)
+        AND ($3::text IS NULL OR o.status = $3)
+        "#,
+        user_id,
+        device_id,
+        status,
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok((rows, total.unwrap_or(0)))
+}
+
+// Hand queued messages to a device. Claims the phone never reported back on
+// within `claim_timeout_seconds` are marked expired first, never handed out again.
+pub async fn claim_outbound_sms(
+    pool: &PgPool,
+    device_id: Uuid,
+    limit: i64,
+    claim_timeout_seconds: f64,
+) -> Result<Vec<OutboundSms>, AppError> {
+    let mut tx = pool.begin().await?;
+
+    sqlx::query!(
+        r#"
+        WITH expired AS (
+            UPDATE outbound_sms
+            SET status = 'expired', error = 'Device did not report a status in time'
+            WHERE device_id = $1 AND status = 'claimed' AND claimed_at < NOW() - make_interval(secs => $2)
+            RETURNING id, error
+        )
+        INSERT INTO outbound_sms_events (outbound_sms_id, status, error)
+        SELECT id, 'expired', error FROM expired
+        "#,
+        device_id,
+        claim_timeout_seconds,
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    let mut claimed = sqlx::query_as!(
+        OutboundSms,
+        r#"
+        WITH due AS (
+            SELECT id
+            FROM outbound_sms
+            WHERE device_id = $1 AND status = 'queued'
+            ORDER BY created_at
+            LIMIT $2
+            FOR UPDATE SKIP LOCKED
+        )
+        UPDATE outbound_sms o
+        SET status = 'claimed', claimed_at = NOW()
+        FROM due
+        WHERE o.id = due.id
+        RETURNING o.id, o.device_id, o.recipient, o.message, o.status, o.error, o.claimed_at, o.created_at, o.updated_at
+        "#,
+        device_id,
+        limit,
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    if !claimed.is_empty() {
+        let ids: Vec<Uuid> = claimed.iter().map(|o| o.id).collect();
+        sqlx::query!(
+            r#"
+            INSERT INTO outbound_sms_events (outbound_sms_id, status)
+            SELECT id, 'claimed' FROM UNNEST($1::uuid[]) AS id
+            "#,
+            &ids,
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+
+    claimed.sort_by_key(|o| o.created_at);
+
+    Ok(claimed)
+}
+
+// Put a failed or expired message back in the queue. Returns None if the
+// message isn't the user's.
+pub async fn retry_user_outbound_sms(
+    pool: &PgPool,
+    outbound_id: Uuid,
+    user_id: Uuid,
+) -> Result<Option<OutboundSms>, AppError> {
+    let mut tx = pool.begin().await?;
+
+    let current = sqlx::query!(
+        r#"
+        SELECT o.status, d.retired_at
+        FROM outbound_sms o
+        JOIN devices d ON d.id = o.device_id
+        WHERE o.id = $1 AND d.user_id = $2
+        FOR UPDATE OF o
+        "#,
+        outbound_id,
+        user_id,
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    let Some(current) = current else {
+        return Ok(None);
+    };
+
+    if current.retired_at.is_some() {
+        return Err(AppError::Conflict("Device has been retired".to_string()));
+    }
+    if !RETRYABLE_STATUSES.contains(&current.status.as_str()) {
+        return Err(AppError::Conflict(format!("Cannot retry a {} message", current.status)));
+    }
+
+    let outbound = sqlx::query_as!(
+        OutboundSms,
+        r#"
+        UPDATE outbound_sms
+        SET status = 'queued', error = NULL, claimed_at = NULL
+        WHERE id = $1
+        RETURNING id, device_id, recipient, message, status, error, claimed_at, created_at, updated_at
+        "#,
+        outbound_id,
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    sqlx::query!(
+        "INSERT INTO outbound_sms_events (outbound_sms_id, status) VALUES ($1, $2)",
+        outbound_id,
+        outbound.status,
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(Some(outbound))
+}
+
+// Apply a status reported by the device. Returns None if the message does not
+// belong to the device; repeating the current status is a no-op.
+pub async fn report_outbound_sms_status(
+    pool: &PgPool,
+    outbound_id: Uuid,
+    device_id: Uuid,
+    status: ReportedStatus,
+    error: Option<&str>,
+) -> Result<Option<OutboundSms>, AppError> {
+    let mut tx = pool.begin().await?;
+
+    let current = sqlx::query_scalar!(
+        "SELECT status FROM outbound_sms WHERE id = $1 AND device_id = $2 FOR UPDATE",
+        outbound_id,
+        device_id,
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    let Some(current) = current else {
+        return Ok(None);
+    };
+
+    if !status.allowed_from().contains(&current.as_str()) {
+        return Err(AppError::Conflict(format!(
+            "Cannot mark a {} message as {}",
+            current,
+            status.as_str()
+        )));
+    }
+
+    let outbound = sqlx::query_as!(
+        OutboundSms,
+        r#"
+        UPDATE outbound_sms
+        SET status = $2, error = COALESCE($3, error)
+        WHERE id = $1
+        RETURNING id, device_id, recipient, message, status, error, claimed_at, created_at, updated_at
+        "#,
+        outbound_id,
+        status.as_str(),
+        error,
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    if current != status.as_str() {
+        sqlx::query!(
+            "INSERT INTO outbound_sms_events (outbound_sms_id, status, error) VALUES ($1, $2, $3)",
+            outbound_id,
+            status.as_str(),
+            error,
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+
+    Ok(Some(outbound))
+}
+
+// One row per (device, counterpart) thread, most recently active first.
+// Spam is left out of the inbox view, and archived messages still show in a
+// thread but don't count as unread, as in the per-device counts.
+pub async fn get_user_conversations(
+    pool: &PgPool,
+    user_id: Uuid,
+    device_id: Option<Uuid>,
+    limit: i64,
+    offset: i64,
+) -> Result<(Vec<Conversation>, i64), AppError> {
+    let rows = sqlx::query!(
+        r#"
+        WITH threads AS (
+            SELECT s.device_id, s.counterpart,
+                   COUNT(*) AS message_count,
+                   COUNT(*) FILTER (WHERE NOT s.is_read AND NOT s.is_archived) AS unread_count,
+                   MAX(s.received_at) AS last_message_at
+            FROM sms s
+            JOIN devices d ON d.id = s.device_id
+            WHERE d.user_id = $1
+            AND ($2::uuid IS NULL OR s.device_id = $2)
+            AND NOT s.is_spam
+            GROUP BY s.device_id, s.counterpart
+            ORDER BY last_message_at DESC
+            LIMIT $3 OFFSET $4
+        )
+        SELECT t.message_count AS "message_count!", t.unread_count AS "unread_count!",
+               t.last_message_at AS "last_message_at!",
+               l.id, l.device_id, l.sender, l.raw_sender, l.sender_type, l.counterpart, l.message,
+               l.received_at, l.tags, l.is_spam, l.is_read, l.is_starred, l.is_archived, l.otp_code, l.otp_issuer,
+               l.device_received_at, l.smsc_timestamp, l.part_count, l.is_partial
+        FROM threads t
+        CROSS JOIN LATERAL (
+            SELECT *
+            FROM sms s
+            WHERE s.device_id = t.device_id AND s.counterpart = t.counterpart AND NOT s.is_spam
+            ORDER BY s.received_at DESC
+            LIMIT 1
+        ) l
+        ORDER BY t.last_message_at DESC
+        "#,
+        user_id,
+        device_id,
+        limit,
+        offset,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let conversations = rows
+        .into_iter()
+        .map(|row| Conversation {
+            device_id: row.device_id,
+            counterpart: row.counterpart.clone(),
+            message_count: row.message_count,
+            unread_count: row.unread_count,
+            last_message_at: row.last_message_at,
+            last_message: Sms {
+                id: row.id,
+                device_id: row.device_id,
+                sender: row.sender,
+                raw_sender: row.raw_sender,
+                sender_type: row.sender_type,
+                counterpart: row.counterpart,
+                message: row.message,
+                received_at: row.received_at,
+                tags: row.tags,
+                is_spam: row.is_spam,
+                is_read: row.is_read,
+                is_starred: row.is_starred,
+                is_archived: row.is_archived,
+                otp_code: row.otp_code,
+                otp_issuer: row.otp_issuer,
+                device_received_at: row.device_received_at,
+                smsc_timestamp: row.smsc_timestamp,
+                part_count: row.part_count,
+                is_partial: row.is_partial,
+            },
+        })
+        .collect();
+
+    let total = sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(DISTINCT (s.device_id, s.counterpart))
+        FROM sms s
+        JOIN devices d ON d.id = s.device_id
+        WHERE d.user_id = $1
+        AND ($2::uuid IS NULL OR s.device_id = $2)
+        AND NOT s.is_spam
+        "#,
+        user_id,
+        device_id,
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok((conversations, total.unwrap_or(0)))
+}
+
+// Messages in one thread, newest first
+pub async fn get_conversation_messages(
+    pool: &PgPool,
+    device_id: Uuid,
+    counterpart: &str,
+    before: Option<DateTime<Utc>>,
+    limit: i64,
+    offset: i64,
+) -> Result<(Vec<Sms>, i64), AppError> {
+    let rows = sqlx::query_as!(
+        Sms,
+        r#"
+        SELECT id, device_id, sender, raw_sender, sender_type, counterpart, message,
+               received_at, tags, is_spam, is_read, is_starred, is_archived, otp_code, otp_issuer,
+               device_received_at, smsc_timestamp, part_count, is_partial
+        FROM sms
+        WHERE device_id = $1 AND counterpart = $2 AND NOT is_spam
+        AND ($3::timestamptz IS NULL OR received_at < $3)
+        ORDER BY received_at DESC
+        LIMIT $4 OFFSET $5
+        "#,
+        device_id,
+        counterpart,
+        before,
+        limit,
+        offset,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let total = sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(*)
+        FROM sms
+        WHERE device_id = $1 AND counterpart = $2 AND NOT is_spam
+        AND ($3::timestamptz IS NULL OR received_at < $3)
+        "#,
+        device_id,
+        counterpart,
+        before,
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok((rows, total.unwrap_or(0)))
+}
+
+pub async fn mark_conversation_read(pool: &PgPool, device_id: Uuid, counterpart: &str) -> Result<u64, AppError> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE sms SET is_read = TRUE
+        WHERE device_id = $1 AND counterpart = $2 AND NOT is_read
+        "#,
+        device_id,
+        counterpart,
+    )
+    .execute(pool)
+    .await
+    .map_err(AppError::DatabaseError)?;
+
+    Ok(result.rows_affected())
+}
+
+// Hold one part of a concatenated message. Work on a message's parts is
+// serialized with an advisory lock, so exactly one upload sees it complete.
+pub async fn buffer_sms_part(pool: &PgPool, part: &NewSmsPart<'_>) -> Result<PartOutcome, AppError> {
+    let mut tx = pool.begin().await?;
+
+    lock_sms_part_group(&mut tx, part.device_id, part.counterpart, part.concat.reference, part.concat.total).await?;
+
+    // A retried part matches by its client message id, or failing that by
+    // position and text: one still waiting, or one recently assembled
+    let existing = sqlx::query!(
+        r#"
+        SELECT id, sms_id FROM sms_parts
+        WHERE device_id = $1
+        AND CASE
+            WHEN $7::text IS NOT NULL THEN client_message_id = $7
+            ELSE counterpart = $2 AND reference = $3 AND total_parts = $4 AND sequence = $5 AND message = $6
+                 AND (sms_id IS NULL OR received_at > NOW() - INTERVAL '1 day')
+        END
+        ORDER BY received_at DESC
+        LIMIT 1
+        "#,
+        part.device_id,
+        part.counterpart,
+        part.concat.reference,
+        part.concat.total,
+        part.concat.sequence,
+        part.message,
+        part.client_message_id,
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    if let Some(existing) = existing {
+        return Ok(match existing.sms_id {
+            Some(sms_id) => PartOutcome::AlreadyAssembled(sms_id),
+            None => PartOutcome::Buffered(existing.id),
+        });
+    }
+
+    // Join the oldest waiting message that still lacks this position. If every
+    // one already has it (with other text), this part belongs to a new message
+    // that reuses the reference.
+    let group_id = sqlx::query_scalar!(
+        r#"
+        SELECT group_id FROM sms_parts
+        WHERE device_id = $1 AND counterpart = $2 AND reference = $3 AND total_parts = $4 AND sms_id IS NULL
+        GROUP BY group_id
+        HAVING bool_and(assembled_at IS NULL) AND NOT bool_or(sequence = $5)
+        ORDER BY MIN(received_at)
+        LIMIT 1
+        "#,
+        part.device_id,
+        part.counterpart,
+        part.concat.reference,
+        part.concat.total,
+        part.concat.sequence,
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    .unwrap_or_else(Uuid::new_v4);
+
+    let part_id = sqlx::query_scalar!(
+        r#"
+        INSERT INTO sms_parts (group_id, device_id, raw_sender, counterpart, reference, total_parts, sequence, message,
+                               client_message_id, device_received_at, smsc_timestamp)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+        RETURNING id
+        "#,
+        group_id,
+        part.device_id,
+        part.raw_sender,
+        part.counterpart,
+        part.concat.reference,
+        part.concat.total,
+        part.concat.sequence,
+        part.message,
+        part.client_message_id,
+        part.device_received_at,
+        part.smsc_timestamp,
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    let received = sqlx::query_scalar!(
+        "SELECT COUNT(DISTINCT sequence) FROM sms_parts WHERE group_id = $1 AND sms_id IS NULL",
+        group_id,
+    )
+    .fetch_one(&mut *tx)
+    .await?
+    .unwrap_or(0);
+
+    let outcome = if received >= part.concat.total as i64 {
+        let parts = claim_parts(&mut tx, group_id, None).await?;
+        if parts.is_empty() {
+            PartOutcome::Buffered(part_id)
+        } else {
+            PartOutcome::Complete(parts)
+        }
+    } else {
+        PartOutcome::Buffered(part_id)
+    };
+
+    tx.commit().await?;
+
+    Ok(outcome)
+}
+
+// Messages still incomplete `timeout_seconds` after their first part arrived
+pub async fn find_stale_sms_part_groups(
+    pool: &PgPool,
+    timeout_seconds: f64,
+    limit: i64,
+) -> Result<Vec<StaleSmsPartGroup>, AppError> {
+    let groups = sqlx::query_as!(
+        StaleSmsPartGroup,
+        r#"
+        SELECT p.group_id, p.device_id, d.user_id, d.default_region, p.counterpart, p.reference, p.total_parts
+        FROM sms_parts p
+        JOIN devices d ON d.id = p.device_id
+        WHERE p.sms_id IS NULL
+        GROUP BY p.group_id, p.device_id, d.user_id, d.default_region, p.counterpart, p.reference, p.total_parts
+        HAVING MIN(p.received_at) < NOW() - make_interval(secs => $1)
+        AND bool_or(p.assembled_at IS NULL OR p.assembled_at < NOW() - make_interval(secs => $1))
+        LIMIT $2
+        "#,
+        timeout_seconds,
+        limit,
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(AppError::DatabaseError)?;
+
+    Ok(groups)
+}
+
+// Claim whatever parts of a stale message are left. Claims older than
+// `timeout_seconds` are taken over, in case the claiming request died.
+pub async fn claim_sms_part_group(
+    pool: &PgPool,
+    group: &StaleSmsPartGroup,
+    timeout_seconds: Option<f64>,
+) -> Result<Vec<SmsPart>, AppError> {
+    let mut tx = pool.begin().await?;
+
+    lock_sms_part_group(&mut tx, &group.device_id, &group.counterpart, group.reference, group.total_parts).await?;
+    let parts = claim_parts(&mut tx, group.group_id, timeout_seconds).await?;
+
+    tx.commit().await?;
+
+    Ok(parts)
+}
+
+async fn lock_sms_part_group(
+    conn: &mut PgConnection,
+    device_id: &Uuid,
+    counterpart: &str,
+    reference: i32,
+    total_parts: i16,
+) -> Result<(), AppError> {
+    sqlx::query!(
+        "SELECT pg_advisory_xact_lock(hashtextextended($1::uuid::text || ':' || $2 || ':' || $3::int || ':' || $4::smallint, 0))",
+        device_id,
+        counterpart,
+        reference,
+        total_parts,
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+async fn claim_parts(
+    conn: &mut PgConnection,
+    group_id: Uuid,
+    timeout_seconds: Option<f64>,
+) -> Result<Vec<SmsPart>, AppError> {
+    let mut parts = sqlx::query_as!(
+        SmsPart,
+        r#"
+        UPDATE sms_parts SET assembled_at = NOW()
+        WHERE group_id = $1 AND sms_id IS NULL
+        AND (assembled_at IS NULL OR assembled_at < NOW() - make_interval(secs => $2))
+        RETURNING id, device_id, raw_sender, counterpart, reference, total_parts, sequence, message,
+                  client_message_id, received_at, device_received_at, smsc_timestamp
+        "#,
+        group_id,
+        timeout_seconds,
+    )
+    .fetch_all(conn)
+    .await?;
+
+    // Groups formed before parts were grouped by message may hold two rows
+    // for one position; keep the first
+    parts.sort_by_key(|part| (part.sequence, part.received_at));
+    parts.dedup_by_key(|part| part.sequence);
+
+    Ok(parts)
+}
+
+// Drop claimed parts whose assembled message was discarded by a rule
+pub async fn delete_sms_parts(pool: &PgPool, part_ids: &[Uuid]) -> Result<(), AppError> {
+    sqlx::query!("DELETE FROM sms_parts WHERE id = ANY($1)", part_ids)
+        .execute(pool)
+        .await
+        .map_err(AppError::DatabaseError)?;
+
+    Ok(())
+}
+
+// Parts of one of the user's messages; None if the message isn't theirs
+pub async fn find_user_sms_parts(pool: &PgPool, sms_id: Uuid, user_id: Uuid) -> Result<Option<Vec<SmsPart>>, AppError> {
+    let owned = sqlx::query_scalar!(
+        r#"
+        SELECT s.id FROM sms s
+        JOIN devices d ON d.id = s.device_id
+        WHERE s.id = $1 AND d.user_id = $2
+        "#,
+        sms_id,
+        user_id,
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    if owned.is_none() {
+        return Ok(None);
+    }
+
+    let parts = sqlx::query_as!(
+        SmsPart,
+        r#"
+        SELECT id, device_id, raw_sender, counterpart, reference, total_parts, sequence, message,
+               client_message_id, received_at, device_received_at, smsc_timestamp
+        FROM sms_parts
+        WHERE sms_id = $1
+        ORDER BY sequence, received_at
+        "#,
+        sms_id,
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(AppError::DatabaseError)?;
+
+    Ok(Some(parts))
+}
+
+// The user-wide policy (if any) followed by device overrides
+pub async fn find_user_retention_policies(pool: &PgPool, user_id: Uuid) -> Result<Vec<RetentionPolicy>, AppError> {
+    let policies = sqlx::query_as!(
+        RetentionPolicy,
+        r#"
+        SELECT id, user_id, device_id, max_age_days, max_count, otp_max_age_hours, created_at, updated_at
+        FROM retention_policies
+        WHERE user_id = $1
+        ORDER BY device_id NULLS FIRST
+        "#,
+        user_id
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(AppError::DatabaseError)?;
+
+    Ok(policies)
+}
+
+// Creates or replaces the user-wide policy, or a device's override when `device_id` is set
+pub async fn upsert_retention_policy(
+    pool: &PgPool,
+    user_id: Uuid,
+    device_id: Option<Uuid>,
+    policy: &RetentionPolicyPayload,
+) -> Result<RetentionPolicy, AppError> {
+    let policy = match device_id {
+        None => sqlx::query_as!(
+            RetentionPolicy,
+            r#"
+            INSERT INTO retention_policies (user_id, max_age_days, max_count, otp_max_age_hours)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (user_id) WHERE device_id IS NULL
+            DO UPDATE SET max_age_days = $2, max_count = $3, otp_max_age_hours = $4
+            RETURNING id, user_id, device_id, max_age_days, max_count, otp_max_age_hours, created_at, updated_at
+            "#,
+            user_id,
+            policy.max_age_days,
+            policy.max_count,
+            policy.otp_max_age_hours,
+        )
+        .fetch_one(pool)
+        .await?,
+        Some(device_id) => sqlx::query_as!(
+            RetentionPolicy,
+            r#"
+            INSERT INTO retention_policies (user_id, device_id, max_age_days, max_count, otp_max_age_hours)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (device_id) WHERE device_id IS NOT NULL
+            DO UPDATE SET max_age_days = $3, max_count = $4, otp_max_age_hours = $5
+            RETURNING id, user_id, device_id, max_age_days, max_count, otp_max_age_hours, created_at, updated_at
+            "#,
+            user_id,
+            device_id,
+            policy.max_age_days,
+            policy.max_count,
+            policy.otp_max_age_hours,
+        )
+        .fetch_one(pool)
+        .await?,
+    };
+
+    Ok(policy)
+}
+
+pub async fn delete_retention_policy(pool: &PgPool, user_id: Uuid, device_id: Option<Uuid>) -> Result<bool, AppError> {
+    let result = sqlx::query!(
+        "DELETE FROM retention_policies WHERE user_id = $1 AND device_id IS NOT DISTINCT FROM $2",
+        user_id,
+        device_id,
+    )
+    .execute(pool)
+    .await
+    .map_err(AppError::DatabaseError)?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+// Every device some policy applies to, grouped by owner
+pub async fn find_effective_retention(pool: &PgPool) -> Result<Vec<EffectiveRetention>, AppError> {
+    let policies = sqlx::query_as!(
+        EffectiveRetention,
+        r#"
+        SELECT d.id AS device_id, d.user_id,
+               COALESCE(dp.max_age_days, up.max_age_days) AS max_age_days,
+               COALESCE(dp.max_count, up.max_count) AS max_count,
+               COALESCE(dp.otp_max_age_hours, up.otp_max_age_hours) AS otp_max_age_hours
+        FROM devices d
+        LEFT JOIN retention_policies up ON up.user_id = d.user_id AND up.device_id IS NULL
+        LEFT JOIN retention_policies dp ON dp.device_id = d.id
+        WHERE up.id IS NOT NULL OR dp.id IS NOT NULL
+        ORDER BY d.user_id, d.id
+        "#
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(AppError::DatabaseError)?;
+
+    Ok(policies)
+}
+
+// Deletes up to `limit` of a device's messages received before `cutoff`
+// (only those with a one-time code if `otp_only`). Starred messages are kept.
+// Rows another purge has locked are skipped rather than waited for.
+pub async fn purge_sms_older_than(
+    pool: &PgPool,
+    device_id: Uuid,
+    cutoff: DateTime<Utc>,
+    otp_only: bool,
+    limit: i64,
+) -> Result<PurgeBatch, AppError> {
+    let row = sqlx::query!(
+        r#"
+        WITH doomed AS (
+            SELECT id FROM sms
+            WHERE device_id = $1 AND received_at < $2 AND NOT is_starred
+            AND (NOT $3 OR otp_code IS NOT NULL)
+            ORDER BY received_at
+            LIMIT $4
+            FOR UPDATE SKIP LOCKED
+        ),
+        gone AS (
+            DELETE FROM sms WHERE id IN (SELECT id FROM doomed) RETURNING id
+        )
+        SELECT (SELECT COUNT(*) FROM gone) AS "deleted!",
+               ARRAY(SELECT a.storage_key FROM sms_attachments a WHERE a.sms_id IN (SELECT id FROM gone)) AS "storage_keys!"
+        "#,
+        device_id,
+        cutoff,
+        otp_only,
+        limit,
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(AppError::DatabaseError)?;
+
+    Ok(PurgeBatch { deleted: row.deleted, storage_keys: row.storage_keys })
+}
+
+// Deletes up to `limit` of a device's messages beyond its newest `keep`.
+// Starred messages are kept and don't count towards the limit.
+pub async fn purge_sms_beyond_count(pool: &PgPool, device_id: Uuid, keep: i64, limit: i64) -> Result<PurgeBatch, AppError> {
+    let row = sqlx::query!(
+        r#"
+        WITH doomed AS (
+            SELECT id FROM sms
+            WHERE device_id = $1 AND NOT is_starred
+            ORDER BY received_at DESC
+            OFFSET $2
+            LIMIT $3
+            FOR UPDATE SKIP LOCKED
+        ),
+        gone AS (
+            DELETE FROM sms WHERE id IN (SELECT id FROM doomed) RETURNING id
+        )
+        SELECT (SELECT COUNT(*) FROM gone) AS "deleted!",
+               ARRAY(SELECT a.storage_key FROM sms_attachments a WHERE a.sms_id IN (SELECT id FROM gone)) AS "storage_keys!"
+        "#,
+        device_id,
+        keep,
+        limit,
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(AppError::DatabaseError)?;
+
+    Ok(PurgeBatch { deleted: row.deleted, storage_keys: row.storage_keys })
+}
+
+pub async fn save_retention_report(pool: &PgPool, report: &RetentionReport) -> Result<(), AppError> {
+    sqlx::query!(
+        r#"
+        INSERT INTO retention_reports (user_id, started_at, finished_at, deleted_by_age, deleted_by_count,
+                                       deleted_otp, deleted_attachments)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        ON CONFLICT (user_id) DO UPDATE
+        SET started_at = $2, finished_at = $3, deleted_by_age = $4, deleted_by_count = $5,
+            deleted_otp = $6, deleted_attachments = $7
+        "#,
+        report.user_id,
+        report.started_at,
+        report.finished_at,
+        report.deleted_by_age,
+        report.deleted_by_count,
+        report.deleted_otp,
+        report.deleted_attachments,
+    )
+    .execute(pool)
+    .await
+    .map_err(AppError::DatabaseError)?;
+
+    Ok(())
+}
+
+pub async fn find_retention_report(pool: &PgPool, user_id: Uuid) -> Result<Option<RetentionReport>, AppError> {
+    let report = sqlx::query_as!(
+        RetentionReport,
+        r#"
+        SELECT user_id, started_at, finished_at, deleted_by_age, deleted_by_count, deleted_otp, deleted_attachments
+        FROM retention_reports
+        WHERE user_id = $1
+        "#,
+        user_id
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(AppError::DatabaseError)?;
+
+    Ok(report)
+}
+
+pub async fn create_alert_channel(
+    pool: &PgPool,
+    user_id: Uuid,
+    kind: &str,
+    target: &str,
+    secret: Option<&str>,
+) -> Result<AlertChannel, AppError> {
+    let channel = sqlx::query_as!(
+        AlertChannel,
+        r#"
+        INSERT INTO alert_channels (user_id, kind, target, secret)
+        VALUES ($1, $2, $3, $4)
+        RETURNING id, kind, target, secret, created_at, updated_at
+        "#,
+        user_id,
+        kind,
+        target,
+        secret,
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(AppError::DatabaseError)?;
+
+    Ok(channel)
+}
+
+pub async fn find_user_alert_channels(pool: &PgPool, user_id: Uuid) -> Result<Vec<AlertChannel>, AppError> {
+    let channels = sqlx::query_as!(
+        AlertChannel,
+        r#"
+        SELECT id, kind, target, secret, created_at, updated_at
+        FROM alert_channels
+        WHERE user_id = $1
+        ORDER BY created_at
+        "#,
+        user_id
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(AppError::DatabaseError)?;
+
+    Ok(channels)
+}
+
+pub async fn find_user_alert_channel(pool: &PgPool, channel_id: Uuid, user_id: Uuid) -> Result<Option<AlertChannel>, AppError> {
+    let channel = sqlx::query_as!(
+        AlertChannel,
+        r#"
+        SELECT id, kind, target, secret, created_at, updated_at
+        FROM alert_channels
+        WHERE id = $1 AND user_id = $2
+        "#,
+        channel_id,
+        user_id
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(AppError::DatabaseError)?;
+
+    Ok(channel)
+}
+
+// Notifications still queued for the channel are given up on
+pub async fn delete_user_alert_channel(pool: &PgPool, channel_id: Uuid, user_id: Uuid) -> Result<bool, AppError> {
+    let mut tx = pool.begin().await?;
+
+    sqlx::query!(
+        r#"
+        UPDATE alert_notifications n
+        SET status = 'failed', error = 'Channel was deleted'
+        FROM alert_channels c
+        WHERE c.id = n.channel_id AND c.id = $1 AND c.user_id = $2 AND n.status = 'pending'
+        "#,
+        channel_id,
+        user_id,
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    let result = sqlx::query!(
+        "DELETE FROM alert_channels WHERE id = $1 AND user_id = $2",
+        channel_id,
+        user_id,
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+// Opens a heartbeat alert for every active device that has sent heartbeats
+// but none within `offline_after_seconds`. Devices that already have one open
+// are skipped, so each outage is reported once. A notification is queued for
+// each of the owner's alert channels.
+pub async fn open_heartbeat_alerts(pool: &PgPool, offline_after_seconds: f64) -> Result<Vec<DeviceAlert>, AppError> {
+    let alerts = sqlx::query_as!(
+        DeviceAlert,
+        r#"
+        WITH opened AS (
+            INSERT INTO device_alerts (device_id, kind, last_activity_at)
+            SELECT id, 'heartbeat', last_seen_at
+            FROM devices
+            WHERE retired_at IS NULL AND last_seen_at < NOW() - make_interval(secs => $1)
+            ON CONFLICT (device_id, kind) WHERE resolved_at IS NULL DO NOTHING
+            RETURNING *
+        ),
+        queued AS (
+            INSERT INTO alert_notifications (alert_id, channel_id, event, channel_kind, target)
+            SELECT o.id, c.id, 'opened', c.kind, c.target
+            FROM opened o
+            JOIN devices d ON d.id = o.device_id
+            JOIN alert_channels c ON c.user_id = d.user_id
+        )
+        SELECT o.id, o.device_id, d.device_name, o.kind, o.last_activity_at, o.opened_at, o.resolved_at
+        FROM opened o
+        JOIN devices d ON d.id = o.device_id
+        "#,
+        offline_after_seconds,
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(AppError::DatabaseError)?;
+
+    Ok(alerts)
+}
+
+// Opens an SMS silence alert for every active device with no message received
+// (or, for new devices, no registration) within `window_seconds`, queueing
+// notifications like `open_heartbeat_alerts`
+pub async fn open_sms_silence_alerts(pool: &PgPool, window_seconds: f64) -> Result<Vec<DeviceAlert>, AppError> {
+    let alerts = sqlx::query_as!(
+        DeviceAlert,
+        r#"
+        WITH opened AS (
+            INSERT INTO device_alerts (device_id, kind, last_activity_at)
+            SELECT d.id, 'sms_silence', latest.received_at
+            FROM devices d
+            LEFT JOIN LATERAL (
+                SELECT MAX(received_at) AS received_at FROM sms WHERE device_id = d.id
+            ) latest ON TRUE
+            WHERE d.retired_at IS NULL
+            AND COALESCE(latest.received_at, d.created_at) < NOW() - make_interval(secs => $1)
+            ON CONFLICT (device_id, kind) WHERE resolved_at IS NULL DO NOTHING
+            RETURNING *
+        ),
+        queued AS (
+            INSERT INTO alert_notifications (alert_id, channel_id, event, channel_kind, target)
+            SELECT o.id, c.id, 'opened', c.kind, c.target
+            FROM opened o
+            JOIN devices d ON d.id = o.device_id
+            JOIN alert_channels c ON c.user_id = d.user_id
+        )
+        SELECT o.id, o.device_id, d.device_name, o.kind, o.last_activity_at, o.opened_at, o.resolved_at
+        FROM opened o
+        JOIN devices d ON d.id = o.device_id
+        "#,
+        window_seconds,
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(AppError::DatabaseError)?;
+
+    Ok(alerts)
+}
+
+// Resolves open alerts whose device has recovered: a heartbeat within
+// `offline_after_seconds`, or an SMS within `sms_window_seconds` (silence
+// alerts are all resolved when that check is turned off, i.e. window <= 0).
+// Alerts of retired devices are closed too, but not returned or notified.
+pub async fn resolve_device_alerts(
+    pool: &PgPool,
+    offline_after_seconds: f64,
+    sms_window_seconds: f64,
+) -> Result<Vec<DeviceAlert>, AppError> {
+    sqlx::query!(
+        r#"
+        UPDATE device_alerts a
+        SET resolved_at = NOW()
+        FROM devices d
+        WHERE d.id = a.device_id AND a.resolved_at IS NULL AND d.retired_at IS NOT NULL
+        "#
+    )
+    .execute(pool)
+    .await
+    .map_err(AppError::DatabaseError)?;
+
+    let alerts = sqlx::query_as!(
+        DeviceAlert,
+        r#"
+        WITH resolved AS (
+            UPDATE device_alerts a
+            SET resolved_at = NOW()
+            FROM devices d
+            WHERE d.id = a.device_id AND a.resolved_at IS NULL
+            AND CASE a.kind
+                WHEN 'heartbeat' THEN d.last_seen_at >= NOW() - make_interval(secs => $1)
+                ELSE $2::float8 <= 0 OR EXISTS (
+                    SELECT 1 FROM sms s
+                    WHERE s.device_id = d.id AND s.received_at >= NOW() - make_interval(secs => $2)
+                )
+            END
+            RETURNING a.id, a.device_id, d.device_name, d.user_id, a.kind, a.last_activity_at, a.opened_at, a.resolved_at
+        ),
+        queued AS (
+            INSERT INTO alert_notifications (alert_id, channel_id, event, channel_kind, target)
+            SELECT r.id, c.id, 'resolved', c.kind, c.target
+            FROM resolved r
+            JOIN alert_channels c ON c.user_id = r.user_id
+        )
+        SELECT id, device_id, device_name, kind, last_activity_at, opened_at, resolved_at
+        FROM resolved
+        "#,
+        offline_after_seconds,
+        sms_window_seconds,
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(AppError::DatabaseError)?;
+
+    Ok(alerts)
+}
+
+// Claims due notifications by pushing their next attempt past the lease, so
+// concurrent dispatchers skip them until they are recorded
+pub async fn claim_due_alert_notifications(
+    pool: &PgPool,
+    limit: i64,
+    lease_seconds: f64,
+) -> Result<Vec<PendingAlertNotification>, AppError> {
+    let rows = sqlx::query!(
+        r#"
+        WITH due AS (
+            SELECT id
+            FROM alert_notifications
+            WHERE status = 'pending' AND next_attempt_at <= NOW()
+            ORDER BY next_attempt_at
+            LIMIT $1
+            FOR UPDATE SKIP LOCKED
+        )
+        UPDATE alert_notifications n
+        SET next_attempt_at = NOW() + make_interval(secs => $2)
+        FROM due, alert_channels c, device_alerts a, devices d
+        WHERE n.id = due.id AND c.id = n.channel_id AND a.id = n.alert_id AND d.id = a.device_id
+        RETURNING n.id, n.attempts, n.event,
+            c.id AS channel_id, c.kind, c.target, c.secret, c.created_at, c.updated_at,
+            a.id AS alert_id, a.device_id, d.device_name, a.kind AS alert_kind,
+            a.last_activity_at, a.opened_at, a.resolved_at
+        "#,
+        limit,
+        lease_seconds,
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(AppError::DatabaseError)?;
+
+    let notifications = rows
+        .into_iter()
+        .map(|row| PendingAlertNotification {
+            id: row.id,
+            attempts: row.attempts,
+            event: if row.event == AlertEvent::Resolved.as_str() { AlertEvent::Resolved } else { AlertEvent::Opened },
+            channel: AlertChannel {
+                id: row.channel_id,
+                kind: row.kind,
+                target: row.target,
+                secret: row.secret,
+                created_at: row.created_at,
+                updated_at: row.updated_at,
+            },
+            alert: DeviceAlert {
+                id: row.alert_id,
+                device_id: row.device_id,
+                device_name: row.device_name,
+                kind: row.alert_kind,
+                last_activity_at: row.last_activity_at,
+                opened_at: row.opened_at,
+                resolved_at: row.resolved_at,
+            },
+        })
+        .collect();
+
+    Ok(notifications)
+}
+
+// Moves a notification to its next state after an attempt
+pub async fn record_alert_notification_attempt(
+    pool: &PgPool,
+    notification_id: Uuid,
+    attempt_number: i32,
+    status: &str,
+    next_attempt_at: DateTime<Utc>,
+    error: Option<&str>,
+) -> Result<(), AppError> {
+    sqlx::query!(
+        r#"
+        UPDATE alert_notifications
+        SET attempts = $2, status = $3, next_attempt_at = $4, error = $5
+        WHERE id = $1
+        "#,
+        notification_id,
+        attempt_number,
+        status,
+        next_attempt_at,
+        error,
+    )
+    .execute(pool)
+    .await
+    .map_err(AppError::DatabaseError)?;
+
+    Ok(())
+}
+
+// Alerts for the user's devices, newest first
+pub async fn get_user_alerts(
+    pool: &PgPool,
+    user_id: Uuid,
+    device_id: Option<Uuid>,
+    open: Option<bool>,
+    limit: i64,
+    offset: i64,
+) -> Result<(Vec<DeviceAlert>, i64), AppError> {
+    let rows = sqlx::query_as!(
+        DeviceAlert,
+        r#"
+        SELECT a.id, a.device_id, d.device_name, a.kind, a.last_activity_at, a.opened_at, a.resolved_at
+        FROM device_alerts a
+        JOIN devices d ON d.id = a.device_id
+        WHERE d.user_id = $1
+        AND ($2::uuid IS NULL OR a.device_id = $2)
+        AND ($3::boolean IS NULL OR (a.resolved_at IS NULL) = $3)
+        ORDER BY a.opened_at DESC
+        LIMIT $4 OFFSET $5
+        "#,
+        user_id,
+        device_id,
+        open,
+        limit,
+        offset,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let total = sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(*)
+        FROM device_alerts a
+        JOIN devices d ON d.id = a.device_id
+        WHERE d.user_id = $1
+        AND ($2::uuid IS NULL OR a.device_id = $2)
+        AND ($3::boolean IS NULL OR (a.resolved_at IS NULL) = $3)
+        "#,
+        user_id,
+        device_id,
+        open,
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok((rows, total.unwrap_or(0)))
+}
+
+pub async fn find_alert_notifications(pool: &PgPool, alert_ids: &[Uuid]) -> Result<Vec<AlertNotification>, AppError> {
+    let notifications = sqlx::query_as!(
+        AlertNotification,
+        r#"
+        SELECT alert_id, channel_id, event, channel_kind, target, status, attempts, error, created_at, updated_at
+        FROM alert_notifications
+        WHERE alert_id = ANY($1)
+        ORDER BY alert_id, created_at
+        "#,
+        alert_ids
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(AppError::DatabaseError)?;
+
+    Ok(notifications)
 }
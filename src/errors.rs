@@ -16,6 +16,9 @@ pub enum AppError {
     #[error("Database error: {0}")]
     DatabaseError(#[from] sqlx::Error),
 
+    #[error("Storage error: {0}")]
+    StorageError(#[from] crate::storage::StorageError),
+
     #[error("Password hashing error: {0}")]
     PasswordHashingError(#[from] bcrypt::BcryptError),
 
@@ -34,6 +37,54 @@ pub enum AppError {
     #[error("Device already exists")]
     DeviceAlreadyExists,
 
+    #[error("Device not found")]
+    DeviceNotFound,
+
+    #[error("Device transfer not found")]
+    TransferNotFound,
+
+    #[error("Alert channel not found")]
+    AlertChannelNotFound,
+
+    #[error("Session not found")]
+    SessionNotFound,
+
+    #[error("Webhook not found")]
+    WebhookNotFound,
+
+    #[error("Rule not found")]
+    RuleNotFound,
+
+    #[error("No matching one-time code")]
+    OtpNotFound,
+
+    #[error("OTP pattern not found")]
+    OtpPatternNotFound,
+
+    #[error("SMS not found")]
+    SmsNotFound,
+
+    #[error("Outbound message not found")]
+    OutboundSmsNotFound,
+
+    #[error("Attachment not found")]
+    AttachmentNotFound,
+
+    #[error("Label not found")]
+    LabelNotFound,
+
+    #[error("Retention policy not found")]
+    RetentionPolicyNotFound,
+
+    #[error("Payload too large: {0}")]
+    PayloadTooLarge(String),
+
+    #[error("Unsupported media type: {0}")]
+    UnsupportedMediaType(String),
+
+    #[error("Conflict: {0}")]
+    Conflict(String),
+
     #[error("Bad request: {0}")]
     BadRequest(String),
 
@@ -54,6 +105,10 @@ impl IntoResponse for AppError {
                  // Check for specific DB errors if needed, otherwise generic
                  (StatusCode::INTERNAL_SERVER_ERROR, "Database operation failed".to_string())
             }
+            AppError::StorageError(e) => {
+                error!("Storage error: {:?}", e);
+                (StatusCode::INTERNAL_SERVER_ERROR, "Attachment storage failed".to_string())
+            }
             AppError::PasswordHashingError(e) => {
                 error!("Password hashing error: {:?}", e);
                 (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error".to_string())
@@ -73,7 +128,55 @@ impl IntoResponse for AppError {
                 (StatusCode::CONFLICT, "Username is already in use".to_string()) 
             }
             AppError::DeviceAlreadyExists => {
-                (StatusCode::CONFLICT, "Device Name is already in use".to_string()) 
+                (StatusCode::CONFLICT, "You already have a device with this name".to_string())
+            }
+            AppError::DeviceNotFound => {
+                (StatusCode::NOT_FOUND, "Device not found".to_string())
+            }
+            AppError::TransferNotFound => {
+                (StatusCode::NOT_FOUND, "Device transfer not found".to_string())
+            }
+            AppError::AlertChannelNotFound => {
+                (StatusCode::NOT_FOUND, "Alert channel not found".to_string())
+            }
+            AppError::SessionNotFound => {
+                (StatusCode::NOT_FOUND, "Session not found".to_string())
+            }
+            AppError::WebhookNotFound => {
+                (StatusCode::NOT_FOUND, "Webhook not found".to_string())
+            }
+            AppError::RuleNotFound => {
+                (StatusCode::NOT_FOUND, "Rule not found".to_string())
+            }
+            AppError::OtpNotFound => {
+                (StatusCode::NOT_FOUND, "No matching one-time code".to_string())
+            }
+            AppError::OtpPatternNotFound => {
+                (StatusCode::NOT_FOUND, "OTP pattern not found".to_string())
+            }
+            AppError::SmsNotFound => {
+                (StatusCode::NOT_FOUND, "SMS not found".to_string())
+            }
+            AppError::OutboundSmsNotFound => {
+                (StatusCode::NOT_FOUND, "Outbound message not found".to_string())
+            }
+            AppError::AttachmentNotFound => {
+                (StatusCode::NOT_FOUND, "Attachment not found".to_string())
+            }
+            AppError::LabelNotFound => {
+                (StatusCode::NOT_FOUND, "Label not found".to_string())
+            }
+            AppError::RetentionPolicyNotFound => {
+                (StatusCode::NOT_FOUND, "Retention policy not found".to_string())
+            }
+            AppError::PayloadTooLarge(msg) => {
+                (StatusCode::PAYLOAD_TOO_LARGE, msg)
+            }
+            AppError::UnsupportedMediaType(msg) => {
+                (StatusCode::UNSUPPORTED_MEDIA_TYPE, msg)
+            }
+            AppError::Conflict(msg) => {
+                (StatusCode::CONFLICT, msg)
             }
             AppError::BadRequest(msg) => {
                 (StatusCode::BAD_REQUEST, msg)
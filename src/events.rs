@@ -0,0 +1,39 @@
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+use crate::models::sms::Sms;
+
+const CHANNEL_CAPACITY: usize = 1024;
+
+#[derive(Debug, Clone)]
+pub enum SmsEvent {
+    // A stored SMS, tagged with the owner of the device it arrived on
+    Stored { user_id: Uuid, sms: Box<Sms> },
+    // Something was queued for the device to send
+    OutboundQueued { device_id: Uuid },
+    // Notifications may have been missed; subscribers should resync from the DB
+    Interrupted,
+}
+
+// In-process fan-out of newly stored SMS to streaming clients, and of queued
+// outbound messages to phones long-polling for them
+#[derive(Clone)]
+pub struct SmsEvents {
+    sender: broadcast::Sender<SmsEvent>,
+}
+
+impl SmsEvents {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(CHANNEL_CAPACITY);
+        SmsEvents { sender }
+    }
+
+    pub fn publish(&self, event: SmsEvent) {
+        // Sending only fails when nobody is listening, which is fine
+        let _ = self.sender.send(event);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<SmsEvent> {
+        self.sender.subscribe()
+    }
+}
@@ -0,0 +1,173 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::Utc;
+use lettre::Address;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use crate::{
+    auth::{middleware::AuthRequired, secret},
+    errors::AppError,
+    handlers::{device::require_owned_device, webhook::validate_url},
+    AppState,
+    db
+};
+use crate::models::alert::{
+    AlertChannelListResponse,
+    AlertChannelPayload,
+    AlertChannelTestResponse,
+    AlertEvent,
+    AlertHistoryItem,
+    AlertListResponse,
+    AlertNotification,
+    AlertQuery,
+    ChannelKind,
+    CreateAlertChannelResponse,
+    DeviceAlert,
+    ALERT_HEARTBEAT
+};
+
+pub async fn create_channel(
+    auth_wrapper: AuthRequired,
+    State(state): State<AppState>,
+    Json(payload): Json<AlertChannelPayload>,
+) -> Result<Json<CreateAlertChannelResponse>, AppError> {
+    let user = auth_wrapper.0;
+
+    let target = payload.target.trim();
+    let signing_secret = match payload.kind {
+        ChannelKind::Webhook => {
+            validate_url(target)?;
+            Some(secret::generate_secret())
+        }
+        ChannelKind::Email => {
+            if !state.notifiers.supports(payload.kind.as_str()) {
+                return Err(AppError::BadRequest("Email alerts are not configured on this server".to_string()));
+            }
+            target
+                .parse::<Address>()
+                .map_err(|_| AppError::BadRequest("Invalid email address".to_string()))?;
+            None
+        }
+    };
+
+    let channel = db::create_alert_channel(
+        &state.db_pool,
+        user.user_id,
+        payload.kind.as_str(),
+        target,
+        signing_secret.as_deref(),
+    )
+    .await?;
+
+    Ok(Json(CreateAlertChannelResponse {
+        channel,
+        secret: signing_secret,
+    }))
+}
+
+pub async fn list_channels(
+    auth_wrapper: AuthRequired,
+    State(state): State<AppState>,
+) -> Result<Json<AlertChannelListResponse>, AppError> {
+    let user = auth_wrapper.0;
+
+    let channels = db::find_user_alert_channels(&state.db_pool, user.user_id).await?;
+
+    Ok(Json(AlertChannelListResponse { channels }))
+}
+
+pub async fn delete_channel(
+    auth_wrapper: AuthRequired,
+    State(state): State<AppState>,
+    Path(channel_id): Path<Uuid>,
+) -> Result<StatusCode, AppError> {
+    let user = auth_wrapper.0;
+
+    if !db::delete_user_alert_channel(&state.db_pool, channel_id, user.user_id).await? {
+        return Err(AppError::AlertChannelNotFound);
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// Sends a sample alert right away, so a channel can be checked before it's needed
+pub async fn test_channel(
+    auth_wrapper: AuthRequired,
+    State(state): State<AppState>,
+    Path(channel_id): Path<Uuid>,
+) -> Result<Json<AlertChannelTestResponse>, AppError> {
+    let user = auth_wrapper.0;
+
+    let channel = db::find_user_alert_channel(&state.db_pool, channel_id, user.user_id)
+        .await?
+        .ok_or(AppError::AlertChannelNotFound)?;
+
+    let now = Utc::now();
+    let sample = DeviceAlert {
+        id: Uuid::nil(),
+        device_id: Uuid::nil(),
+        device_name: "Test device".to_string(),
+        kind: ALERT_HEARTBEAT.to_string(),
+        last_activity_at: Some(now),
+        opened_at: now,
+        resolved_at: None,
+    };
+
+    let error = state
+        .notifiers
+        .notify(&channel, &sample, AlertEvent::Test)
+        .await
+        .err()
+        .map(|e| e.to_string());
+
+    Ok(Json(AlertChannelTestResponse {
+        delivered: error.is_none(),
+        error,
+    }))
+}
+
+// Alert history for the user's devices, with the notifications each one sent
+pub async fn list_alerts(
+    auth_wrapper: AuthRequired,
+    State(state): State<AppState>,
+    Query(params): Query<AlertQuery>,
+) -> Result<Json<AlertListResponse>, AppError> {
+    let user = auth_wrapper.0;
+
+    if let Some(device_id) = params.device_id {
+        require_owned_device(&state, device_id, user.user_id).await?;
+    }
+
+    let limit = params.limit.unwrap_or(20).min(100);
+    let offset = params.offset.unwrap_or(0);
+
+    let (alerts, total) = db::get_user_alerts(
+        &state.db_pool,
+        user.user_id,
+        params.device_id,
+        params.open,
+        limit,
+        offset,
+    )
+    .await?;
+
+    let ids: Vec<Uuid> = alerts.iter().map(|alert| alert.id).collect();
+    let mut notifications: HashMap<Uuid, Vec<AlertNotification>> = HashMap::new();
+    for notification in db::find_alert_notifications(&state.db_pool, &ids).await? {
+        notifications.entry(notification.alert_id).or_default().push(notification);
+    }
+
+    let data = alerts
+        .into_iter()
+        .map(|alert| AlertHistoryItem {
+            notifications: notifications.remove(&alert.id).unwrap_or_default(),
+            alert,
+        })
+        .collect();
+
+    Ok(Json(AlertListResponse { total, data }))
+}
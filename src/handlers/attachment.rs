@@ -0,0 +1,55 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, HeaderValue},
+    response::{IntoResponse, Response},
+};
+use uuid::Uuid;
+
+use crate::{
+    attachments,
+    db,
+    errors::AppError,
+    models::attachment::DownloadQuery,
+    AppState,
+};
+
+// Serves attachment bytes to anyone holding a valid signed URL. The URLs are
+// only handed out in responses to the message owner and expire quickly.
+pub async fn download_attachment(
+    State(state): State<AppState>,
+    Path(attachment_id): Path<Uuid>,
+    Query(params): Query<DownloadQuery>,
+) -> Result<Response, AppError> {
+    if !attachments::verify_download(&state.config, attachment_id, params.expires, &params.signature) {
+        return Err(AppError::Unauthorized);
+    }
+
+    let attachment = db::find_attachment_by_id(&state.db_pool, attachment_id)
+        .await?
+        .ok_or(AppError::AttachmentNotFound)?;
+
+    let data = state
+        .blob_store
+        .get(&attachment.storage_key)
+        .await?
+        .ok_or(AppError::AttachmentNotFound)?;
+
+    let content_type = HeaderValue::from_str(&attachment.content_type)
+        .unwrap_or(HeaderValue::from_static("application/octet-stream"));
+
+    // Filenames come from the phone, so only plain ones are echoed back
+    let disposition = attachment
+        .filename
+        .filter(|name| name.chars().all(|c| c.is_ascii_alphanumeric() || "._- ".contains(c)))
+        .and_then(|name| HeaderValue::from_str(&format!("inline; filename=\"{}\"", name)).ok())
+        .unwrap_or(HeaderValue::from_static("inline"));
+
+    let headers = [
+        (header::CONTENT_TYPE, content_type),
+        (header::CONTENT_DISPOSITION, disposition),
+        (header::X_CONTENT_TYPE_OPTIONS, HeaderValue::from_static("nosniff")),
+        (header::CACHE_CONTROL, HeaderValue::from_static("private, max-age=3600")),
+    ];
+
+    Ok((headers, data).into_response())
+}
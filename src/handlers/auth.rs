@@ -1,16 +1,21 @@
-use axum::{extract::State, Json};
+use axum::{extract::State, http::StatusCode, Json};
+use axum_extra::{headers::UserAgent, TypedHeader};
+use chrono::{Duration, Utc};
+use uuid::Uuid;
+use crate::models::session::{NewSession, RefreshOutcome, RefreshPayload};
 use crate::models::user::{RegisterPayload, LoginPayload, LoginResponse, NewUser};
 use crate::db;
-use crate::auth::{password, jwt};
+use crate::auth::{middleware::AuthRequired, password, jwt, secret};
 use crate::errors::AppError;
 use crate::AppState;
 
 pub async fn register_handler(
     State(state): State<AppState>,
+    user_agent: Option<TypedHeader<UserAgent>>,
     Json(payload): Json<RegisterPayload>,
 ) -> Result<Json<LoginResponse>, AppError> {
 
-    if payload.username.is_empty() || payload.password.len() == 0 {
+    if payload.username.is_empty() || payload.password.is_empty() {
         return Err(AppError::BadRequest("Invalid name or password too short".to_string()));
     }
 
@@ -23,12 +28,13 @@ pub async fn register_handler(
 
     let user = db::create_user(&state.db_pool, &new_user_data).await?;
 
-    let token = jwt::create_jwt(user.id, &state.config.jwt_secret, state.config.jwt_expiration_seconds)?;
-    Ok(Json(LoginResponse { token, token_type: "Bearer".to_string() }))
+    let response = start_session(&state, user.id, user_agent).await?;
+    Ok(Json(response))
 }
 
 pub async fn login_handler(
     State(state): State<AppState>,
+    user_agent: Option<TypedHeader<UserAgent>>,
     Json(payload): Json<LoginPayload>,
 ) -> Result<Json<LoginResponse>, AppError> {
      if payload.username.is_empty() || payload.password.is_empty() {
@@ -45,10 +51,93 @@ pub async fn login_handler(
         return Err(AppError::InvalidCredentials);
     }
 
-    let token = jwt::create_jwt(user.id, &state.config.jwt_secret, state.config.jwt_expiration_seconds)?;
+    let response = start_session(&state, user.id, user_agent).await?;
+    Ok(Json(response))
+}
+
+// Exchange a refresh token for a new access/refresh token pair
+pub async fn refresh_handler(
+    State(state): State<AppState>,
+    Json(payload): Json<RefreshPayload>,
+) -> Result<Json<LoginResponse>, AppError> {
+    let refresh_token = secret::generate_secret();
+    let expires_at = Utc::now() + Duration::seconds(state.config.refresh_token_expiration_seconds);
+
+    let outcome = db::rotate_refresh_token(
+        &state.db_pool,
+        &secret::hash_secret(&payload.refresh_token),
+        &secret::hash_secret(&refresh_token),
+        expires_at,
+    ).await?;
+
+    let session = match outcome {
+        RefreshOutcome::Rotated(session) => session,
+        RefreshOutcome::Reused => {
+            tracing::warn!("Refresh token reuse detected, session revoked");
+            return Err(AppError::Unauthorized);
+        }
+        RefreshOutcome::Invalid => return Err(AppError::Unauthorized),
+    };
+
+    let token = jwt::create_jwt(session.user_id, session.id, &state.config.jwt_secret, state.config.jwt_expiration_seconds)?;
 
     Ok(Json(LoginResponse {
         token,
         token_type: "Bearer".to_string(),
+        expires_in: state.config.jwt_expiration_seconds,
+        refresh_token,
     }))
 }
+
+// Revoke the presented access token and end its session
+pub async fn logout_handler(
+    auth_wrapper: AuthRequired,
+    State(state): State<AppState>,
+) -> Result<StatusCode, AppError> {
+    let user = auth_wrapper.0;
+
+    db::revoke_access_token(&state.db_pool, user.token_id, user.user_id, user.token_expires_at).await?;
+    db::revoke_user_session(&state.db_pool, user.session_id, user.user_id).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// Invalidate every token and session the user currently holds
+pub async fn logout_all_handler(
+    auth_wrapper: AuthRequired,
+    State(state): State<AppState>,
+) -> Result<StatusCode, AppError> {
+    let user = auth_wrapper.0;
+
+    db::revoke_all_user_tokens(&state.db_pool, user.user_id).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// Open a new session for the user and mint its first token pair
+async fn start_session(
+    state: &AppState,
+    user_id: Uuid,
+    user_agent: Option<TypedHeader<UserAgent>>,
+) -> Result<LoginResponse, AppError> {
+    let refresh_token = secret::generate_secret();
+    let refresh_token_hash = secret::hash_secret(&refresh_token);
+
+    let new_session = NewSession {
+        user_id: &user_id,
+        user_agent: user_agent.as_ref().map(|TypedHeader(ua)| ua.as_str()),
+        refresh_token_hash: &refresh_token_hash,
+        expires_at: Utc::now() + Duration::seconds(state.config.refresh_token_expiration_seconds),
+    };
+
+    let session = db::create_session(&state.db_pool, &new_session).await?;
+
+    let token = jwt::create_jwt(user_id, session.id, &state.config.jwt_secret, state.config.jwt_expiration_seconds)?;
+
+    Ok(LoginResponse {
+        token,
+        token_type: "Bearer".to_string(),
+        expires_in: state.config.jwt_expiration_seconds,
+        refresh_token,
+    })
+}
@@ -0,0 +1,94 @@
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use uuid::Uuid;
+
+use crate::{
+    attachments,
+    auth::middleware::AuthRequired,
+    errors::AppError,
+    handlers::device::require_owned_device,
+    phone,
+    AppState,
+    db
+};
+use crate::models::conversation::{
+    ConversationListResponse,
+    ConversationMessagesQuery,
+    ConversationMessagesResponse,
+    ConversationQuery,
+    MarkReadResponse
+};
+
+pub async fn list_conversations(
+    auth_wrapper: AuthRequired,
+    State(state): State<AppState>,
+    Query(params): Query<ConversationQuery>,
+) -> Result<Json<ConversationListResponse>, AppError> {
+    let user = auth_wrapper.0;
+
+    if let Some(device_id) = params.device_id {
+        require_owned_device(&state, device_id, user.user_id).await?;
+    }
+
+    let limit = params.limit.unwrap_or(20).min(100);
+    let offset = params.offset.unwrap_or(0);
+
+    let (data, total) = db::get_user_conversations(
+        &state.db_pool,
+        user.user_id,
+        params.device_id,
+        limit,
+        offset,
+    )
+    .await?;
+
+    Ok(Json(ConversationListResponse { total, data }))
+}
+
+// The counterpart may be given as listed or as the raw sender; both normalize
+// to the same thread.
+pub async fn get_conversation_messages(
+    auth_wrapper: AuthRequired,
+    State(state): State<AppState>,
+    Path((device_id, counterpart)): Path<(Uuid, String)>,
+    Query(params): Query<ConversationMessagesQuery>,
+) -> Result<Json<ConversationMessagesResponse>, AppError> {
+    let user = auth_wrapper.0;
+
+    let device = require_owned_device(&state, device_id, user.user_id).await?;
+
+    let counterpart = phone::normalize_sender(&counterpart, device.default_region.as_deref()).counterpart;
+    let limit = params.limit.unwrap_or(20).min(100);
+    let offset = params.offset.unwrap_or(0);
+
+    let (messages, total) = db::get_conversation_messages(
+        &state.db_pool,
+        device_id,
+        &counterpart,
+        params.before,
+        limit,
+        offset,
+    )
+    .await?;
+
+    let data = attachments::attach(&state.db_pool, &state.config, messages).await?;
+
+    Ok(Json(ConversationMessagesResponse { total, data }))
+}
+
+pub async fn mark_conversation_read(
+    auth_wrapper: AuthRequired,
+    State(state): State<AppState>,
+    Path((device_id, counterpart)): Path<(Uuid, String)>,
+) -> Result<Json<MarkReadResponse>, AppError> {
+    let user = auth_wrapper.0;
+
+    let device = require_owned_device(&state, device_id, user.user_id).await?;
+
+    let counterpart = phone::normalize_sender(&counterpart, device.default_region.as_deref()).counterpart;
+    let updated = db::mark_conversation_read(&state.db_pool, device_id, &counterpart).await?;
+
+    Ok(Json(MarkReadResponse { updated }))
+}
@@ -1,17 +1,51 @@
-use axum::{extract::State, Json};
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::{Duration, Utc};
+use std::collections::HashMap;
+use uuid::Uuid;
 
 use crate::{
-    auth::middleware::AuthRequired,
+    attachments,
+    auth::{device_token, middleware::{AuthRequired, DeviceAuthRequired}, secret},
     errors::AppError,
+    phone,
     AppState,
     db
 };
 use crate::models::device::{
+    DeleteDeviceQuery,
+    Device,
+    DeviceSummary,
     FindAllResponse,
+    IngestTokenResponse,
     NewDevice,
     RegisterPayload,
-    RegisterResponse
+    RegisterResponse,
+    UpdateDevicePayload
+};
+use crate::models::heartbeat::{
+    HeartbeatListResponse,
+    HeartbeatPayload,
+    HeartbeatQuery,
+    NewHeartbeat
 };
+use crate::models::transfer::{
+    DeviceTransfer,
+    TransferAcceptPayload,
+    TransferAcceptedResponse,
+    TransferListResponse,
+    TransferPayload,
+    STATUS_CANCELLED,
+    STATUS_DECLINED
+};
+
+const MAX_DEVICE_NAME_LENGTH: usize = 255;
+// Heartbeats kept per device, newest first
+const HEARTBEAT_HISTORY: i64 = 100;
+const MAX_HEARTBEAT_FIELD_LENGTH: usize = 64;
 
 pub async fn register_device(
     auth_wrapper: AuthRequired,
@@ -20,14 +54,25 @@ pub async fn register_device(
 ) -> Result<Json<RegisterResponse>, AppError> {
     let user = auth_wrapper.0;
 
+    let device_name = validate_name(&payload.device_name)?;
+    let default_region = payload.default_region.as_deref().map(validate_region).transpose()?;
+
+    let ingest_secret = secret::generate_secret();
+    let secret_hash = secret::hash_secret(&ingest_secret);
+
     let new_device = NewDevice {
-        device_name: payload.device_name.as_str(),
+        device_name,
         user_id: &user.user_id,
+        default_region: default_region.as_deref(),
+        ingest_secret_hash: &secret_hash,
     };
 
     let device = db::create_device(&state.db_pool, &new_device).await?;
 
-    Ok(Json(RegisterResponse{ device_id: device.id }))
+    Ok(Json(RegisterResponse {
+        device_id: device.id,
+        ingest_token: device_token::encode_token(device.id, &ingest_secret),
+    }))
 }
 
 pub async fn find_all_user_devices(
@@ -37,6 +82,314 @@ pub async fn find_all_user_devices(
     let user = auth_wrapper.0;
 
     let devices = db::find_user_devices(&state.db_pool, user.user_id).await?;
+    let unread: HashMap<Uuid, i64> = db::count_user_unread_sms(&state.db_pool, user.user_id)
+        .await?
+        .into_iter()
+        .collect();
+
+    let mut health: HashMap<Uuid, _> = db::find_latest_heartbeats(&state.db_pool, user.user_id)
+        .await?
+        .into_iter()
+        .map(|heartbeat| (heartbeat.device_id, heartbeat))
+        .collect();
+
+    let now = Utc::now();
+    let offline_after = Duration::seconds(state.config.device_offline_after_seconds);
+    let devices = devices
+        .into_iter()
+        .map(|device| DeviceSummary {
+            status: device.status(now, offline_after),
+            unread_count: unread.get(&device.id).copied().unwrap_or(0),
+            health: health.remove(&device.id),
+            device,
+        })
+        .collect();
 
     Ok(Json(FindAllResponse { devices }))
 }
+
+pub async fn update_device(
+    auth_wrapper: AuthRequired,
+    State(state): State<AppState>,
+    Path(device_id): Path<Uuid>,
+    Json(payload): Json<UpdateDevicePayload>,
+) -> Result<Json<Device>, AppError> {
+    let user = auth_wrapper.0;
+
+    let device_name = payload.device_name.as_deref().map(validate_name).transpose()?;
+    let default_region = payload.default_region.as_deref().map(validate_region).transpose()?;
+
+    let device = db::update_user_device(
+        &state.db_pool,
+        device_id,
+        user.user_id,
+        device_name,
+        default_region.as_deref(),
+    )
+    .await?
+    .ok_or(AppError::DeviceNotFound)?;
+
+    Ok(Json(device))
+}
+
+// Deletes the device and all of its messages, or with `keep_history=true`
+// retires it: the messages stay but the phone can no longer post or send
+pub async fn delete_device(
+    auth_wrapper: AuthRequired,
+    State(state): State<AppState>,
+    Path(device_id): Path<Uuid>,
+    Query(params): Query<DeleteDeviceQuery>,
+) -> Result<StatusCode, AppError> {
+    let user = auth_wrapper.0;
+
+    if params.keep_history {
+        if !db::retire_user_device(&state.db_pool, device_id, user.user_id).await? {
+            return Err(AppError::DeviceNotFound);
+        }
+        return Ok(StatusCode::NO_CONTENT);
+    }
+
+    let storage_keys = db::delete_user_device(&state.db_pool, device_id, user.user_id)
+        .await?
+        .ok_or(AppError::DeviceNotFound)?;
+    attachments::discard(state.blob_store.as_ref(), &storage_keys).await;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// Offers the device to another user. Nothing moves until they accept. An
+// offer to a name nobody has is recorded like any other, so the response
+// doesn't reveal which usernames exist.
+pub async fn transfer_device(
+    auth_wrapper: AuthRequired,
+    State(state): State<AppState>,
+    Path(device_id): Path<Uuid>,
+    Json(payload): Json<TransferPayload>,
+) -> Result<Json<DeviceTransfer>, AppError> {
+    let user = auth_wrapper.0;
+
+    let device = require_owned_device(&state, device_id, user.user_id).await?;
+    if device.retired_at.is_some() {
+        return Err(AppError::Conflict("Retired devices can't be transferred".to_string()));
+    }
+
+    let username = payload.username.trim();
+    if username.is_empty() {
+        return Err(AppError::BadRequest("Username is required".to_string()));
+    }
+
+    let recipient = db::find_user_by_name(&state.db_pool, username).await?;
+    if recipient.as_ref().is_some_and(|recipient| recipient.id == user.user_id) {
+        return Err(AppError::BadRequest("Can't transfer a device to yourself".to_string()));
+    }
+
+    let transfer = db::create_device_transfer(
+        &state.db_pool,
+        device_id,
+        user.user_id,
+        recipient.map(|recipient| recipient.id),
+        username,
+        payload.include_history,
+    )
+    .await?;
+
+    Ok(Json(transfer))
+}
+
+pub async fn list_transfers(
+    auth_wrapper: AuthRequired,
+    State(state): State<AppState>,
+) -> Result<Json<TransferListResponse>, AppError> {
+    let user = auth_wrapper.0;
+
+    let (incoming, outgoing) = db::find_user_transfers(&state.db_pool, user.user_id)
+        .await?
+        .into_iter()
+        .partition(|transfer| transfer.to_user_id == Some(user.user_id));
+
+    Ok(Json(TransferListResponse { incoming, outgoing }))
+}
+
+// Takes ownership of the device. The phone is issued a new ingest secret, so
+// the previous owner's token stops working. The body is optional; it can give
+// the device a new name if the caller already has a device by its name.
+pub async fn accept_transfer(
+    auth_wrapper: AuthRequired,
+    State(state): State<AppState>,
+    Path(transfer_id): Path<Uuid>,
+    payload: Option<Json<TransferAcceptPayload>>,
+) -> Result<Json<TransferAcceptedResponse>, AppError> {
+    let user = auth_wrapper.0;
+
+    let device_name = payload.as_ref().and_then(|Json(payload)| payload.device_name.as_deref());
+    let device_name = device_name.map(validate_name).transpose()?;
+
+    let ingest_secret = secret::generate_secret();
+    let secret_hash = secret::hash_secret(&ingest_secret);
+
+    let transfer = db::accept_device_transfer(&state.db_pool, transfer_id, user.user_id, &secret_hash, device_name)
+        .await?
+        .ok_or(AppError::TransferNotFound)?;
+
+    Ok(Json(TransferAcceptedResponse {
+        ingest_token: device_token::encode_token(transfer.device_id, &ingest_secret),
+        transfer,
+    }))
+}
+
+pub async fn decline_transfer(
+    auth_wrapper: AuthRequired,
+    State(state): State<AppState>,
+    Path(transfer_id): Path<Uuid>,
+) -> Result<StatusCode, AppError> {
+    let user = auth_wrapper.0;
+
+    if !db::close_device_transfer(&state.db_pool, transfer_id, user.user_id, STATUS_DECLINED).await? {
+        return Err(AppError::TransferNotFound);
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn cancel_transfer(
+    auth_wrapper: AuthRequired,
+    State(state): State<AppState>,
+    Path(transfer_id): Path<Uuid>,
+) -> Result<StatusCode, AppError> {
+    let user = auth_wrapper.0;
+
+    if !db::close_device_transfer(&state.db_pool, transfer_id, user.user_id, STATUS_CANCELLED).await? {
+        return Err(AppError::TransferNotFound);
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// Records the phone's health and marks it as seen
+pub async fn heartbeat(
+    device_wrapper: DeviceAuthRequired,
+    State(state): State<AppState>,
+    Json(payload): Json<HeartbeatPayload>,
+) -> Result<StatusCode, AppError> {
+    let device = device_wrapper.0;
+
+    if payload.battery_level.is_some_and(|level| !(0..=100).contains(&level)) {
+        return Err(AppError::BadRequest("battery_level must be between 0 and 100".to_string()));
+    }
+    if payload.signal_strength.is_some_and(|dbm| !(-150..=0).contains(&dbm)) {
+        return Err(AppError::BadRequest("signal_strength must be in dBm, between -150 and 0".to_string()));
+    }
+
+    let sim = payload.sim.as_ref();
+    let sim_country = sim
+        .and_then(|sim| sim.country.as_deref())
+        .map(|country| {
+            phone::parse_region(country)
+                .ok_or_else(|| AppError::BadRequest(format!("Unknown SIM country: {}", country)))
+        })
+        .transpose()?;
+
+    let new_heartbeat = NewHeartbeat {
+        device_id: device.device_id,
+        battery_level: payload.battery_level,
+        is_charging: payload.is_charging,
+        signal_strength: payload.signal_strength,
+        network_type: payload.network_type.map(|network| network.as_str()),
+        app_version: optional_field("app_version", payload.app_version.as_deref())?,
+        sim_operator: optional_field("sim.operator", sim.and_then(|sim| sim.operator.as_deref()))?,
+        sim_country: sim_country.as_deref(),
+    };
+
+    db::record_heartbeat(&state.db_pool, &new_heartbeat, HEARTBEAT_HISTORY).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// The device's recent heartbeats, newest first
+pub async fn list_heartbeats(
+    auth_wrapper: AuthRequired,
+    State(state): State<AppState>,
+    Path(device_id): Path<Uuid>,
+    Query(params): Query<HeartbeatQuery>,
+) -> Result<Json<HeartbeatListResponse>, AppError> {
+    let user = auth_wrapper.0;
+
+    require_owned_device(&state, device_id, user.user_id).await?;
+
+    let limit = params.limit.unwrap_or(20).clamp(1, HEARTBEAT_HISTORY);
+    let data = db::find_device_heartbeats(&state.db_pool, device_id, limit).await?;
+
+    Ok(Json(HeartbeatListResponse { data }))
+}
+
+// Issue a new ingest secret, invalidating the previous one
+pub async fn rotate_ingest_secret(
+    auth_wrapper: AuthRequired,
+    State(state): State<AppState>,
+    Path(device_id): Path<Uuid>,
+) -> Result<Json<IngestTokenResponse>, AppError> {
+    let user = auth_wrapper.0;
+
+    let ingest_secret = secret::generate_secret();
+    let secret_hash = secret::hash_secret(&ingest_secret);
+
+    if !db::set_device_ingest_secret(&state.db_pool, device_id, user.user_id, Some(&secret_hash)).await? {
+        return Err(AppError::DeviceNotFound);
+    }
+
+    Ok(Json(IngestTokenResponse {
+        device_id,
+        ingest_token: device_token::encode_token(device_id, &ingest_secret),
+    }))
+}
+
+// Revoke the device's ingest secret; the phone can't post SMS until a new one is issued
+pub async fn revoke_ingest_secret(
+    auth_wrapper: AuthRequired,
+    State(state): State<AppState>,
+    Path(device_id): Path<Uuid>,
+) -> Result<StatusCode, AppError> {
+    let user = auth_wrapper.0;
+
+    if !db::set_device_ingest_secret(&state.db_pool, device_id, user.user_id, None).await? {
+        return Err(AppError::DeviceNotFound);
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// Look up a device for its owner. Other users' devices are reported as
+// missing rather than forbidden, so device ids can't be probed.
+pub async fn require_owned_device(state: &AppState, device_id: Uuid, user_id: Uuid) -> Result<Device, AppError> {
+    db::find_user_device(&state.db_pool, device_id, user_id)
+        .await?
+        .ok_or(AppError::DeviceNotFound)
+}
+
+fn validate_name(name: &str) -> Result<&str, AppError> {
+    let name = name.trim();
+    if name.is_empty() || name.chars().count() > MAX_DEVICE_NAME_LENGTH {
+        return Err(AppError::BadRequest(format!(
+            "Device name must be 1-{} characters",
+            MAX_DEVICE_NAME_LENGTH
+        )));
+    }
+    Ok(name)
+}
+
+// Free-text telemetry; blank values are treated as missing
+fn optional_field<'a>(field: &str, value: Option<&'a str>) -> Result<Option<&'a str>, AppError> {
+    let value = value.map(str::trim).filter(|value| !value.is_empty());
+    if value.is_some_and(|value| value.chars().count() > MAX_HEARTBEAT_FIELD_LENGTH) {
+        return Err(AppError::BadRequest(format!(
+            "{} must be at most {} characters",
+            field, MAX_HEARTBEAT_FIELD_LENGTH
+        )));
+    }
+    Ok(value)
+}
+
+fn validate_region(region: &str) -> Result<String, AppError> {
+    phone::parse_region(region)
+        .ok_or_else(|| AppError::BadRequest(format!("Unknown region code: {}", region)))
+}
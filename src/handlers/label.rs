@@ -0,0 +1,156 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use uuid::Uuid;
+
+use crate::{
+    auth::middleware::AuthRequired,
+    errors::AppError,
+    handlers::sms::{require_criteria, resolve_selection, MAX_BATCH_SIZE},
+    AppState,
+    db
+};
+use crate::models::label::{
+    Label,
+    LabelListResponse,
+    LabelPayload,
+    LabelTargetPayload
+};
+use crate::models::sms::SmsUpdateResponse;
+
+const MAX_LABEL_LENGTH: usize = 64;
+
+pub async fn create_label(
+    auth_wrapper: AuthRequired,
+    State(state): State<AppState>,
+    Json(payload): Json<LabelPayload>,
+) -> Result<Json<Label>, AppError> {
+    let user = auth_wrapper.0;
+
+    let name = validate_name(&payload.name)?;
+    let label = db::create_label(&state.db_pool, user.user_id, name).await?;
+
+    Ok(Json(label))
+}
+
+pub async fn list_labels(
+    auth_wrapper: AuthRequired,
+    State(state): State<AppState>,
+) -> Result<Json<LabelListResponse>, AppError> {
+    let user = auth_wrapper.0;
+
+    let labels = db::find_user_labels(&state.db_pool, user.user_id).await?;
+
+    Ok(Json(LabelListResponse { labels }))
+}
+
+pub async fn rename_label(
+    auth_wrapper: AuthRequired,
+    State(state): State<AppState>,
+    Path(label_id): Path<Uuid>,
+    Json(payload): Json<LabelPayload>,
+) -> Result<Json<Label>, AppError> {
+    let user = auth_wrapper.0;
+
+    let name = validate_name(&payload.name)?;
+    let label = db::rename_user_label(&state.db_pool, label_id, user.user_id, name)
+        .await?
+        .ok_or(AppError::LabelNotFound)?;
+
+    Ok(Json(label))
+}
+
+pub async fn delete_label(
+    auth_wrapper: AuthRequired,
+    State(state): State<AppState>,
+    Path(label_id): Path<Uuid>,
+) -> Result<StatusCode, AppError> {
+    let user = auth_wrapper.0;
+
+    if !db::delete_user_label(&state.db_pool, label_id, user.user_id).await? {
+        return Err(AppError::LabelNotFound);
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// Puts the label on the listed messages, or on everything a filter matches
+pub async fn apply_label(
+    auth_wrapper: AuthRequired,
+    State(state): State<AppState>,
+    Path(label_id): Path<Uuid>,
+    Json(payload): Json<LabelTargetPayload>,
+) -> Result<Json<SmsUpdateResponse>, AppError> {
+    let user = auth_wrapper.0;
+
+    require_owned_label(&state, label_id, user.user_id).await?;
+
+    let updated = match (payload.ids, payload.filter) {
+        (Some(ids), None) => {
+            validate_ids(&ids)?;
+            db::apply_label_by_ids(&state.db_pool, user.user_id, label_id, &ids).await?
+        }
+        (None, Some(selection)) => {
+            require_criteria(&selection, payload.all)?;
+            let filter = resolve_selection(&state, user.user_id, selection).await?;
+            db::apply_label_by_filter(&state.db_pool, user.user_id, label_id, &filter).await?
+        }
+        _ => return Err(AppError::BadRequest("Give either ids or filter".to_string())),
+    };
+
+    Ok(Json(SmsUpdateResponse { updated }))
+}
+
+pub async fn remove_label(
+    auth_wrapper: AuthRequired,
+    State(state): State<AppState>,
+    Path(label_id): Path<Uuid>,
+    Json(payload): Json<LabelTargetPayload>,
+) -> Result<Json<SmsUpdateResponse>, AppError> {
+    let user = auth_wrapper.0;
+
+    require_owned_label(&state, label_id, user.user_id).await?;
+
+    let updated = match (payload.ids, payload.filter) {
+        (Some(ids), None) => {
+            validate_ids(&ids)?;
+            db::remove_label_by_ids(&state.db_pool, user.user_id, label_id, &ids).await?
+        }
+        (None, Some(selection)) => {
+            require_criteria(&selection, payload.all)?;
+            let filter = resolve_selection(&state, user.user_id, selection).await?;
+            db::remove_label_by_filter(&state.db_pool, user.user_id, label_id, &filter).await?
+        }
+        _ => return Err(AppError::BadRequest("Give either ids or filter".to_string())),
+    };
+
+    Ok(Json(SmsUpdateResponse { updated }))
+}
+
+async fn require_owned_label(state: &AppState, label_id: Uuid, user_id: Uuid) -> Result<Label, AppError> {
+    db::find_user_label(&state.db_pool, label_id, user_id)
+        .await?
+        .ok_or(AppError::LabelNotFound)
+}
+
+fn validate_name(name: &str) -> Result<&str, AppError> {
+    let name = name.trim();
+    if name.is_empty() || name.chars().count() > MAX_LABEL_LENGTH {
+        return Err(AppError::BadRequest(format!("Label name must be 1-{} characters", MAX_LABEL_LENGTH)));
+    }
+
+    Ok(name)
+}
+
+fn validate_ids(ids: &[Uuid]) -> Result<(), AppError> {
+    if ids.len() > MAX_BATCH_SIZE {
+        return Err(AppError::BadRequest(format!(
+            "At most {} ids may be updated at once",
+            MAX_BATCH_SIZE
+        )));
+    }
+
+    Ok(())
+}
@@ -1,3 +1,14 @@
+pub mod alert;
+pub mod attachment;
 pub mod auth;
+pub mod conversation;
 pub mod device;
-pub mod sms;
\ No newline at end of file
+pub mod label;
+pub mod otp;
+pub mod outbound;
+pub mod retention;
+pub mod rule;
+pub mod session;
+pub mod sms;
+pub mod stream;
+pub mod webhook;
\ No newline at end of file
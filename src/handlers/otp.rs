@@ -0,0 +1,112 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use uuid::Uuid;
+
+use crate::{
+    auth::middleware::AuthRequired,
+    errors::AppError,
+    handlers::device::require_owned_device,
+    handlers::sms::sender_counterpart,
+    otp,
+    rules,
+    AppState,
+    db
+};
+use crate::models::otp::{
+    NewOtpPattern,
+    OtpPattern,
+    OtpPatternListResponse,
+    OtpPatternPayload,
+    OtpQuery,
+    OtpResponse
+};
+
+// Newest unused code, optionally marking it consumed
+pub async fn latest_otp(
+    auth_wrapper: AuthRequired,
+    State(state): State<AppState>,
+    Query(params): Query<OtpQuery>,
+) -> Result<Json<OtpResponse>, AppError> {
+    let user = auth_wrapper.0;
+
+    let device = match params.device_id {
+        Some(device_id) => Some(require_owned_device(&state, device_id, user.user_id).await?),
+        None => None,
+    };
+
+    let counterpart = params
+        .sender
+        .as_deref()
+        .map(|sender| sender_counterpart(sender, device.as_ref()))
+        .transpose()?;
+
+    let otp = db::find_latest_otp(
+        &state.db_pool,
+        user.user_id,
+        params.device_id,
+        counterpart.as_deref(),
+        params.since,
+        params.consume,
+    )
+    .await?
+    .ok_or(AppError::OtpNotFound)?;
+
+    Ok(Json(otp))
+}
+
+pub async fn create_pattern(
+    auth_wrapper: AuthRequired,
+    State(state): State<AppState>,
+    Json(payload): Json<OtpPatternPayload>,
+) -> Result<Json<OtpPattern>, AppError> {
+    let user = auth_wrapper.0;
+
+    rules::validate_pattern(&payload.pattern)
+        .map_err(|e| AppError::BadRequest(format!("Invalid pattern: {}", e)))?;
+
+    let issuer = payload.issuer.as_deref().map(str::trim).filter(|issuer| !issuer.is_empty());
+    if issuer.is_some_and(|issuer| issuer.chars().count() > otp::MAX_ISSUER_LENGTH) {
+        return Err(AppError::BadRequest(format!(
+            "Issuer must be at most {} characters",
+            otp::MAX_ISSUER_LENGTH
+        )));
+    }
+
+    let new_pattern = NewOtpPattern {
+        user_id: &user.user_id,
+        pattern: &payload.pattern,
+        issuer,
+    };
+
+    let pattern = db::create_otp_pattern(&state.db_pool, &new_pattern).await?;
+
+    Ok(Json(pattern))
+}
+
+pub async fn list_patterns(
+    auth_wrapper: AuthRequired,
+    State(state): State<AppState>,
+) -> Result<Json<OtpPatternListResponse>, AppError> {
+    let user = auth_wrapper.0;
+
+    let patterns = db::find_user_otp_patterns(&state.db_pool, user.user_id).await?;
+
+    Ok(Json(OtpPatternListResponse { patterns }))
+}
+
+pub async fn delete_pattern(
+    auth_wrapper: AuthRequired,
+    State(state): State<AppState>,
+    Path(pattern_id): Path<Uuid>,
+) -> Result<StatusCode, AppError> {
+    let user = auth_wrapper.0;
+
+    if !db::delete_user_otp_pattern(&state.db_pool, pattern_id, user.user_id).await? {
+        return Err(AppError::OtpPatternNotFound);
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,195 @@
+use std::time::Duration;
+
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use tokio::sync::broadcast::error::RecvError;
+use uuid::Uuid;
+
+use crate::{
+    auth::middleware::{AuthRequired, DeviceAuthRequired},
+    errors::AppError,
+    events::SmsEvent,
+    handlers::device::require_owned_device,
+    AppState,
+    db
+};
+use crate::models::outbound::{
+    ClaimQuery,
+    ClaimResponse,
+    NewOutboundSms,
+    OutboundDetailResponse,
+    OutboundListResponse,
+    OutboundPayload,
+    OutboundQuery,
+    OutboundSms,
+    ReportedStatus,
+    StatusReportPayload,
+    STATUSES,
+};
+
+const MAX_MESSAGE_CHARS: usize = 1600;
+const MAX_CLAIM_WAIT_SECONDS: u64 = 30;
+
+pub async fn send_sms(
+    auth_wrapper: AuthRequired,
+    State(state): State<AppState>,
+    Json(payload): Json<OutboundPayload>,
+) -> Result<Json<OutboundSms>, AppError> {
+    let user = auth_wrapper.0;
+
+    let device = require_owned_device(&state, payload.device_id, user.user_id).await?;
+    if device.retired_at.is_some() {
+        return Err(AppError::Conflict("Device has been retired".to_string()));
+    }
+
+    let recipient = payload.recipient.trim();
+    if recipient.is_empty() {
+        return Err(AppError::BadRequest("Recipient is required".to_string()));
+    }
+    if payload.message.is_empty() {
+        return Err(AppError::BadRequest("Message is required".to_string()));
+    }
+    if payload.message.chars().count() > MAX_MESSAGE_CHARS {
+        return Err(AppError::BadRequest(format!(
+            "Message must be at most {} characters",
+            MAX_MESSAGE_CHARS
+        )));
+    }
+
+    let new_outbound = NewOutboundSms {
+        device_id: &payload.device_id,
+        recipient,
+        message: &payload.message,
+    };
+
+    let outbound = db::create_outbound_sms(&state.db_pool, &new_outbound).await?;
+
+    Ok(Json(outbound))
+}
+
+pub async fn list_outbound(
+    auth_wrapper: AuthRequired,
+    State(state): State<AppState>,
+    Query(params): Query<OutboundQuery>,
+) -> Result<Json<OutboundListResponse>, AppError> {
+    let user = auth_wrapper.0;
+
+    if let Some(device_id) = params.device_id {
+        require_owned_device(&state, device_id, user.user_id).await?;
+    }
+
+    if let Some(status) = params.status.as_deref().filter(|s| !STATUSES.contains(s)) {
+        return Err(AppError::BadRequest(format!("Unknown status: {}", status)));
+    }
+
+    let limit = params.limit.unwrap_or(20).min(100);
+    let offset = params.offset.unwrap_or(0);
+
+    let (data, total) = db::get_user_outbound_sms_with_filters(
+        &state.db_pool,
+        user.user_id,
+        params.device_id,
+        params.status.as_deref(),
+        limit,
+        offset,
+    )
+    .await?;
+
+    Ok(Json(OutboundListResponse { total, data }))
+}
+
+pub async fn get_outbound(
+    auth_wrapper: AuthRequired,
+    State(state): State<AppState>,
+    Path(outbound_id): Path<Uuid>,
+) -> Result<Json<OutboundDetailResponse>, AppError> {
+    let user = auth_wrapper.0;
+
+    let message = db::find_user_outbound_sms(&state.db_pool, outbound_id, user.user_id)
+        .await?
+        .ok_or(AppError::OutboundSmsNotFound)?;
+
+    let events = db::get_outbound_sms_events(&state.db_pool, outbound_id).await?;
+
+    Ok(Json(OutboundDetailResponse { message, events }))
+}
+
+// Polled by the phone. With `wait` set, holds the request open until
+// something is queued for the device or the wait runs out.
+pub async fn claim_outbound(
+    device_wrapper: DeviceAuthRequired,
+    State(state): State<AppState>,
+    Query(params): Query<ClaimQuery>,
+) -> Result<Json<ClaimResponse>, AppError> {
+    let device = device_wrapper.0;
+
+    let limit = params.limit.unwrap_or(10).clamp(1, 100);
+    let wait = Duration::from_secs(params.wait.unwrap_or(0).min(MAX_CLAIM_WAIT_SECONDS));
+    let claim_timeout = state.config.outbound_claim_timeout_seconds as f64;
+    let deadline = tokio::time::Instant::now() + wait;
+
+    // Subscribe before claiming so nothing queued in between is missed
+    let mut events = state.sms_events.subscribe();
+
+    loop {
+        let messages = db::claim_outbound_sms(&state.db_pool, device.device_id, limit, claim_timeout).await?;
+        if !messages.is_empty() {
+            return Ok(Json(ClaimResponse { messages }));
+        }
+
+        // Sleep until this device has work, or check again if notifications
+        // may have been missed
+        loop {
+            match tokio::time::timeout_at(deadline, events.recv()).await {
+                Err(_) => return Ok(Json(ClaimResponse { messages })),
+                Ok(Ok(SmsEvent::OutboundQueued { device_id })) if device_id == device.device_id => break,
+                Ok(Ok(SmsEvent::Interrupted) | Err(RecvError::Lagged(_))) => break,
+                Ok(Err(RecvError::Closed)) => return Ok(Json(ClaimResponse { messages })),
+                Ok(Ok(_)) => {}
+            }
+        }
+    }
+}
+
+// Re-queue a message that failed or whose claim expired
+pub async fn retry_outbound(
+    auth_wrapper: AuthRequired,
+    State(state): State<AppState>,
+    Path(outbound_id): Path<Uuid>,
+) -> Result<Json<OutboundSms>, AppError> {
+    let user = auth_wrapper.0;
+
+    let outbound = db::retry_user_outbound_sms(&state.db_pool, outbound_id, user.user_id)
+        .await?
+        .ok_or(AppError::OutboundSmsNotFound)?;
+
+    Ok(Json(outbound))
+}
+
+pub async fn report_status(
+    device_wrapper: DeviceAuthRequired,
+    State(state): State<AppState>,
+    Path(outbound_id): Path<Uuid>,
+    Json(payload): Json<StatusReportPayload>,
+) -> Result<Json<OutboundSms>, AppError> {
+    let device = device_wrapper.0;
+
+    let error = match payload.status {
+        ReportedStatus::Failed => payload.error.as_deref(),
+        _ => None,
+    };
+
+    let outbound = db::report_outbound_sms_status(
+        &state.db_pool,
+        outbound_id,
+        device.device_id,
+        payload.status,
+        error,
+    )
+    .await?
+    .ok_or(AppError::OutboundSmsNotFound)?;
+
+    Ok(Json(outbound))
+}
@@ -0,0 +1,116 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use uuid::Uuid;
+
+use crate::{
+    auth::middleware::AuthRequired,
+    errors::AppError,
+    handlers::device::require_owned_device,
+    AppState,
+    db
+};
+use crate::models::retention::{
+    RetentionPolicy,
+    RetentionPolicyPayload,
+    RetentionResponse
+};
+
+const MAX_AGE_DAYS: i32 = 3650;
+const MAX_COUNT: i32 = 1_000_000;
+const MAX_OTP_AGE_HOURS: i32 = 8760;
+
+// The user-wide policy, device overrides and what the last purge removed
+pub async fn get_retention(
+    auth_wrapper: AuthRequired,
+    State(state): State<AppState>,
+) -> Result<Json<RetentionResponse>, AppError> {
+    let user = auth_wrapper.0;
+
+    let mut device_policies = db::find_user_retention_policies(&state.db_pool, user.user_id).await?;
+    let policy = device_policies
+        .first()
+        .filter(|p| p.device_id.is_none())
+        .cloned();
+    if policy.is_some() {
+        device_policies.remove(0);
+    }
+    let last_purge = db::find_retention_report(&state.db_pool, user.user_id).await?;
+
+    Ok(Json(RetentionResponse { policy, device_policies, last_purge }))
+}
+
+pub async fn put_retention(
+    auth_wrapper: AuthRequired,
+    State(state): State<AppState>,
+    Json(payload): Json<RetentionPolicyPayload>,
+) -> Result<Json<RetentionPolicy>, AppError> {
+    let user = auth_wrapper.0;
+
+    validate_policy(&payload)?;
+    let policy = db::upsert_retention_policy(&state.db_pool, user.user_id, None, &payload).await?;
+
+    Ok(Json(policy))
+}
+
+pub async fn delete_retention(
+    auth_wrapper: AuthRequired,
+    State(state): State<AppState>,
+) -> Result<StatusCode, AppError> {
+    let user = auth_wrapper.0;
+
+    if !db::delete_retention_policy(&state.db_pool, user.user_id, None).await? {
+        return Err(AppError::RetentionPolicyNotFound);
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// Overrides the user-wide limits for one device; unset fields fall back to them
+pub async fn put_device_retention(
+    auth_wrapper: AuthRequired,
+    State(state): State<AppState>,
+    Path(device_id): Path<Uuid>,
+    Json(payload): Json<RetentionPolicyPayload>,
+) -> Result<Json<RetentionPolicy>, AppError> {
+    let user = auth_wrapper.0;
+
+    require_owned_device(&state, device_id, user.user_id).await?;
+    validate_policy(&payload)?;
+    let policy = db::upsert_retention_policy(&state.db_pool, user.user_id, Some(device_id), &payload).await?;
+
+    Ok(Json(policy))
+}
+
+pub async fn delete_device_retention(
+    auth_wrapper: AuthRequired,
+    State(state): State<AppState>,
+    Path(device_id): Path<Uuid>,
+) -> Result<StatusCode, AppError> {
+    let user = auth_wrapper.0;
+
+    require_owned_device(&state, device_id, user.user_id).await?;
+    if !db::delete_retention_policy(&state.db_pool, user.user_id, Some(device_id)).await? {
+        return Err(AppError::RetentionPolicyNotFound);
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+fn validate_policy(payload: &RetentionPolicyPayload) -> Result<(), AppError> {
+    let limits = [
+        ("max_age_days", payload.max_age_days, MAX_AGE_DAYS),
+        ("max_count", payload.max_count, MAX_COUNT),
+        ("otp_max_age_hours", payload.otp_max_age_hours, MAX_OTP_AGE_HOURS),
+    ];
+
+    for (field, value, max) in limits {
+        if value.is_some_and(|v| !(1..=max).contains(&v)) {
+            return Err(AppError::BadRequest(format!("{} must be between 1 and {}", field, max)));
+        }
+    }
+
+    Ok(())
+}
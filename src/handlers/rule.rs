@@ -0,0 +1,176 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use uuid::Uuid;
+
+use crate::{
+    auth::middleware::AuthRequired,
+    errors::AppError,
+    handlers::device::require_owned_device,
+    rules,
+    AppState,
+    db
+};
+use crate::models::rule::{
+    DryRunPayload,
+    DryRunResponse,
+    DryRunResult,
+    Rule,
+    RuleAction,
+    RuleInput,
+    RuleListResponse,
+    RulePayload
+};
+
+const MAX_TAG_LENGTH: usize = 64;
+
+pub async fn create_rule(
+    auth_wrapper: AuthRequired,
+    State(state): State<AppState>,
+    Json(payload): Json<RulePayload>,
+) -> Result<Json<Rule>, AppError> {
+    let user = auth_wrapper.0;
+
+    validate_rule(&state, user.user_id, &payload).await?;
+
+    let rule = db::create_rule(&state.db_pool, user.user_id, &payload).await?;
+
+    Ok(Json(rule))
+}
+
+pub async fn list_rules(
+    auth_wrapper: AuthRequired,
+    State(state): State<AppState>,
+) -> Result<Json<RuleListResponse>, AppError> {
+    let user = auth_wrapper.0;
+
+    let rules = db::find_user_rules(&state.db_pool, user.user_id).await?;
+
+    Ok(Json(RuleListResponse { rules }))
+}
+
+pub async fn update_rule(
+    auth_wrapper: AuthRequired,
+    State(state): State<AppState>,
+    Path(rule_id): Path<Uuid>,
+    Json(payload): Json<RulePayload>,
+) -> Result<Json<Rule>, AppError> {
+    let user = auth_wrapper.0;
+
+    validate_rule(&state, user.user_id, &payload).await?;
+
+    let rule = db::update_user_rule(&state.db_pool, rule_id, user.user_id, &payload)
+        .await?
+        .ok_or(AppError::RuleNotFound)?;
+
+    Ok(Json(rule))
+}
+
+pub async fn delete_rule(
+    auth_wrapper: AuthRequired,
+    State(state): State<AppState>,
+    Path(rule_id): Path<Uuid>,
+) -> Result<StatusCode, AppError> {
+    let user = auth_wrapper.0;
+
+    if !db::delete_user_rule(&state.db_pool, rule_id, user.user_id).await? {
+        return Err(AppError::RuleNotFound);
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// Evaluate the user's current rules against stored messages without changing anything
+pub async fn dry_run(
+    auth_wrapper: AuthRequired,
+    State(state): State<AppState>,
+    Json(payload): Json<DryRunPayload>,
+) -> Result<Json<DryRunResponse>, AppError> {
+    let user = auth_wrapper.0;
+
+    if let Some(device_id) = payload.device_id {
+        require_owned_device(&state, device_id, user.user_id).await?;
+    }
+
+    let limit = payload.limit.unwrap_or(20).min(100);
+
+    let user_rules = rules::compile(db::find_user_rules(&state.db_pool, user.user_id).await?);
+    let messages = db::get_recent_user_sms(&state.db_pool, user.user_id, payload.device_id, limit).await?;
+
+    let results = messages
+        .into_iter()
+        .map(|sms| {
+            // The same time ingest used for time-window rules
+            let outcome = rules::evaluate(&user_rules, &RuleInput {
+                device_id: sms.device_id,
+                sender: &sms.sender,
+                message: &sms.message,
+                at: sms.device_received_at.unwrap_or(sms.received_at),
+            });
+
+            DryRunResult {
+                sms_id: sms.id,
+                device_id: sms.device_id,
+                sender: sms.sender,
+                received_at: sms.received_at,
+                outcome,
+            }
+        })
+        .collect();
+
+    Ok(Json(DryRunResponse { results }))
+}
+
+async fn validate_rule(state: &AppState, user_id: Uuid, payload: &RulePayload) -> Result<(), AppError> {
+    if payload.name.trim().is_empty() {
+        return Err(AppError::BadRequest("Rule name is required".to_string()));
+    }
+
+    for pattern in [&payload.sender_pattern, &payload.message_pattern].into_iter().flatten() {
+        rules::validate_pattern(pattern)
+            .map_err(|e| AppError::BadRequest(format!("Invalid pattern: {}", e)))?;
+    }
+
+    // UTC-12:00 through UTC+14:00
+    if !(-720..=840).contains(&payload.utc_offset_minutes) {
+        return Err(AppError::BadRequest("utc_offset_minutes is out of range".to_string()));
+    }
+
+    if let Some(device_id) = payload.device_id {
+        require_owned_device(state, device_id, user_id).await?;
+    }
+
+    if payload.label_id.is_some() && !matches!(payload.action, RuleAction::Label) {
+        return Err(AppError::BadRequest("Only label rules take a label_id".to_string()));
+    }
+
+    match payload.action {
+        RuleAction::Forward => {
+            let webhook_id = payload.webhook_id
+                .ok_or_else(|| AppError::BadRequest("Forward rules require a webhook_id".to_string()))?;
+
+            if db::find_user_webhook(&state.db_pool, webhook_id, user_id).await?.is_none() {
+                return Err(AppError::WebhookNotFound);
+            }
+        }
+        RuleAction::Tag => {
+            let tag = payload.tag.as_deref().unwrap_or("").trim();
+            if tag.is_empty() || tag.len() > MAX_TAG_LENGTH {
+                return Err(AppError::BadRequest(format!("Tag rules require a tag of 1-{} characters", MAX_TAG_LENGTH)));
+            }
+        }
+        RuleAction::Label => {
+            let label_id = payload.label_id
+                .ok_or_else(|| AppError::BadRequest("Label rules require a label_id".to_string()))?;
+
+            db::find_user_label(&state.db_pool, label_id, user_id)
+                .await?
+                .ok_or(AppError::LabelNotFound)?;
+        }
+        RuleAction::Discard | RuleAction::MarkSpam => {}
+    }
+
+    Ok(())
+}
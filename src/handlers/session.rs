@@ -0,0 +1,46 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use uuid::Uuid;
+
+use crate::{
+    auth::middleware::AuthRequired,
+    errors::AppError,
+    AppState,
+    db
+};
+use crate::models::session::{SessionInfo, SessionListResponse};
+
+pub async fn list_sessions(
+    auth_wrapper: AuthRequired,
+    State(state): State<AppState>,
+) -> Result<Json<SessionListResponse>, AppError> {
+    let user = auth_wrapper.0;
+
+    let sessions = db::find_active_user_sessions(&state.db_pool, user.user_id)
+        .await?
+        .into_iter()
+        .map(|session| SessionInfo {
+            current: session.id == user.session_id,
+            session,
+        })
+        .collect();
+
+    Ok(Json(SessionListResponse { sessions }))
+}
+
+pub async fn revoke_session(
+    auth_wrapper: AuthRequired,
+    State(state): State<AppState>,
+    Path(session_id): Path<Uuid>,
+) -> Result<StatusCode, AppError> {
+    let user = auth_wrapper.0;
+
+    if !db::revoke_user_session(&state.db_pool, session_id, user.user_id).await? {
+        return Err(AppError::SessionNotFound);
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
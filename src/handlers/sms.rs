@@ -1,45 +1,526 @@
 use axum::{
-    extract::{Query, State},
+    body::{Body, Bytes},
+    extract::{multipart::{Field, MultipartError}, FromRequest, Multipart, Path, Query, Request, State},
+    http::{header, HeaderMap, StatusCode},
     Json
 };
 
+use chrono::{DateTime, Utc};
+use http_body_util::Limited;
+use tracing::error;
+use uuid::Uuid;
+
 use crate::{
-    db, errors::AppError, models::sms::{NewSms, SmsListResponse, SmsPayload, SmsQuery, SmsResponse}, AppState
+    attachments, auth::middleware::{AuthRequired, DeviceAuthRequired}, db, errors::AppError, handlers::device::require_owned_device, ingest::{self, IncomingSms, IngestRouting}, models::attachment::{MmsPayload, NewAttachment}, models::device::{AuthenticatedDevice, Device}, models::sms::{BatchSmsItem, BatchSmsResponse, ConcatInfo, SmsListResponse, SmsFilter, SmsPartListResponse, SmsPayload, SmsQuery, SmsResponse, SmsSelection, SmsUpdatePayload, SmsUpdateResponse, StoredSms}, phone, search, AppState
 };
 
+pub const MAX_BATCH_SIZE: usize = 1000;
+// axum's default, for JSON messages on the multipart-sized POST /sms route
+const JSON_BODY_LIMIT: usize = 2 * 1024 * 1024;
+// Device timestamps further ahead of the server clock than this are rejected
+const MAX_CLOCK_SKEW_MINUTES: i64 = 5;
+const CLIENT_MESSAGE_ID_ERROR: &str = "Client message id must be 1-255 characters";
+
+// An attachment read from a multipart upload, before it is stored
+struct Upload {
+    filename: Option<String>,
+    content_type: &'static str,
+    data: Vec<u8>,
+}
+
+// Takes a JSON message, or an MMS as multipart/form-data: a JSON `payload`
+// part followed by one or more `attachment` files
 pub async fn sms_handler(
+    device_wrapper: DeviceAuthRequired,
     State(state): State<AppState>,
-    Json(payload): Json<SmsPayload>,
+    headers: HeaderMap,
+    request: Request,
 ) -> Result<Json<SmsResponse>, AppError> {
-    let new_sms = NewSms {
-        device_id: &payload.device_id,
+    let device = device_wrapper.0;
+
+    let is_multipart = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("multipart/form-data"));
+
+    if is_multipart {
+        let multipart = Multipart::from_request(request, &state)
+            .await
+            .map_err(|e| rejection(e.status(), e.body_text()))?;
+        return Ok(Json(store_mms(&state, &device, &headers, multipart).await?));
+    }
+
+    // The route's body limit is sized for attachments; plain messages keep the
+    // usual one
+    let request = request.map(|body| Body::new(Limited::new(body, JSON_BODY_LIMIT)));
+    let Json(payload) = Json::<SmsPayload>::from_request(request, &state)
+        .await
+        .map_err(|e| rejection(e.status(), e.body_text()))?;
+
+    let client_message_id = client_message_id(&headers, payload.client_message_id.as_deref())?;
+    validate_timestamps(payload.received_at, payload.smsc_timestamp).map_err(AppError::BadRequest)?;
+
+    if let Some(concat) = &payload.concat {
+        validate_concat(concat).map_err(AppError::BadRequest)?;
+    }
+
+    let routing = IngestRouting::load(&state.db_pool, device.user_id).await?;
+
+    let incoming = IncomingSms {
+        sender: &payload.sender,
+        message: &payload.message,
+        client_message_id,
+        received_at: payload.received_at,
+        smsc_timestamp: payload.smsc_timestamp,
+        concat: payload.concat.as_ref(),
+        attachments: &[],
+    };
+
+    // A replayed upload gets the original message's response
+    let response = match ingest::store_sms(&state.db_pool, &state.config, &device, &routing, &incoming).await? {
+        StoredSms::Created(sms) => SmsResponse { id: Some(sms.id), buffered: false, discarded: false, attachments: Vec::new() },
+        StoredSms::Duplicate(id) => SmsResponse { id: Some(id), buffered: false, discarded: false, attachments: Vec::new() },
+        StoredSms::Buffered(id) => SmsResponse { id: Some(id), buffered: true, discarded: false, attachments: Vec::new() },
+        StoredSms::Discarded => SmsResponse { id: None, buffered: false, discarded: true, attachments: Vec::new() },
+    };
+
+    Ok(Json(response))
+}
+
+async fn store_mms(
+    state: &AppState,
+    device: &AuthenticatedDevice,
+    headers: &HeaderMap,
+    mut multipart: Multipart,
+) -> Result<SmsResponse, AppError> {
+    let mut payload: Option<MmsPayload> = None;
+    let mut uploads: Vec<Upload> = Vec::new();
+
+    while let Some(field) = multipart.next_field().await.map_err(multipart_error)? {
+        match field.name() {
+            Some("payload") => {
+                let bytes = field.bytes().await.map_err(multipart_error)?;
+                payload = Some(
+                    serde_json::from_slice(&bytes)
+                        .map_err(|e| AppError::BadRequest(format!("Invalid payload part: {}", e)))?,
+                );
+            }
+            Some("attachment") => {
+                if uploads.len() == attachments::MAX_ATTACHMENTS_PER_MESSAGE {
+                    return Err(AppError::BadRequest(format!(
+                        "A message may have at most {} attachments",
+                        attachments::MAX_ATTACHMENTS_PER_MESSAGE
+                    )));
+                }
+                uploads.push(read_upload(field, state.config.attachment_max_bytes).await?);
+            }
+            _ => {
+                return Err(AppError::BadRequest(
+                    "Expected only payload and attachment parts".to_string(),
+                ));
+            }
+        }
+    }
+
+    let payload = payload.ok_or_else(|| AppError::BadRequest("Missing payload part".to_string()))?;
+    if uploads.is_empty() {
+        return Err(AppError::BadRequest("Multipart upload has no attachments".to_string()));
+    }
+
+    let client_message_id = client_message_id(headers, payload.client_message_id.as_deref())?;
+    validate_timestamps(payload.received_at, payload.smsc_timestamp).map_err(AppError::BadRequest)?;
+
+    let routing = IngestRouting::load(&state.db_pool, device.user_id).await?;
+
+    // Blobs go in first; they are removed again if the message isn't stored
+    let ids: Vec<Uuid> = uploads.iter().map(|_| Uuid::new_v4()).collect();
+    let keys: Vec<String> = ids.iter().map(|id| attachments::storage_key(device.device_id, *id)).collect();
+
+    for (stored, (upload, key)) in uploads.iter().zip(&keys).enumerate() {
+        if let Err(e) = state.blob_store.put(key, upload.content_type, &upload.data).await {
+            attachments::discard(state.blob_store.as_ref(), &keys[..stored]).await;
+            return Err(e.into());
+        }
+    }
+
+    let new_attachments: Vec<NewAttachment> = uploads
+        .iter()
+        .zip(ids.iter().zip(&keys))
+        .enumerate()
+        .map(|(position, (upload, (id, key)))| NewAttachment {
+            id: *id,
+            position: position as i16,
+            content_type: upload.content_type,
+            size_bytes: upload.data.len() as i64,
+            filename: upload.filename.as_deref(),
+            storage_key: key,
+        })
+        .collect();
+
+    let incoming = IncomingSms {
         sender: &payload.sender,
         message: &payload.message,
+        client_message_id,
+        received_at: payload.received_at,
+        smsc_timestamp: payload.smsc_timestamp,
+        concat: None,
+        attachments: &new_attachments,
+    };
+
+    let id = match ingest::store_sms(&state.db_pool, &state.config, device, &routing, &incoming).await {
+        Ok(StoredSms::Created(sms)) => sms.id,
+        // A replay: the original message kept its own copies
+        Ok(StoredSms::Duplicate(id) | StoredSms::Buffered(id)) => {
+            attachments::discard(state.blob_store.as_ref(), &keys).await;
+            id
+        }
+        Ok(StoredSms::Discarded) => {
+            attachments::discard(state.blob_store.as_ref(), &keys).await;
+            return Ok(SmsResponse { id: None, buffered: false, discarded: true, attachments: Vec::new() });
+        }
+        Err(e) => {
+            attachments::discard(state.blob_store.as_ref(), &keys).await;
+            return Err(e);
+        }
     };
 
-    let saved_sms = db::create_sms(&state.db_pool, &new_sms).await?;
+    let attachments = db::find_sms_attachments(&state.db_pool, &[id])
+        .await?
+        .into_iter()
+        .map(|attachment| attachments::describe(&state.config, attachment))
+        .collect();
+
+    Ok(SmsResponse { id: Some(id), buffered: false, discarded: false, attachments })
+}
+
+// Reads one attachment, enforcing the size limit as it streams in
+async fn read_upload(mut field: Field<'_>, max_bytes: usize) -> Result<Upload, AppError> {
+    let filename = field.file_name().map(|name| name.chars().take(255).collect());
+    let mut data = Vec::new();
+
+    while let Some(chunk) = field.chunk().await.map_err(multipart_error)? {
+        if data.len() + chunk.len() > max_bytes {
+            return Err(AppError::PayloadTooLarge(format!(
+                "Attachments may be at most {} bytes",
+                max_bytes
+            )));
+        }
+        data.extend_from_slice(&chunk);
+    }
+
+    if data.is_empty() {
+        return Err(AppError::BadRequest("Attachment is empty".to_string()));
+    }
+
+    let content_type = attachments::sniff_content_type(&data).ok_or_else(|| {
+        AppError::UnsupportedMediaType("Attachments must be images, audio or vCards".to_string())
+    })?;
+
+    Ok(Upload { filename, content_type, data })
+}
+
+// Keeps the status of body limit and content type rejections
+fn rejection(status: StatusCode, message: String) -> AppError {
+    match status {
+        StatusCode::PAYLOAD_TOO_LARGE => AppError::PayloadTooLarge(message),
+        StatusCode::UNSUPPORTED_MEDIA_TYPE => AppError::UnsupportedMediaType(message),
+        _ => AppError::BadRequest(message),
+    }
+}
+
+fn multipart_error(e: MultipartError) -> AppError {
+    rejection(e.status(), e.body_text())
+}
+
+// Replays messages buffered on the phone. The body is a JSON array, or one
+// message per line when sent as application/x-ndjson. Items are stored one at
+// a time, so a bad item is reported without holding back the rest.
+pub async fn batch_sms_handler(
+    device_wrapper: DeviceAuthRequired,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Json<BatchSmsResponse>, AppError> {
+    let device = device_wrapper.0;
+
+    let is_ndjson = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/x-ndjson"));
+
+    let items: Vec<Result<BatchSmsItem, String>> = if is_ndjson {
+        body.split(|byte| *byte == b'\n')
+            .filter(|line| !line.trim_ascii().is_empty())
+            .map(|line| serde_json::from_slice(line).map_err(|e| e.to_string()))
+            .collect()
+    } else {
+        serde_json::from_slice::<Vec<serde_json::Value>>(&body)
+            .map_err(|e| AppError::BadRequest(format!("Expected a JSON array of messages: {}", e)))?
+            .into_iter()
+            .map(|value| serde_json::from_value(value).map_err(|e| e.to_string()))
+            .collect()
+    };
+
+    if items.is_empty() {
+        return Err(AppError::BadRequest("Batch contains no messages".to_string()));
+    }
+    if items.len() > MAX_BATCH_SIZE {
+        return Err(AppError::BadRequest(format!(
+            "Batch may contain at most {} messages",
+            MAX_BATCH_SIZE
+        )));
+    }
+
+    let routing = IngestRouting::load(&state.db_pool, device.user_id).await?;
+    let mut response = BatchSmsResponse::default();
+
+    for (index, item) in items.into_iter().enumerate() {
+        let outcome = match item.and_then(|item| validate_batch_item(&item).map(|()| item)) {
+            Ok(item) => {
+                let incoming = IncomingSms {
+                    sender: &item.sender,
+                    message: &item.message,
+                    client_message_id: item.client_message_id.as_deref(),
+                    received_at: item.received_at,
+                    smsc_timestamp: item.smsc_timestamp,
+                    concat: item.concat.as_ref(),
+                    attachments: &[],
+                };
+
+                // One bad item shouldn't fail the messages already stored around it
+                ingest::store_sms(&state.db_pool, &state.config, &device, &routing, &incoming)
+                    .await
+                    .map_err(|e| {
+                        error!("Failed to store batch item {}: {:?}", index, e);
+                        "Failed to store message".to_string()
+                    })
+            }
+            Err(reason) => Err(reason),
+        };
 
-    Ok(Json(SmsResponse { id: saved_sms.id }))
+        response.push(index, outcome);
+    }
+
+    Ok(Json(response))
+}
+
+// The buffered parts a concatenated message was assembled from
+pub async fn get_sms_parts_handler(
+    auth_wrapper: AuthRequired,
+    State(state): State<AppState>,
+    Path(sms_id): Path<Uuid>,
+) -> Result<Json<SmsPartListResponse>, AppError> {
+    let user = auth_wrapper.0;
+
+    let parts = db::find_user_sms_parts(&state.db_pool, sms_id, user.user_id)
+        .await?
+        .ok_or(AppError::SmsNotFound)?;
+
+    Ok(Json(SmsPartListResponse { parts }))
 }
 
 pub async fn get_sms_handler(
+    auth_wrapper: AuthRequired,
     State(state): State<AppState>,
     Query(params): Query<SmsQuery>,
 ) -> Result<Json<SmsListResponse>, AppError> {
+    let user = auth_wrapper.0;
+
+    let filter = resolve_selection(&state, user.user_id, params.selection()).await?;
+
     let limit = params.limit.unwrap_or(20).min(100);
     let offset = params.offset.unwrap_or(0);
 
-    let (sms_list, total) = db::get_sms_by_device_with_filters(
+    let (mut sms_list, total) = db::get_user_sms_with_filters(
         &state.db_pool,
-        params.device_id,
-        params.from,
-        params.to,
+        user.user_id,
+        &filter,
         limit,
         offset,
     ).await?;
 
+    let ids: Vec<Uuid> = sms_list.iter().map(|item| item.sms.id).collect();
+    let mut described = attachments::describe_for(&state.db_pool, &state.config, &ids).await?;
+    for item in sms_list.iter_mut() {
+        item.attachments = described.remove(&item.sms.id).unwrap_or_default();
+    }
+    for label in db::find_sms_labels(&state.db_pool, &ids).await? {
+        if let Some(item) = sms_list.iter_mut().find(|item| item.sms.id == label.sms_id) {
+            item.labels.push(label);
+        }
+    }
+
     Ok(Json(SmsListResponse {
         total,
         data: sms_list,
     }))
 }
+
+// Bulk flag changes, for the listed ids or for everything a filter matches
+pub async fn update_sms_handler(
+    auth_wrapper: AuthRequired,
+    State(state): State<AppState>,
+    Json(payload): Json<SmsUpdatePayload>,
+) -> Result<Json<SmsUpdateResponse>, AppError> {
+    let user = auth_wrapper.0;
+
+    let flags = payload.set;
+    if flags.is_read.is_none() && flags.is_starred.is_none() && flags.is_archived.is_none() {
+        return Err(AppError::BadRequest("Nothing to set".to_string()));
+    }
+
+    let updated = match (payload.ids, payload.filter) {
+        (Some(ids), None) => {
+            if ids.len() > MAX_BATCH_SIZE {
+                return Err(AppError::BadRequest(format!(
+                    "At most {} ids may be updated at once",
+                    MAX_BATCH_SIZE
+                )));
+            }
+            db::update_user_sms_flags_by_ids(&state.db_pool, user.user_id, &ids, &flags).await?
+        }
+        (None, Some(selection)) => {
+            require_criteria(&selection, payload.all)?;
+            let filter = resolve_selection(&state, user.user_id, selection).await?;
+            db::update_user_sms_flags_by_filter(&state.db_pool, user.user_id, &filter, &flags).await?
+        }
+        _ => return Err(AppError::BadRequest("Give either ids or filter".to_string())),
+    };
+
+    Ok(Json(SmsUpdateResponse { updated }))
+}
+
+// Keeps a bulk change from touching every message because of an empty filter
+pub fn require_criteria(selection: &SmsSelection, all: bool) -> Result<(), AppError> {
+    if selection.is_empty() && !all {
+        return Err(AppError::BadRequest(
+            "Filter has no criteria; set all to true to select every message".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+// Check the selection only refers to the user's own device and label, and
+// prepare it for the database
+pub async fn resolve_selection(state: &AppState, user_id: Uuid, selection: SmsSelection) -> Result<SmsFilter, AppError> {
+    let device = match selection.device_id {
+        Some(device_id) => Some(require_owned_device(state, device_id, user_id).await?),
+        None => None,
+    };
+
+    if let Some(label_id) = selection.label {
+        db::find_user_label(&state.db_pool, label_id, user_id)
+            .await?
+            .ok_or(AppError::LabelNotFound)?;
+    }
+
+    let counterpart = selection
+        .sender
+        .as_deref()
+        .map(|sender| sender_counterpart(sender, device.as_ref()))
+        .transpose()?;
+
+    // A query with no searchable words is rejected rather than matching everything
+    let tsquery = match selection.q.as_deref().map(str::trim).filter(|q| !q.is_empty()) {
+        Some(q) => Some(
+            search::build_tsquery(q)
+                .ok_or_else(|| AppError::BadRequest("Search query has no searchable terms".to_string()))?,
+        ),
+        None => None,
+    };
+
+    Ok(SmsFilter {
+        device_id: selection.device_id,
+        counterpart,
+        sender_type: selection.sender_type,
+        clock: selection.clock.unwrap_or_default(),
+        tsquery,
+        from: selection.from,
+        to: selection.to,
+        is_read: selection.is_read,
+        is_starred: selection.is_starred,
+        is_archived: selection.is_archived,
+        label_id: selection.label,
+    })
+}
+
+// Read a sender filter the way the sender was normalized on ingest. A
+// national number can only be placed in the region of the device it came
+// through, so one without a country code needs a device_id.
+pub fn sender_counterpart(sender: &str, device: Option<&Device>) -> Result<String, AppError> {
+    if device.is_none() && phone::is_national_number(sender) {
+        return Err(AppError::BadRequest(
+            "Give the sender in international format, or a device_id to read it in that device's region".to_string(),
+        ));
+    }
+
+    let region = device.and_then(|d| d.default_region.as_deref());
+    Ok(phone::normalize_sender(sender, region).counterpart)
+}
+
+// The id may come in the body or as an Idempotency-Key header, but not as two different values
+fn client_message_id<'a>(headers: &'a HeaderMap, body: Option<&'a str>) -> Result<Option<&'a str>, AppError> {
+    let header = headers
+        .get("idempotency-key")
+        .map(|value| value.to_str().map_err(|_| AppError::BadRequest("Invalid Idempotency-Key header".to_string())))
+        .transpose()?;
+
+    let id = match (body, header) {
+        (Some(body), Some(header)) if body != header => {
+            return Err(AppError::BadRequest(
+                "client_message_id does not match the Idempotency-Key header".to_string(),
+            ));
+        }
+        (Some(id), _) | (None, Some(id)) => id,
+        (None, None) => return Ok(None),
+    };
+
+    validate_client_message_id(id)?;
+
+    Ok(Some(id))
+}
+
+fn validate_client_message_id(id: &str) -> Result<(), AppError> {
+    if id.is_empty() || id.len() > 255 {
+        return Err(AppError::BadRequest(CLIENT_MESSAGE_ID_ERROR.to_string()));
+    }
+
+    Ok(())
+}
+
+fn validate_batch_item(item: &BatchSmsItem) -> Result<(), String> {
+    if item.sender.trim().is_empty() {
+        return Err("Sender is required".to_string());
+    }
+    if let Some(id) = item.client_message_id.as_deref() {
+        validate_client_message_id(id).map_err(|_| CLIENT_MESSAGE_ID_ERROR.to_string())?;
+    }
+
+    if let Some(concat) = &item.concat {
+        validate_concat(concat)?;
+    }
+
+    validate_timestamps(item.received_at, item.smsc_timestamp)
+}
+
+fn validate_concat(concat: &ConcatInfo) -> Result<(), String> {
+    if !(1..=255).contains(&concat.total) || !(1..=concat.total).contains(&concat.sequence) {
+        return Err("concat must have 1 <= sequence <= total <= 255".to_string());
+    }
+
+    Ok(())
+}
+
+fn validate_timestamps(received_at: Option<DateTime<Utc>>, smsc_timestamp: Option<DateTime<Utc>>) -> Result<(), String> {
+    let latest = Utc::now() + chrono::Duration::minutes(MAX_CLOCK_SKEW_MINUTES);
+
+    if received_at.is_some_and(|at| at > latest) {
+        return Err("received_at is in the future".to_string());
+    }
+    if smsc_timestamp.is_some_and(|at| at > latest) {
+        return Err("smsc_timestamp is in the future".to_string());
+    }
+
+    Ok(())
+}
@@ -0,0 +1,203 @@
+use std::collections::HashSet;
+use std::convert::Infallible;
+
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        Query, State,
+    },
+    http::HeaderMap,
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        Response,
+    },
+};
+use futures_util::{future, stream, stream::BoxStream, Stream, StreamExt};
+use serde::Serialize;
+use tokio_stream::wrappers::BroadcastStream;
+use tracing::error;
+use uuid::Uuid;
+
+use crate::{
+    attachments,
+    auth::middleware::AuthRequired,
+    db,
+    errors::AppError,
+    events::SmsEvent,
+    handlers::device::require_owned_device,
+    models::sms::{SmsStreamQuery, SmsWithAttachments},
+    AppState,
+};
+
+// Upper bound on messages replayed when a client resumes from a cursor
+const REPLAY_LIMIT: usize = 1000;
+
+enum StreamItem {
+    Sms(Box<SmsWithAttachments>),
+    Control(StreamControl),
+}
+
+// Sent in place of an SMS when the replay can't be delivered as asked.
+// WebSocket clients tell these apart from SMS by their `event` field.
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum StreamControl {
+    // The cursor is unknown (purged, or never the caller's); the client should
+    // resync from GET /sms, the stream then carries on with live messages
+    Reset,
+    // More than REPLAY_LIMIT messages were missed; the stream closes after this
+    // batch and the client should reconnect from the last message it received
+    Truncated,
+}
+
+impl StreamControl {
+    fn as_str(&self) -> &'static str {
+        match self {
+            StreamControl::Reset => "reset",
+            StreamControl::Truncated => "truncated",
+        }
+    }
+}
+
+pub async fn sse_handler(
+    auth_wrapper: AuthRequired,
+    State(state): State<AppState>,
+    Query(params): Query<SmsStreamQuery>,
+    headers: HeaderMap,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, AppError> {
+    let user = auth_wrapper.0;
+
+    // EventSource sends the id of the last event it saw when reconnecting
+    let last_event_id = headers
+        .get("last-event-id")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| Uuid::parse_str(value).ok());
+
+    let messages = open_sms_stream(state, user.user_id, params.device_id, last_event_id.or(params.after)).await?;
+
+    let events = messages.filter_map(|item| {
+        let event = match &item {
+            StreamItem::Sms(sms) => Event::default().event("sms").id(sms.sms.id.to_string()).json_data(sms),
+            StreamItem::Control(control) => Event::default().event(control.as_str()).json_data(control),
+        };
+        let event = event.map_err(|e| error!("Failed to serialize SMS event: {:?}", e)).ok();
+        future::ready(event.map(Ok))
+    });
+
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+pub async fn ws_handler(
+    auth_wrapper: AuthRequired,
+    State(state): State<AppState>,
+    Query(params): Query<SmsStreamQuery>,
+    ws: WebSocketUpgrade,
+) -> Result<Response, AppError> {
+    let user = auth_wrapper.0;
+
+    let messages = open_sms_stream(state, user.user_id, params.device_id, params.after).await?;
+
+    Ok(ws.on_upgrade(move |socket| forward_to_socket(socket, messages)))
+}
+
+async fn forward_to_socket(mut socket: WebSocket, messages: impl Stream<Item = StreamItem>) {
+    let mut messages = std::pin::pin!(messages);
+
+    loop {
+        tokio::select! {
+            next = messages.next() => {
+                let Some(item) = next else { break };
+
+                let text = match &item {
+                    StreamItem::Sms(sms) => serde_json::to_string(sms),
+                    StreamItem::Control(control) => serde_json::to_string(control),
+                };
+                let text = match text {
+                    Ok(text) => text,
+                    Err(e) => {
+                        error!("Failed to serialize SMS event: {:?}", e);
+                        continue;
+                    }
+                };
+
+                if socket.send(Message::Text(text.into())).await.is_err() {
+                    break;
+                }
+            }
+            incoming = socket.recv() => {
+                // Anything other than a close (or a dropped connection) is ignored
+                match incoming {
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                    Some(Ok(_)) => {}
+                }
+            }
+        }
+    }
+}
+
+// Replays messages after `after` (if given), then follows newly stored ones.
+// The stream ends if the client falls too far behind, the replay was truncated
+// or notifications were interrupted; it should reconnect with the id of the
+// last message it received.
+async fn open_sms_stream(
+    state: AppState,
+    user_id: Uuid,
+    device_id: Option<Uuid>,
+    after: Option<Uuid>,
+) -> Result<BoxStream<'static, StreamItem>, AppError> {
+    if let Some(device_id) = device_id {
+        require_owned_device(&state, device_id, user_id).await?;
+    }
+
+    // Subscribe before replaying so nothing stored in between is lost
+    let receiver = state.sms_events.subscribe();
+
+    // One extra row tells a full replay apart from a truncated one
+    let (backlog, control) = match after {
+        Some(cursor) => match db::get_user_sms_after(&state.db_pool, user_id, device_id, cursor, REPLAY_LIMIT as i64 + 1).await? {
+            Some(backlog) if backlog.len() > REPLAY_LIMIT => (backlog, Some(StreamControl::Truncated)),
+            Some(backlog) => (backlog, None),
+            None => (Vec::new(), Some(StreamControl::Reset)),
+        },
+        None => (Vec::new(), None),
+    };
+
+    let backlog = attachments::attach(&state.db_pool, &state.config, backlog).await?;
+
+    if let Some(StreamControl::Truncated) = control {
+        let replay = backlog.into_iter().take(REPLAY_LIMIT).map(|sms| StreamItem::Sms(Box::new(sms)));
+        return Ok(stream::iter(replay).chain(stream::once(future::ready(StreamItem::Control(StreamControl::Truncated)))).boxed());
+    }
+
+    let replayed: HashSet<Uuid> = backlog.iter().map(|sms| sms.sms.id).collect();
+
+    // Attachments are looked up as each message arrives; if that fails the
+    // stream ends like any other interruption and the client resumes from it
+    let live = BroadcastStream::new(receiver)
+        .take_while(|event| future::ready(!matches!(event, Err(_) | Ok(SmsEvent::Interrupted))))
+        .filter_map(move |event| {
+            let sms = match event {
+                Ok(SmsEvent::Stored { user_id: owner, sms })
+                    if owner == user_id
+                        && device_id.is_none_or(|id| id == sms.device_id)
+                        && !replayed.contains(&sms.id) => Some(*sms),
+                _ => None,
+            };
+            future::ready(sms)
+        })
+        .then(move |sms| {
+            let state = state.clone();
+            async move {
+                attachments::attach(&state.db_pool, &state.config, vec![sms])
+                    .await
+                    .map_err(|e| error!("Failed to load attachments for streamed SMS: {:?}", e))
+            }
+        })
+        .take_while(|loaded| future::ready(loaded.is_ok()))
+        .flat_map(|loaded| stream::iter(loaded.unwrap_or_default()))
+        .map(|sms| StreamItem::Sms(Box::new(sms)));
+
+    let replay = backlog.into_iter().map(|sms| StreamItem::Sms(Box::new(sms)));
+
+    Ok(stream::iter(control.map(StreamItem::Control)).chain(stream::iter(replay)).chain(live).boxed())
+}
@@ -0,0 +1,154 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use uuid::Uuid;
+
+use crate::{
+    auth::{middleware::AuthRequired, secret},
+    errors::AppError,
+    AppState,
+    db
+};
+use crate::models::webhook::{
+    CreateWebhookPayload,
+    CreateWebhookResponse,
+    NewWebhook,
+    UpdateWebhookPayload,
+    Webhook,
+    WebhookAttemptListResponse,
+    WebhookDeliveryListResponse,
+    WebhookListResponse,
+    WebhookLogQuery
+};
+
+pub async fn create_webhook(
+    auth_wrapper: AuthRequired,
+    State(state): State<AppState>,
+    Json(payload): Json<CreateWebhookPayload>,
+) -> Result<Json<CreateWebhookResponse>, AppError> {
+    let user = auth_wrapper.0;
+
+    validate_url(&payload.url)?;
+
+    let signing_secret = secret::generate_secret();
+
+    let new_webhook = NewWebhook {
+        user_id: &user.user_id,
+        url: &payload.url,
+        description: payload.description.as_deref(),
+        secret: &signing_secret,
+        forward_all: payload.forward_all.unwrap_or(true),
+    };
+
+    let webhook = db::create_webhook(&state.db_pool, &new_webhook).await?;
+
+    Ok(Json(CreateWebhookResponse {
+        webhook,
+        secret: signing_secret,
+    }))
+}
+
+pub async fn list_webhooks(
+    auth_wrapper: AuthRequired,
+    State(state): State<AppState>,
+) -> Result<Json<WebhookListResponse>, AppError> {
+    let user = auth_wrapper.0;
+
+    let webhooks = db::find_user_webhooks(&state.db_pool, user.user_id).await?;
+
+    Ok(Json(WebhookListResponse { webhooks }))
+}
+
+pub async fn update_webhook(
+    auth_wrapper: AuthRequired,
+    State(state): State<AppState>,
+    Path(webhook_id): Path<Uuid>,
+    Json(payload): Json<UpdateWebhookPayload>,
+) -> Result<Json<Webhook>, AppError> {
+    let user = auth_wrapper.0;
+
+    if let Some(url) = &payload.url {
+        validate_url(url)?;
+    }
+
+    let webhook = db::update_user_webhook(
+        &state.db_pool,
+        webhook_id,
+        user.user_id,
+        payload.url.as_deref(),
+        payload.description.as_deref(),
+        payload.enabled,
+        payload.forward_all,
+    )
+    .await?
+    .ok_or(AppError::WebhookNotFound)?;
+
+    Ok(Json(webhook))
+}
+
+pub async fn delete_webhook(
+    auth_wrapper: AuthRequired,
+    State(state): State<AppState>,
+    Path(webhook_id): Path<Uuid>,
+) -> Result<StatusCode, AppError> {
+    let user = auth_wrapper.0;
+
+    if !db::delete_user_webhook(&state.db_pool, webhook_id, user.user_id).await? {
+        return Err(AppError::WebhookNotFound);
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn list_deliveries(
+    auth_wrapper: AuthRequired,
+    State(state): State<AppState>,
+    Path(webhook_id): Path<Uuid>,
+    Query(params): Query<WebhookLogQuery>,
+) -> Result<Json<WebhookDeliveryListResponse>, AppError> {
+    let user = auth_wrapper.0;
+
+    if db::find_user_webhook(&state.db_pool, webhook_id, user.user_id).await?.is_none() {
+        return Err(AppError::WebhookNotFound);
+    }
+
+    let limit = params.limit.unwrap_or(20).min(100);
+    let offset = params.offset.unwrap_or(0);
+
+    let (data, total) = db::get_webhook_deliveries(&state.db_pool, webhook_id, limit, offset).await?;
+
+    Ok(Json(WebhookDeliveryListResponse { total, data }))
+}
+
+pub async fn list_attempts(
+    auth_wrapper: AuthRequired,
+    State(state): State<AppState>,
+    Path(webhook_id): Path<Uuid>,
+    Query(params): Query<WebhookLogQuery>,
+) -> Result<Json<WebhookAttemptListResponse>, AppError> {
+    let user = auth_wrapper.0;
+
+    if db::find_user_webhook(&state.db_pool, webhook_id, user.user_id).await?.is_none() {
+        return Err(AppError::WebhookNotFound);
+    }
+
+    let limit = params.limit.unwrap_or(20).min(100);
+    let offset = params.offset.unwrap_or(0);
+
+    let (data, total) = db::get_webhook_attempts(&state.db_pool, webhook_id, limit, offset).await?;
+
+    Ok(Json(WebhookAttemptListResponse { total, data }))
+}
+
+pub fn validate_url(url: &str) -> Result<(), AppError> {
+    let parsed = reqwest::Url::parse(url)
+        .map_err(|_| AppError::BadRequest("Invalid webhook URL".to_string()))?;
+
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(AppError::BadRequest("Webhook URL must use http or https".to_string()));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,12 @@
+use std::time::Duration;
+
+use reqwest::Client;
+
+// Every outgoing request is bounded by `timeout`. Building only fails when the
+// TLS backend can't initialize, which no request could recover from.
+pub fn client(timeout: Duration) -> Client {
+    Client::builder()
+        .timeout(timeout)
+        .build()
+        .expect("HTTP client configuration is valid")
+}
@@ -0,0 +1,398 @@
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::config::AppConfig;
+use crate::db;
+use crate::errors::AppError;
+use crate::models::attachment::NewAttachment;
+use crate::models::device::AuthenticatedDevice;
+use crate::models::rule::RuleInput;
+use crate::models::sms::{ConcatInfo, NewSms, NewSmsPart, PartOutcome, SmsPart, StoredSms};
+use crate::{otp, phone, rules};
+
+// A message as uploaded by the phone, before normalization
+pub struct IncomingSms<'a> {
+    pub sender: &'a str,
+    pub message: &'a str,
+    pub client_message_id: Option<&'a str>,
+    pub received_at: Option<DateTime<Utc>>,
+    pub smsc_timestamp: Option<DateTime<Utc>>,
+    pub concat: Option<&'a ConcatInfo>,
+    pub attachments: &'a [NewAttachment<'a>],
+}
+
+// The owner's OTP patterns and routing rules, loaded once per request
+pub struct IngestRouting {
+    otp_patterns: Vec<otp::CompiledOtpPattern>,
+    rules: Vec<rules::CompiledRule>,
+}
+
+impl IngestRouting {
+    pub async fn load(pool: &PgPool, user_id: Uuid) -> Result<Self, AppError> {
+        Ok(IngestRouting {
+            otp_patterns: otp::compile(db::find_user_otp_patterns(pool, user_id).await?),
+            rules: rules::compile(db::find_user_rules(pool, user_id).await?),
+        })
+    }
+}
+
+// Multipart info attached to a message assembled from buffered parts
+struct Assembly<'a> {
+    part_ids: &'a [Uuid],
+    total: i16,
+    is_partial: bool,
+}
+
+// Normalize, extract OTPs, apply the owner's rules and store the message
+// (unless a rule discards it).
+// Parts of a concatenated message are buffered until the last one arrives.
+pub async fn store_sms(
+    pool: &PgPool,
+    config: &AppConfig,
+    device: &AuthenticatedDevice,
+    routing: &IngestRouting,
+    incoming: &IncomingSms<'_>,
+) -> Result<StoredSms, AppError> {
+    let Some(concat) = incoming.concat.filter(|concat| concat.total > 1) else {
+        return store(pool, config, device, routing, incoming, None).await;
+    };
+
+    let sender = phone::normalize_sender(incoming.sender, device.default_region.as_deref());
+
+    let part = NewSmsPart {
+        device_id: &device.device_id,
+        raw_sender: incoming.sender,
+        counterpart: &sender.counterpart,
+        concat,
+        message: incoming.message,
+        client_message_id: incoming.client_message_id,
+        device_received_at: incoming.received_at,
+        smsc_timestamp: incoming.smsc_timestamp,
+    };
+
+    match db::buffer_sms_part(pool, &part).await? {
+        PartOutcome::Buffered(part_id) => Ok(StoredSms::Buffered(part_id)),
+        PartOutcome::AlreadyAssembled(sms_id) => Ok(StoredSms::Duplicate(sms_id)),
+        PartOutcome::Complete(parts) => assemble(pool, config, device, routing, &parts).await,
+    }
+}
+
+// Store a message from claimed parts, in sequence order. Used both when the
+// last part arrives and when reassembly times out with parts still missing.
+pub async fn assemble(
+    pool: &PgPool,
+    config: &AppConfig,
+    device: &AuthenticatedDevice,
+    routing: &IngestRouting,
+    parts: &[SmsPart],
+) -> Result<StoredSms, AppError> {
+    let Some(first) = parts.first() else {
+        return Err(AppError::InternalServerError("No parts to assemble".to_string()));
+    };
+
+    let message: String = parts.iter().map(|part| part.message.as_str()).collect();
+    let part_ids: Vec<Uuid> = parts.iter().map(|part| part.id).collect();
+
+    // The first part's id stands for the message, so a retry of the whole
+    // message after its parts are gone is still recognized
+    let incoming = IncomingSms {
+        sender: &first.raw_sender,
+        message: &message,
+        client_message_id: first.client_message_id.as_deref(),
+        received_at: first.device_received_at,
+        smsc_timestamp: first.smsc_timestamp,
+        concat: None,
+        attachments: &[],
+    };
+
+    let assembly = Assembly {
+        part_ids: &part_ids,
+        total: first.total_parts,
+        is_partial: parts.len() < first.total_parts as usize,
+    };
+
+    store(pool, config, device, routing, &incoming, Some(assembly)).await
+}
+
+async fn store(
+    pool: &PgPool,
+    config: &AppConfig,
+    device: &AuthenticatedDevice,
+    routing: &IngestRouting,
+    incoming: &IncomingSms<'_>,
+    assembly: Option<Assembly<'_>>,
+) -> Result<StoredSms, AppError> {
+    let sender = phone::normalize_sender(incoming.sender, device.default_region.as_deref());
+    let extracted_otp = otp::extract(&routing.otp_patterns, &sender.sender, incoming.message);
+
+    let new_sms = NewSms {
+        device_id: &device.device_id,
+        sender: &sender.sender,
+        raw_sender: incoming.sender,
+        sender_type: sender.sender_type.as_str(),
+        counterpart: &sender.counterpart,
+        message: incoming.message,
+        otp_code: extracted_otp.as_ref().map(|otp| otp.code.as_str()),
+        otp_issuer: extracted_otp.as_ref().and_then(|otp| otp.issuer.as_deref()),
+        client_message_id: incoming.client_message_id,
+        device_received_at: incoming.received_at,
+        smsc_timestamp: incoming.smsc_timestamp,
+        part_count: assembly.as_ref().map(|a| a.total),
+        is_partial: assembly.as_ref().is_some_and(|a| a.is_partial),
+        part_ids: assembly.as_ref().map_or(&[], |a| a.part_ids),
+        attachments: incoming.attachments,
+    };
+
+    // Time-window rules apply to when the phone got the message, if it told us
+    let outcome = rules::evaluate(&routing.rules, &RuleInput {
+        device_id: device.device_id,
+        sender: &sender.sender,
+        message: incoming.message,
+        at: incoming.received_at.unwrap_or_else(Utc::now),
+    });
+
+    // Discarded messages are never stored; their parts go too, so the
+    // reassembly sweep doesn't pick them up again
+    if outcome.discard {
+        if !new_sms.part_ids.is_empty() {
+            db::delete_sms_parts(pool, new_sms.part_ids).await?;
+        }
+        return Ok(StoredSms::Discarded);
+    }
+
+    let dedup_window = config.sms_dedup_window_seconds as f64;
+
+    db::create_sms(pool, &new_sms, &outcome, dedup_window).await
+}
+
+// These run against the database in DATABASE_URL and are skipped without one
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::config::BlobStoreConfig;
+    use crate::models::device::NewDevice;
+    use crate::models::sms::Sms;
+    use crate::models::user::NewUser;
+
+    struct Fixture {
+        pool: PgPool,
+        config: AppConfig,
+        device: AuthenticatedDevice,
+        routing: IngestRouting,
+    }
+
+    async fn setup() -> Fixture {
+        let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL is set");
+        let pool = PgPool::connect(&database_url).await.expect("test database is reachable");
+        sqlx::migrate!("./migrations").run(&pool).await.expect("migrations apply");
+
+        let username = format!("reassembly-{}", Uuid::new_v4());
+        let user = db::create_user(&pool, &NewUser { username: &username, password_hash: "x" })
+            .await
+            .expect("user is created");
+        let device = db::create_device(&pool, &NewDevice {
+            device_name: "phone",
+            user_id: &user.id,
+            default_region: Some("US"),
+            ingest_secret_hash: &Uuid::new_v4().simple().to_string(),
+        })
+        .await
+        .expect("device is created");
+
+        let config = AppConfig {
+            database_url,
+            jwt_secret: "test".to_string(),
+            jwt_expiration_seconds: 3600,
+            refresh_token_expiration_seconds: 3600,
+            webhook_max_attempts: 1,
+            webhook_timeout_seconds: 1,
+            outbound_claim_timeout_seconds: 300,
+            sms_dedup_window_seconds: 0,
+            sms_reassembly_timeout_seconds: 300,
+            attachment_max_bytes: 1024,
+            attachment_url_ttl_seconds: 60,
+            blob_store: BlobStoreConfig::Local { path: PathBuf::from("/tmp") },
+            retention_interval_seconds: 3600,
+            device_offline_after_seconds: 600,
+            alert_check_interval_seconds: 60,
+            alert_sms_silence_seconds: 0,
+            smtp: None,
+        };
+
+        Fixture {
+            routing: IngestRouting::load(&pool, user.id).await.expect("routing loads"),
+            device: AuthenticatedDevice { device_id: device.id, user_id: user.id, default_region: Some("US".to_string()) },
+            pool,
+            config,
+        }
+    }
+
+    impl Fixture {
+        // Messages and parts go with the device
+        async fn cleanup(self) {
+            sqlx::query("DELETE FROM devices WHERE user_id = $1")
+                .bind(self.device.user_id)
+                .execute(&self.pool)
+                .await
+                .unwrap();
+            sqlx::query("DELETE FROM users WHERE id = $1")
+                .bind(self.device.user_id)
+                .execute(&self.pool)
+                .await
+                .unwrap();
+        }
+
+        async fn upload(&self, reference: i32, total: i16, sequence: i16, message: &str, client_message_id: Option<&str>) -> StoredSms {
+            let concat = ConcatInfo { reference, total, sequence };
+            let incoming = IncomingSms {
+                sender: "+1 202 555 0147",
+                message,
+                client_message_id,
+                received_at: None,
+                smsc_timestamp: None,
+                concat: Some(&concat),
+                attachments: &[],
+            };
+
+            store_sms(&self.pool, &self.config, &self.device, &self.routing, &incoming)
+                .await
+                .expect("part is stored")
+        }
+    }
+
+    fn created(stored: StoredSms) -> Sms {
+        match stored {
+            StoredSms::Created(sms) => *sms,
+            other => panic!("expected a stored message, got {:?}", other),
+        }
+    }
+
+    fn buffered(stored: StoredSms) -> Uuid {
+        match stored {
+            StoredSms::Buffered(id) => id,
+            other => panic!("expected a buffered part, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "requires DATABASE_URL"]
+    async fn assembles_parts_in_sequence_order() {
+        let fixture = setup().await;
+
+        buffered(fixture.upload(1, 3, 3, "!", None).await);
+        buffered(fixture.upload(1, 3, 1, "Hello, ", None).await);
+        let sms = created(fixture.upload(1, 3, 2, "world", None).await);
+
+        assert_eq!(sms.message, "Hello, world!");
+        assert_eq!(sms.sender, "+12025550147");
+        assert_eq!(sms.part_count, Some(3));
+        assert!(!sms.is_partial);
+
+        fixture.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore = "requires DATABASE_URL"]
+    async fn retried_parts_are_not_stored_twice() {
+        let fixture = setup().await;
+
+        let first = buffered(fixture.upload(2, 2, 1, "Hello, ", None).await);
+        assert_eq!(buffered(fixture.upload(2, 2, 1, "Hello, ", None).await), first);
+
+        let sms = created(fixture.upload(2, 2, 2, "world", None).await);
+        assert_eq!(sms.message, "Hello, world");
+
+        // A retry after assembly points back at the stored message
+        match fixture.upload(2, 2, 2, "world", None).await {
+            StoredSms::Duplicate(id) => assert_eq!(id, sms.id),
+            other => panic!("expected a duplicate, got {:?}", other),
+        }
+
+        fixture.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore = "requires DATABASE_URL"]
+    async fn reused_reference_starts_a_new_message() {
+        let fixture = setup().await;
+
+        // Two messages share reference 3; the second's first part must not be
+        // taken for a retry of the first's
+        let first = buffered(fixture.upload(3, 2, 1, "Meet at ", None).await);
+        let second = buffered(fixture.upload(3, 2, 1, "Call me ", None).await);
+        assert_ne!(first, second);
+
+        let sms = created(fixture.upload(3, 2, 2, "noon", None).await);
+        assert_eq!(sms.message, "Meet at noon");
+
+        let sms = created(fixture.upload(3, 2, 2, "later", None).await);
+        assert_eq!(sms.message, "Call me later");
+
+        fixture.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore = "requires DATABASE_URL"]
+    async fn client_message_ids_deduplicate_parts_and_message() {
+        let fixture = setup().await;
+
+        buffered(fixture.upload(4, 2, 1, "Your code ", Some("m4-1")).await);
+        let sms = created(fixture.upload(4, 2, 2, "is 1234", Some("m4-2")).await);
+
+        match fixture.upload(4, 2, 1, "Your code ", Some("m4-1")).await {
+            StoredSms::Duplicate(id) => assert_eq!(id, sms.id),
+            other => panic!("expected a duplicate, got {:?}", other),
+        }
+
+        // With its parts gone, the message itself still carries the first part's id
+        sqlx::query("DELETE FROM sms_parts WHERE sms_id = $1")
+            .bind(sms.id)
+            .execute(&fixture.pool)
+            .await
+            .unwrap();
+
+        buffered(fixture.upload(4, 2, 1, "Your code ", Some("m4-1")).await);
+        match fixture.upload(4, 2, 2, "is 1234", Some("m4-2")).await {
+            StoredSms::Duplicate(id) => assert_eq!(id, sms.id),
+            other => panic!("expected a duplicate, got {:?}", other),
+        }
+
+        let pending: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM sms_parts WHERE device_id = $1 AND sms_id IS NULL")
+            .bind(fixture.device.device_id)
+            .fetch_one(&fixture.pool)
+            .await
+            .unwrap();
+        assert_eq!(pending, 0);
+
+        fixture.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore = "requires DATABASE_URL"]
+    async fn stale_parts_are_stored_as_partial() {
+        let fixture = setup().await;
+
+        buffered(fixture.upload(5, 3, 1, "Part one, ", None).await);
+        buffered(fixture.upload(5, 3, 3, "part three", None).await);
+
+        let groups: Vec<_> = db::find_stale_sms_part_groups(&fixture.pool, 0.0, 1000)
+            .await
+            .unwrap()
+            .into_iter()
+            .filter(|group| group.device_id == fixture.device.device_id)
+            .collect();
+        assert_eq!(groups.len(), 1);
+
+        let parts = db::claim_sms_part_group(&fixture.pool, &groups[0], Some(0.0)).await.unwrap();
+        let sms = created(assemble(&fixture.pool, &fixture.config, &fixture.device, &fixture.routing, &parts).await.unwrap());
+
+        assert_eq!(sms.message, "Part one, part three");
+        assert_eq!(sms.part_count, Some(3));
+        assert!(sms.is_partial);
+
+        fixture.cleanup().await;
+    }
+}
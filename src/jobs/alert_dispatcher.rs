@@ -0,0 +1,82 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::Utc;
+use sqlx::PgPool;
+use tracing::{error, warn};
+
+use crate::config::AppConfig;
+use crate::db;
+use crate::errors::AppError;
+use crate::jobs::webhook_dispatcher::retry_delay_seconds;
+use crate::models::alert::PendingAlertNotification;
+use crate::notify::{Notifiers, SMTP_TIMEOUT};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+const BATCH_SIZE: i64 = 50;
+
+// Sends the alert notifications queued by the device monitor, retrying
+// failures on the same schedule and up to the same number of attempts as
+// SMS webhook deliveries
+pub async fn run(pool: PgPool, config: AppConfig, notifiers: Arc<Notifiers>) {
+    let mut interval = tokio::time::interval(POLL_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        if let Err(e) = dispatch_due(&pool, &config, &notifiers).await {
+            error!("Alert notification dispatch failed: {:?}", e);
+        }
+    }
+}
+
+async fn dispatch_due(pool: &PgPool, config: &AppConfig, notifiers: &Notifiers) -> Result<(), AppError> {
+    // The lease has to outlive a full batch of timed-out webhooks or emails
+    let timeout = Duration::from_secs(config.webhook_timeout_seconds).max(SMTP_TIMEOUT);
+    let lease_seconds = (timeout * 3).as_secs_f64();
+
+    let notifications = db::claim_due_alert_notifications(pool, BATCH_SIZE, lease_seconds).await?;
+
+    let sends = notifications
+        .into_iter()
+        .map(|notification| deliver(pool, config, notifiers, notification));
+    futures_util::future::join_all(sends).await;
+
+    Ok(())
+}
+
+async fn deliver(pool: &PgPool, config: &AppConfig, notifiers: &Notifiers, notification: PendingAlertNotification) {
+    let attempt_number = notification.attempts + 1;
+
+    let error = notifiers
+        .notify(&notification.channel, &notification.alert, notification.event)
+        .await
+        .err()
+        .map(|e| e.to_string());
+
+    let now = Utc::now();
+    let (status, next_attempt_at) = if error.is_none() {
+        ("succeeded", now)
+    } else if attempt_number >= config.webhook_max_attempts {
+        warn!(
+            "Alert notification {} failed permanently after {} attempts",
+            notification.id, attempt_number
+        );
+        ("failed", now)
+    } else {
+        ("pending", now + chrono::Duration::seconds(retry_delay_seconds(attempt_number)))
+    };
+
+    if let Err(e) = db::record_alert_notification_attempt(
+        pool,
+        notification.id,
+        attempt_number,
+        status,
+        next_attempt_at,
+        error.as_deref(),
+    )
+    .await
+    {
+        error!("Failed to record attempt for alert notification {}: {:?}", notification.id, e);
+    }
+}
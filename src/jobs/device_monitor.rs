@@ -0,0 +1,44 @@
+use std::time::Duration;
+
+use sqlx::PgPool;
+use tracing::{error, warn};
+
+use crate::config::AppConfig;
+use crate::db;
+use crate::errors::AppError;
+
+// Opens an alert when a device stops sending heartbeats (or, if enabled,
+// stops receiving SMS) and resolves it once the device recovers. Both queue
+// notifications to the owner's alert channels, which the alert dispatcher
+// sends.
+pub async fn run(pool: PgPool, config: AppConfig) {
+    let mut interval = tokio::time::interval(Duration::from_secs(config.alert_check_interval_seconds.max(1)));
+
+    loop {
+        interval.tick().await;
+
+        if let Err(e) = check_devices(&pool, &config).await {
+            error!("Device monitor check failed: {:?}", e);
+        }
+    }
+}
+
+async fn check_devices(pool: &PgPool, config: &AppConfig) -> Result<(), AppError> {
+    let offline_after_seconds = config.device_offline_after_seconds as f64;
+    let sms_window_seconds = config.alert_sms_silence_seconds as f64;
+
+    // Recoveries first, so a device that came back and went quiet again
+    // within one check gets a fresh alert
+    db::resolve_device_alerts(pool, offline_after_seconds, sms_window_seconds).await?;
+
+    let mut opened = db::open_heartbeat_alerts(pool, offline_after_seconds).await?;
+    if config.alert_sms_silence_seconds > 0 {
+        opened.extend(db::open_sms_silence_alerts(pool, sms_window_seconds).await?);
+    }
+
+    for alert in &opened {
+        warn!("Device {} alert opened: {}", alert.device_id, alert.kind);
+    }
+
+    Ok(())
+}
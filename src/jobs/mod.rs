@@ -0,0 +1,8 @@
+pub mod alert_dispatcher;
+pub mod device_monitor;
+pub mod retention;
+pub mod sender_backfill;
+pub mod sms_listener;
+pub mod sms_reassembly;
+pub mod token_cleanup;
+pub mod webhook_dispatcher;
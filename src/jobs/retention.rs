@@ -0,0 +1,131 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::attachments;
+use crate::config::AppConfig;
+use crate::db;
+use crate::errors::AppError;
+use crate::models::retention::{EffectiveRetention, RetentionReport};
+use crate::storage::BlobStore;
+
+// Deletes are done in slices so a large backlog never holds long locks
+const BATCH_SIZE: i64 = 500;
+
+enum Purge {
+    OlderThan { cutoff: DateTime<Utc>, otp_only: bool },
+    BeyondCount(i64),
+}
+
+// Applies every user's retention policies and records what each run removed
+pub async fn run(pool: PgPool, config: AppConfig, blob_store: Arc<dyn BlobStore>) {
+    let mut interval = tokio::time::interval(Duration::from_secs(config.retention_interval_seconds.max(1)));
+
+    loop {
+        interval.tick().await;
+
+        if let Err(e) = purge_expired(&pool, blob_store.as_ref()).await {
+            error!("Retention sweep failed: {:?}", e);
+        }
+    }
+}
+
+async fn purge_expired(pool: &PgPool, blob_store: &dyn BlobStore) -> Result<(), AppError> {
+    let policies = db::find_effective_retention(pool).await?;
+
+    // Rows come ordered by user, so each user's devices are contiguous
+    let mut report: Option<RetentionReport> = None;
+    for policy in policies {
+        if report.as_ref().is_some_and(|r| r.user_id != policy.user_id) {
+            finish(pool, report.take()).await;
+        }
+        let report = report.get_or_insert_with(|| RetentionReport::new(policy.user_id));
+
+        if let Err(e) = purge_device(pool, blob_store, &policy, report).await {
+            error!("Retention purge failed for device {}: {:?}", policy.device_id, e);
+        }
+    }
+    finish(pool, report).await;
+
+    Ok(())
+}
+
+async fn purge_device(
+    pool: &PgPool,
+    blob_store: &dyn BlobStore,
+    policy: &EffectiveRetention,
+    report: &mut RetentionReport,
+) -> Result<(), AppError> {
+    let now = Utc::now();
+
+    // One-time codes go first so the age and count passes don't claim them
+    if let Some(hours) = policy.otp_max_age_hours {
+        let cutoff = now - chrono::Duration::hours(hours.into());
+        let purge = Purge::OlderThan { cutoff, otp_only: true };
+        let deleted = drain(pool, blob_store, policy.device_id, &purge, report).await?;
+        report.deleted_otp += deleted;
+    }
+
+    if let Some(days) = policy.max_age_days {
+        let cutoff = now - chrono::Duration::days(days.into());
+        let purge = Purge::OlderThan { cutoff, otp_only: false };
+        let deleted = drain(pool, blob_store, policy.device_id, &purge, report).await?;
+        report.deleted_by_age += deleted;
+    }
+
+    if let Some(keep) = policy.max_count {
+        let purge = Purge::BeyondCount(keep.into());
+        let deleted = drain(pool, blob_store, policy.device_id, &purge, report).await?;
+        report.deleted_by_count += deleted;
+    }
+
+    Ok(())
+}
+
+// Repeats one kind of purge until a batch comes back short
+async fn drain(
+    pool: &PgPool,
+    blob_store: &dyn BlobStore,
+    device_id: Uuid,
+    purge: &Purge,
+    report: &mut RetentionReport,
+) -> Result<i64, AppError> {
+    let mut total = 0;
+
+    loop {
+        let batch = match *purge {
+            Purge::OlderThan { cutoff, otp_only } => {
+                db::purge_sms_older_than(pool, device_id, cutoff, otp_only, BATCH_SIZE).await?
+            }
+            Purge::BeyondCount(keep) => db::purge_sms_beyond_count(pool, device_id, keep, BATCH_SIZE).await?,
+        };
+
+        attachments::discard(blob_store, &batch.storage_keys).await;
+        report.deleted_attachments += batch.storage_keys.len() as i64;
+        total += batch.deleted;
+
+        if batch.deleted < BATCH_SIZE {
+            return Ok(total);
+        }
+    }
+}
+
+async fn finish(pool: &PgPool, report: Option<RetentionReport>) {
+    let Some(mut report) = report else {
+        return;
+    };
+    report.finished_at = Utc::now();
+
+    let deleted = report.deleted_by_age + report.deleted_by_count + report.deleted_otp;
+    if deleted > 0 {
+        info!("Retention removed {} messages for user {}", deleted, report.user_id);
+    }
+
+    if let Err(e) = db::save_retention_report(pool, &report).await {
+        error!("Failed to save retention report for user {}: {:?}", report.user_id, e);
+    }
+}
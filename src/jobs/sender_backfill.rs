@@ -0,0 +1,55 @@
+use std::time::Duration;
+
+use sqlx::PgPool;
+use tracing::{error, info};
+
+use crate::db;
+use crate::errors::AppError;
+use crate::phone;
+
+const BATCH_SIZE: i64 = 500;
+const RETRY_DELAY: Duration = Duration::from_secs(60);
+
+// Rewrite senders of messages stored before normalization, so old and new
+// rows group into the same conversations. Exits once nothing is left.
+pub async fn run(pool: PgPool) {
+    loop {
+        match backfill(&pool).await {
+            Ok(count) => {
+                if count > 0 {
+                    info!("Normalized senders of {} existing messages", count);
+                }
+                return;
+            }
+            Err(e) => error!("Sender backfill failed: {:?}", e),
+        }
+
+        tokio::time::sleep(RETRY_DELAY).await;
+    }
+}
+
+async fn backfill(pool: &PgPool) -> Result<u64, AppError> {
+    let mut total = 0;
+
+    loop {
+        let rows = db::find_unnormalized_senders(pool, BATCH_SIZE).await?;
+        if rows.is_empty() {
+            return Ok(total);
+        }
+
+        let mut ids = Vec::with_capacity(rows.len());
+        let mut senders = Vec::with_capacity(rows.len());
+        let mut sender_types = Vec::with_capacity(rows.len());
+        let mut counterparts = Vec::with_capacity(rows.len());
+
+        for row in rows {
+            let normalized = phone::normalize_sender(&row.raw_sender, row.default_region.as_deref());
+            ids.push(row.id);
+            senders.push(normalized.sender);
+            sender_types.push(normalized.sender_type.as_str().to_string());
+            counterparts.push(normalized.counterpart);
+        }
+
+        total += db::update_normalized_senders(pool, &ids, &senders, &sender_types, &counterparts).await?;
+    }
+}
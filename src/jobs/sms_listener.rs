@@ -0,0 +1,76 @@
+use std::time::Duration;
+
+use serde::Deserialize;
+use sqlx::{postgres::PgListener, PgPool};
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::db;
+use crate::events::{SmsEvent, SmsEvents};
+
+// Channels used by the `notify_sms_inserted` and `notify_outbound_queued` triggers
+const SMS_CHANNEL: &str = "sms_inserted";
+const OUTBOUND_CHANNEL: &str = "outbound_queued";
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Deserialize)]
+struct SmsNotification {
+    id: Uuid,
+    user_id: Uuid,
+}
+
+#[derive(Debug, Deserialize)]
+struct OutboundNotification {
+    device_id: Uuid,
+}
+
+// Relay Postgres notifications for new SMS and queued outbound messages to
+// this instance's subscribers, so every replica sees changes made through any other
+pub async fn run(pool: PgPool, events: SmsEvents) {
+    loop {
+        if let Err(e) = listen(&pool, &events).await {
+            error!("SMS listener failed: {:?}", e);
+        }
+
+        events.publish(SmsEvent::Interrupted);
+        tokio::time::sleep(RECONNECT_DELAY).await;
+    }
+}
+
+async fn listen(pool: &PgPool, events: &SmsEvents) -> Result<(), sqlx::Error> {
+    let mut listener = PgListener::connect_with(pool).await?;
+    listener.listen_all([SMS_CHANNEL, OUTBOUND_CHANNEL]).await?;
+    info!("Listening for SMS notifications");
+
+    loop {
+        // `None` means the connection dropped; the listener reconnects on the
+        // next call, but anything sent in between is gone
+        let Some(notification) = listener.try_recv().await? else {
+            warn!("SMS listener connection lost, reconnecting");
+            events.publish(SmsEvent::Interrupted);
+            continue;
+        };
+
+        if notification.channel() == OUTBOUND_CHANNEL {
+            match serde_json::from_str::<OutboundNotification>(notification.payload()) {
+                Ok(payload) => events.publish(SmsEvent::OutboundQueued { device_id: payload.device_id }),
+                Err(e) => error!("Invalid outbound notification payload: {:?}", e),
+            }
+            continue;
+        }
+
+        let payload: SmsNotification = match serde_json::from_str(notification.payload()) {
+            Ok(payload) => payload,
+            Err(e) => {
+                error!("Invalid SMS notification payload: {:?}", e);
+                continue;
+            }
+        };
+
+        match db::find_sms_by_id(pool, payload.id).await {
+            Ok(Some(sms)) => events.publish(SmsEvent::Stored { user_id: payload.user_id, sms: Box::new(sms) }),
+            Ok(None) => {}
+            Err(e) => error!("Failed to load notified SMS {}: {:?}", payload.id, e),
+        }
+    }
+}
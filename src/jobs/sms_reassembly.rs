@@ -0,0 +1,83 @@
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::time::Duration;
+
+use sqlx::PgPool;
+use tracing::{error, warn};
+use uuid::Uuid;
+
+use crate::config::AppConfig;
+use crate::db;
+use crate::errors::AppError;
+use crate::ingest::{self, IngestRouting};
+use crate::models::device::AuthenticatedDevice;
+use crate::models::sms::StaleSmsPartGroup;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(30);
+const BATCH_SIZE: i64 = 100;
+
+// Stores concatenated messages whose remaining parts never arrived, once
+// their first part is older than `sms_reassembly_timeout_seconds`
+pub async fn run(pool: PgPool, config: AppConfig) {
+    let mut interval = tokio::time::interval(POLL_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        if let Err(e) = flush_stale_parts(&pool, &config).await {
+            error!("SMS reassembly sweep failed: {:?}", e);
+        }
+    }
+}
+
+async fn flush_stale_parts(pool: &PgPool, config: &AppConfig) -> Result<(), AppError> {
+    let timeout_seconds = config.sms_reassembly_timeout_seconds as f64;
+    let groups = db::find_stale_sms_part_groups(pool, timeout_seconds, BATCH_SIZE).await?;
+
+    let mut routing_by_user: HashMap<Uuid, IngestRouting> = HashMap::new();
+
+    // A message that can't be stored is left for the next sweep; the rest go ahead
+    for group in groups {
+        if let Err(e) = flush_group(pool, config, &mut routing_by_user, &group, timeout_seconds).await {
+            error!("Failed to store partial message {} from device {}: {:?}", group.group_id, group.device_id, e);
+        }
+    }
+
+    Ok(())
+}
+
+async fn flush_group(
+    pool: &PgPool,
+    config: &AppConfig,
+    routing_by_user: &mut HashMap<Uuid, IngestRouting>,
+    group: &StaleSmsPartGroup,
+    timeout_seconds: f64,
+) -> Result<(), AppError> {
+    // Another replica, or the last part arriving just now, may have got there first
+    let parts = db::claim_sms_part_group(pool, group, Some(timeout_seconds)).await?;
+    if parts.is_empty() {
+        return Ok(());
+    }
+
+    let routing = match routing_by_user.entry(group.user_id) {
+        Entry::Occupied(entry) => entry.into_mut(),
+        Entry::Vacant(entry) => entry.insert(IngestRouting::load(pool, group.user_id).await?),
+    };
+
+    let device = AuthenticatedDevice {
+        device_id: group.device_id,
+        user_id: group.user_id,
+        default_region: group.default_region.clone(),
+    };
+
+    warn!(
+        "Storing partial message from device {}: {} of {} parts",
+        device.device_id,
+        parts.len(),
+        group.total_parts
+    );
+
+    ingest::assemble(pool, config, &device, routing, &parts).await?;
+
+    Ok(())
+}
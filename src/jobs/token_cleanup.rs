@@ -0,0 +1,23 @@
+use std::time::Duration;
+
+use sqlx::PgPool;
+use tracing::{error, info};
+
+use crate::db;
+
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+// Periodically drop denylist entries whose tokens have expired on their own
+pub async fn run(pool: PgPool) {
+    let mut interval = tokio::time::interval(CLEANUP_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        match db::delete_expired_revoked_tokens(&pool).await {
+            Ok(0) => {}
+            Ok(count) => info!("Removed {} expired revoked tokens", count),
+            Err(e) => error!("Failed to clean up revoked tokens: {:?}", e),
+        }
+    }
+}
@@ -0,0 +1,150 @@
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+use reqwest::{header::CONTENT_TYPE, Client};
+use sqlx::PgPool;
+use tracing::{error, warn};
+
+use crate::attachments;
+use crate::config::AppConfig;
+use crate::db;
+use crate::errors::AppError;
+use crate::http_client;
+use crate::models::sms::SmsWithAttachments;
+use crate::models::webhook::{DeliveryAttemptResult, PendingDelivery, WebhookEventBody};
+use crate::signing;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+const BATCH_SIZE: i64 = 50;
+const BASE_RETRY_DELAY_SECONDS: i64 = 30;
+const MAX_RETRY_DELAY_SECONDS: i64 = 6 * 60 * 60;
+
+const EVENT_SMS_RECEIVED: &str = "sms.received";
+
+// Works through the webhook delivery queue, retrying failures with
+// exponential backoff until `webhook_max_attempts` is reached
+pub async fn run(pool: PgPool, config: AppConfig) {
+    let client = http_client::client(Duration::from_secs(config.webhook_timeout_seconds));
+
+    let mut interval = tokio::time::interval(POLL_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        if let Err(e) = dispatch_due(&pool, &client, &config).await {
+            error!("Webhook dispatch failed: {:?}", e);
+        }
+    }
+}
+
+async fn dispatch_due(pool: &PgPool, client: &Client, config: &AppConfig) -> Result<(), AppError> {
+    // The lease has to outlive a full batch of timed-out requests
+    let lease_seconds = (config.webhook_timeout_seconds * 3) as f64;
+
+    let deliveries = db::claim_due_webhook_deliveries(pool, BATCH_SIZE, lease_seconds).await?;
+
+    let sends = deliveries
+        .into_iter()
+        .map(|delivery| deliver(pool, client, config, delivery));
+    futures_util::future::join_all(sends).await;
+
+    Ok(())
+}
+
+async fn deliver(pool: &PgPool, client: &Client, config: &AppConfig, delivery: PendingDelivery) {
+    let attempt_number = delivery.attempts + 1;
+
+    // A delivery that can't be prepared counts as a failed attempt, so it
+    // backs off and eventually gives up like any other
+    let result = match send(pool, client, config, &delivery).await {
+        Ok(result) => result,
+        Err(e) => {
+            error!("Failed to prepare webhook delivery {}: {:?}", delivery.id, e);
+            DeliveryAttemptResult {
+                response_status: None,
+                error: Some("Failed to prepare the delivery".to_string()),
+                duration_ms: 0,
+            }
+        }
+    };
+
+    let now = Utc::now();
+    let (status, next_attempt_at) = if result.error.is_none() {
+        ("succeeded", now)
+    } else if attempt_number >= config.webhook_max_attempts {
+        warn!("Webhook delivery {} failed permanently after {} attempts", delivery.id, attempt_number);
+        ("failed", now)
+    } else {
+        ("pending", now + chrono::Duration::seconds(retry_delay_seconds(attempt_number)))
+    };
+
+    if let Err(e) = db::record_webhook_attempt(pool, delivery.id, attempt_number, &result, status, next_attempt_at).await {
+        error!("Failed to record webhook attempt for {}: {:?}", delivery.id, e);
+    }
+}
+
+async fn send(
+    pool: &PgPool,
+    client: &Client,
+    config: &AppConfig,
+    delivery: &PendingDelivery,
+) -> Result<DeliveryAttemptResult, AppError> {
+    let sms = db::find_sms_by_id(pool, delivery.sms_id)
+        .await?
+        .ok_or_else(|| AppError::InternalServerError(format!("SMS {} no longer exists", delivery.sms_id)))?;
+    // Links are signed afresh on every attempt, so retries don't carry expired ones
+    let attachments = attachments::describe_for(pool, config, &[sms.id])
+        .await?
+        .remove(&sms.id)
+        .unwrap_or_default();
+    let sms = SmsWithAttachments { sms, attachments };
+
+    let body = serde_json::to_vec(&WebhookEventBody {
+        event: EVENT_SMS_RECEIVED,
+        delivery_id: delivery.id,
+        sms: &sms,
+    })
+    .map_err(|e| AppError::InternalServerError(format!("Failed to serialize webhook body: {}", e)))?;
+
+    let timestamp = Utc::now().timestamp();
+    let signature = signing::sign_webhook(&delivery.secret, timestamp, &body);
+
+    let started = Instant::now();
+    let response = client
+        .post(&delivery.url)
+        .header(CONTENT_TYPE, "application/json")
+        .header("X-Relay-Event", EVENT_SMS_RECEIVED)
+        .header("X-Relay-Delivery", delivery.id.to_string())
+        .header("X-Relay-Timestamp", timestamp.to_string())
+        .header("X-Relay-Signature", format!("sha256={}", signature))
+        .body(body)
+        .send()
+        .await;
+    let duration_ms = started.elapsed().as_millis() as i32;
+
+    let result = match response {
+        Ok(response) => {
+            let status = response.status();
+            DeliveryAttemptResult {
+                response_status: Some(status.as_u16() as i32),
+                error: (!status.is_success()).then(|| format!("Endpoint responded with {}", status)),
+                duration_ms,
+            }
+        }
+        Err(e) => DeliveryAttemptResult {
+            response_status: None,
+            error: Some(e.to_string()),
+            duration_ms,
+        },
+    };
+
+    Ok(result)
+}
+
+// Also the schedule for alert notification retries
+pub fn retry_delay_seconds(attempt_number: i32) -> i64 {
+    let exponent = (attempt_number - 1).clamp(0, 20) as u32;
+    BASE_RETRY_DELAY_SECONDS
+        .saturating_mul(2_i64.pow(exponent))
+        .min(MAX_RETRY_DELAY_SECONDS)
+}
@@ -1,32 +1,52 @@
 use axum::{
-    routing::{get, post},
+    extract::DefaultBodyLimit,
+    routing::{delete, get, patch, post, put},
     Router,
     serve,
 };
 use sqlx::PgPool;
 use std::net::SocketAddr;
+use std::sync::Arc;
 use tower_http::{
     cors::{Any, CorsLayer},
     trace::TraceLayer,
 };
-use tracing::info;
+use tracing::{info, warn};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
+mod attachments;
 mod config;
 mod db;
 mod errors;
+mod events;
 mod handlers;
+mod http_client;
+mod ingest;
+mod jobs;
 mod models;
+mod notify;
+mod otp;
+mod phone;
+mod rules;
+mod search;
+mod signing;
+mod storage;
 mod auth;
 
 use config::{AppConfig, create_db_pool};
 use errors::AppError;
+use events::SmsEvents;
+use notify::Notifiers;
+use storage::BlobStore;
 
 // Shared application state
 #[derive(Clone)]
 pub struct AppState {
     db_pool: PgPool,
     config: AppConfig,
+    sms_events: SmsEvents,
+    blob_store: Arc<dyn BlobStore>,
+    notifiers: Arc<Notifiers>,
 }
 
 #[tokio::main]
@@ -57,10 +77,42 @@ async fn main() -> Result<(), AppError> {
         .map_err(|e| AppError::DatabaseError(sqlx::Error::Migrate(Box::new(e))))?;
     info!("Migrations completed.");
 
+    // Secrets are only ever shown once, so devices registered before ingest
+    // tokens existed can't be issued one here; their owners have to call
+    // POST /device/{id}/secret and load the returned token onto the phone.
+    let without_secret = db::count_devices_without_ingest_secret(&db_pool).await?;
+    if without_secret > 0 {
+        warn!(
+            "{} device(s) have no ingest secret and will be rejected until their owner rotates one via POST /device/{{id}}/secret",
+            without_secret
+        );
+    }
+
+    let sms_events = SmsEvents::new();
+    let blob_store = storage::from_config(&config.blob_store);
+    let notifiers = Arc::new(Notifiers::from_config(&config)?);
+
+    // Room for a full set of attachments plus the form's other parts. Only
+    // multipart uploads get this much; sms_handler holds JSON to the usual limit.
+    let sms_body_limit = config.attachment_max_bytes * attachments::MAX_ATTACHMENTS_PER_MESSAGE + 1024 * 1024;
+
+    // Start background jobs
+    tokio::spawn(jobs::token_cleanup::run(db_pool.clone()));
+    tokio::spawn(jobs::sender_backfill::run(db_pool.clone()));
+    tokio::spawn(jobs::sms_listener::run(db_pool.clone(), sms_events.clone()));
+    tokio::spawn(jobs::webhook_dispatcher::run(db_pool.clone(), config.clone()));
+    tokio::spawn(jobs::sms_reassembly::run(db_pool.clone(), config.clone()));
+    tokio::spawn(jobs::retention::run(db_pool.clone(), config.clone(), blob_store.clone()));
+    tokio::spawn(jobs::device_monitor::run(db_pool.clone(), config.clone()));
+    tokio::spawn(jobs::alert_dispatcher::run(db_pool.clone(), config.clone(), notifiers.clone()));
+
     // Create application state
     let app_state = AppState {
         db_pool,
         config, // Clone config into state
+        sms_events,
+        blob_store,
+        notifiers,
     };
 
     // CORS configuration
@@ -74,10 +126,72 @@ async fn main() -> Result<(), AppError> {
         .route("/health", get(|| async { "OK" })) // Simple health check
         .route("/register", post(handlers::auth::register_handler))
         .route("/login", post(handlers::auth::login_handler))
+        .route("/logout", post(handlers::auth::logout_handler))
+        .route("/logout/all", post(handlers::auth::logout_all_handler))
+        .route("/token/refresh", post(handlers::auth::refresh_handler))
+        .route("/sessions", get(handlers::session::list_sessions))
+        .route("/sessions/{id}", delete(handlers::session::revoke_session))
         .route("/device", post(handlers::device::register_device))
         .route("/device", get(handlers::device::find_all_user_devices))
-        .route("/sms", post(handlers::sms::sms_handler))
+        .route("/device/{id}", patch(handlers::device::update_device))
+        .route("/device/{id}", delete(handlers::device::delete_device))
+        .route("/device/{id}/transfer", post(handlers::device::transfer_device))
+        .route("/device/{id}/heartbeats", get(handlers::device::list_heartbeats))
+        .route("/device/heartbeat", post(handlers::device::heartbeat))
+        .route("/device/transfers", get(handlers::device::list_transfers))
+        .route("/device/transfers/{id}/accept", post(handlers::device::accept_transfer))
+        .route("/device/transfers/{id}/decline", post(handlers::device::decline_transfer))
+        .route("/device/transfers/{id}", delete(handlers::device::cancel_transfer))
+        .route("/device/{id}/secret", post(handlers::device::rotate_ingest_secret))
+        .route("/device/{id}/secret", delete(handlers::device::revoke_ingest_secret))
+        .route("/device/outbound/claim", post(handlers::outbound::claim_outbound))
+        .route("/device/outbound/{id}/status", post(handlers::outbound::report_status))
+        .route("/sms", post(handlers::sms::sms_handler).layer(DefaultBodyLimit::max(sms_body_limit)))
         .route("/sms", get(handlers::sms::get_sms_handler))
+        .route("/sms", patch(handlers::sms::update_sms_handler))
+        .route("/sms/batch", post(handlers::sms::batch_sms_handler))
+        .route("/sms/{id}/parts", get(handlers::sms::get_sms_parts_handler))
+        .route("/attachments/{id}", get(handlers::attachment::download_attachment))
+        .route("/sms/stream", get(handlers::stream::sse_handler))
+        .route("/sms/stream/ws", get(handlers::stream::ws_handler))
+        .route("/sms/outbound", post(handlers::outbound::send_sms))
+        .route("/sms/outbound", get(handlers::outbound::list_outbound))
+        .route("/sms/outbound/{id}", get(handlers::outbound::get_outbound))
+        .route("/sms/outbound/{id}/retry", post(handlers::outbound::retry_outbound))
+        .route("/conversations", get(handlers::conversation::list_conversations))
+        .route("/conversations/{device_id}/{counterpart}", get(handlers::conversation::get_conversation_messages))
+        .route("/conversations/{device_id}/{counterpart}/read", post(handlers::conversation::mark_conversation_read))
+        .route("/labels", post(handlers::label::create_label))
+        .route("/labels", get(handlers::label::list_labels))
+        .route("/labels/{id}", patch(handlers::label::rename_label))
+        .route("/labels/{id}", delete(handlers::label::delete_label))
+        .route("/labels/{id}/apply", post(handlers::label::apply_label))
+        .route("/labels/{id}/remove", post(handlers::label::remove_label))
+        .route("/retention", get(handlers::retention::get_retention))
+        .route("/retention", put(handlers::retention::put_retention))
+        .route("/retention", delete(handlers::retention::delete_retention))
+        .route("/retention/devices/{device_id}", put(handlers::retention::put_device_retention))
+        .route("/retention/devices/{device_id}", delete(handlers::retention::delete_device_retention))
+        .route("/alerts", get(handlers::alert::list_alerts))
+        .route("/alerts/channels", post(handlers::alert::create_channel))
+        .route("/alerts/channels", get(handlers::alert::list_channels))
+        .route("/alerts/channels/{id}", delete(handlers::alert::delete_channel))
+        .route("/alerts/channels/{id}/test", post(handlers::alert::test_channel))
+        .route("/otp/latest", get(handlers::otp::latest_otp))
+        .route("/otp/patterns", post(handlers::otp::create_pattern))
+        .route("/otp/patterns", get(handlers::otp::list_patterns))
+        .route("/otp/patterns/{id}", delete(handlers::otp::delete_pattern))
+        .route("/rules", post(handlers::rule::create_rule))
+        .route("/rules", get(handlers::rule::list_rules))
+        .route("/rules/dry-run", post(handlers::rule::dry_run))
+        .route("/rules/{id}", put(handlers::rule::update_rule))
+        .route("/rules/{id}", delete(handlers::rule::delete_rule))
+        .route("/webhooks", post(handlers::webhook::create_webhook))
+        .route("/webhooks", get(handlers::webhook::list_webhooks))
+        .route("/webhooks/{id}", patch(handlers::webhook::update_webhook))
+        .route("/webhooks/{id}", delete(handlers::webhook::delete_webhook))
+        .route("/webhooks/{id}/deliveries", get(handlers::webhook::list_deliveries))
+        .route("/webhooks/{id}/attempts", get(handlers::webhook::list_attempts))
         // Apply state and CORS layer
         .with_state(app_state)
         .layer(cors)
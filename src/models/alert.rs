@@ -0,0 +1,148 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+pub const CHANNEL_WEBHOOK: &str = "webhook";
+pub const CHANNEL_EMAIL: &str = "email";
+
+// No heartbeat within `device_offline_after_seconds`. The other kind,
+// "sms_silence", means no SMS within `alert_sms_silence_seconds`.
+pub const ALERT_HEARTBEAT: &str = "heartbeat";
+
+#[derive(Debug, Serialize, FromRow, Clone)]
+pub struct AlertChannel {
+    pub id: Uuid,
+    pub kind: String,
+    pub target: String,
+    #[serde(skip)]
+    pub secret: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ChannelKind {
+    Webhook,
+    Email,
+}
+
+impl ChannelKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChannelKind::Webhook => CHANNEL_WEBHOOK,
+            ChannelKind::Email => CHANNEL_EMAIL,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AlertChannelPayload {
+    pub kind: ChannelKind,
+    // The webhook URL or email address
+    pub target: String,
+}
+
+// The signing secret of a webhook channel is only shown here
+#[derive(Debug, Serialize)]
+pub struct CreateAlertChannelResponse {
+    pub channel: AlertChannel,
+    pub secret: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AlertChannelListResponse {
+    pub channels: Vec<AlertChannel>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AlertChannelTestResponse {
+    pub delivered: bool,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, FromRow, Clone)]
+pub struct DeviceAlert {
+    pub id: Uuid,
+    pub device_id: Uuid,
+    pub device_name: String,
+    pub kind: String,
+    pub last_activity_at: Option<DateTime<Utc>>,
+    pub opened_at: DateTime<Utc>,
+    pub resolved_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlertEvent {
+    Opened,
+    Resolved,
+    // Sent on request to check a channel; not tied to a stored alert
+    Test,
+}
+
+impl AlertEvent {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AlertEvent::Opened => "opened",
+            AlertEvent::Resolved => "resolved",
+            AlertEvent::Test => "test",
+        }
+    }
+}
+
+// A notification queued for one channel. `status` is pending until it is
+// delivered or runs out of attempts; `error` is the latest failure.
+#[derive(Debug, Serialize, FromRow, Clone)]
+pub struct AlertNotification {
+    #[serde(skip)]
+    pub alert_id: Uuid,
+    pub channel_id: Option<Uuid>,
+    pub event: String,
+    pub channel_kind: String,
+    pub target: String,
+    pub status: String,
+    pub attempts: i32,
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+// A due notification, loaded with what the notifier needs to send it
+#[derive(Debug)]
+pub struct PendingAlertNotification {
+    pub id: Uuid,
+    pub attempts: i32,
+    pub event: AlertEvent,
+    pub channel: AlertChannel,
+    pub alert: DeviceAlert,
+}
+
+// Body POSTed to webhook channels
+#[derive(Debug, Serialize)]
+pub struct AlertWebhookBody<'a> {
+    pub event: &'static str,
+    pub alert: &'a DeviceAlert,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AlertQuery {
+    pub device_id: Option<Uuid>,
+    // Only alerts that are still open (true) or already resolved (false)
+    pub open: Option<bool>,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AlertHistoryItem {
+    #[serde(flatten)]
+    pub alert: DeviceAlert,
+    pub notifications: Vec<AlertNotification>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AlertListResponse {
+    pub total: i64,
+    pub data: Vec<AlertHistoryItem>,
+}
@@ -0,0 +1,55 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+#[derive(Debug, FromRow, Clone)]
+pub struct Attachment {
+    pub id: Uuid,
+    pub sms_id: Uuid,
+    pub content_type: String,
+    pub size_bytes: i64,
+    pub filename: Option<String>,
+    pub storage_key: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug)]
+pub struct NewAttachment<'a> {
+    pub id: Uuid,
+    pub position: i16,
+    pub content_type: &'a str,
+    pub size_bytes: i64,
+    pub filename: Option<&'a str>,
+    pub storage_key: &'a str,
+}
+
+// An attachment as returned to clients, with a signed download URL
+#[derive(Debug, Serialize)]
+pub struct AttachmentInfo {
+    pub id: Uuid,
+    pub content_type: String,
+    pub size_bytes: i64,
+    pub filename: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub url: String,
+    pub url_expires_at: DateTime<Utc>,
+}
+
+// The JSON `payload` part of a multipart MMS upload
+#[derive(Debug, Deserialize)]
+pub struct MmsPayload {
+    pub sender: String,
+    // Picture messages often have no text
+    #[serde(default)]
+    pub message: String,
+    pub client_message_id: Option<String>,
+    pub received_at: Option<DateTime<Utc>>,
+    pub smsc_timestamp: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DownloadQuery {
+    pub expires: i64,
+    pub signature: String,
+}
@@ -0,0 +1,48 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::models::sms::{Sms, SmsWithAttachments};
+
+// A device's messages from one counterpart, summarized for the inbox
+#[derive(Debug, Serialize)]
+pub struct Conversation {
+    pub device_id: Uuid,
+    pub counterpart: String,
+    pub message_count: i64,
+    pub unread_count: i64,
+    pub last_message_at: DateTime<Utc>,
+    pub last_message: Sms,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ConversationQuery {
+    pub device_id: Option<Uuid>,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+#[derive(Serialize)]
+pub struct ConversationListResponse {
+    pub total: i64,
+    pub data: Vec<Conversation>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ConversationMessagesQuery {
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+    // Only messages received before this time, for scrolling back through a thread
+    pub before: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize)]
+pub struct ConversationMessagesResponse {
+    pub total: i64,
+    pub data: Vec<SmsWithAttachments>,
+}
+
+#[derive(Serialize)]
+pub struct MarkReadResponse {
+    pub updated: u64,
+}
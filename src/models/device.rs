@@ -1,14 +1,22 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Duration, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::prelude::FromRow;
 use uuid::Uuid;
 
+use crate::models::heartbeat::Heartbeat;
+
 // Represents a user record fetched from the database
 #[derive(Debug, Serialize, FromRow, Clone)]
 pub struct Device {
     pub id: Uuid,
     pub user_id: Uuid,
     pub device_name: String,
+    // Region used to read sender numbers that lack a country code
+    pub default_region: Option<String>,
+    // Set once the device is retired; its messages are kept but it can't ingest or send
+    pub retired_at: Option<DateTime<Utc>>,
+    // When the phone last sent a heartbeat
+    pub last_seen_at: Option<DateTime<Utc>>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
@@ -17,19 +25,83 @@ pub struct Device {
 pub struct NewDevice<'a> {
     pub device_name: &'a str,
     pub user_id: &'a Uuid,
+    pub default_region: Option<&'a str>,
+    pub ingest_secret_hash: &'a str,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct RegisterPayload {
     pub device_name: String,
+    pub default_region: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateDevicePayload {
+    pub device_name: Option<String>,
+    pub default_region: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeleteDeviceQuery {
+    // Retire the device instead of deleting it along with its messages
+    #[serde(default)]
+    pub keep_history: bool,
 }
 
 #[derive(Debug, Serialize)]
 pub struct RegisterResponse {
     pub device_id: Uuid,
+    pub ingest_token: String,
+}
+
+// Returned when a device's ingest secret is rotated; the token is only shown once
+#[derive(Debug, Serialize)]
+pub struct IngestTokenResponse {
+    pub device_id: Uuid,
+    pub ingest_token: String,
+}
+
+impl Device {
+    // Online while heartbeats keep arriving; offline once none has been seen
+    // for `offline_after`. Phones that never sent one are unknown.
+    pub fn status(&self, now: DateTime<Utc>, offline_after: Duration) -> DeviceStatus {
+        match (self.retired_at, self.last_seen_at) {
+            (Some(_), _) => DeviceStatus::Retired,
+            (None, None) => DeviceStatus::Unknown,
+            (None, Some(seen)) if now - seen > offline_after => DeviceStatus::Offline,
+            (None, Some(_)) => DeviceStatus::Online,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DeviceStatus {
+    Online,
+    Offline,
+    Unknown,
+    Retired,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DeviceSummary {
+    #[serde(flatten)]
+    pub device: Device,
+    pub status: DeviceStatus,
+    pub unread_count: i64,
+    // The latest heartbeat, if the phone has sent one
+    pub health: Option<Heartbeat>,
 }
 
 #[derive(Debug, Serialize)]
 pub struct FindAllResponse {
-    pub devices: Vec<Device>,
+    pub devices: Vec<DeviceSummary>,
+}
+
+// Represents the device authenticated by its ingest token
+#[derive(Debug, Clone, Serialize)]
+pub struct AuthenticatedDevice {
+    pub device_id: Uuid,
+    pub user_id: Uuid,
+    pub default_region: Option<String>,
 }
@@ -0,0 +1,91 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+#[derive(Debug, Serialize, FromRow, Clone)]
+pub struct Heartbeat {
+    #[serde(skip)]
+    pub device_id: Uuid,
+    pub battery_level: Option<i16>,
+    pub is_charging: Option<bool>,
+    pub signal_strength: Option<i16>,
+    pub network_type: Option<String>,
+    pub app_version: Option<String>,
+    pub sim_operator: Option<String>,
+    pub sim_country: Option<String>,
+    pub received_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum NetworkType {
+    Wifi,
+    Ethernet,
+    #[serde(rename = "5g")]
+    Cellular5g,
+    #[serde(rename = "4g")]
+    Cellular4g,
+    #[serde(rename = "3g")]
+    Cellular3g,
+    #[serde(rename = "2g")]
+    Cellular2g,
+    None,
+}
+
+impl NetworkType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NetworkType::Wifi => "wifi",
+            NetworkType::Ethernet => "ethernet",
+            NetworkType::Cellular5g => "5g",
+            NetworkType::Cellular4g => "4g",
+            NetworkType::Cellular3g => "3g",
+            NetworkType::Cellular2g => "2g",
+            NetworkType::None => "none",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SimInfo {
+    pub operator: Option<String>,
+    // ISO 3166 country of the SIM's network
+    pub country: Option<String>,
+}
+
+// Sent periodically by the phone; every field is optional since not every
+// phone can report everything
+#[derive(Debug, Deserialize)]
+pub struct HeartbeatPayload {
+    // Percent
+    pub battery_level: Option<i16>,
+    pub is_charging: Option<bool>,
+    // dBm
+    pub signal_strength: Option<i16>,
+    pub network_type: Option<NetworkType>,
+    pub app_version: Option<String>,
+    pub sim: Option<SimInfo>,
+}
+
+#[derive(Debug)]
+pub struct NewHeartbeat<'a> {
+    pub device_id: Uuid,
+    pub battery_level: Option<i16>,
+    pub is_charging: Option<bool>,
+    pub signal_strength: Option<i16>,
+    pub network_type: Option<&'a str>,
+    pub app_version: Option<&'a str>,
+    pub sim_operator: Option<&'a str>,
+    pub sim_country: Option<&'a str>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct HeartbeatQuery {
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct HeartbeatListResponse {
+    pub data: Vec<Heartbeat>,
+}
@@ -0,0 +1,46 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+use crate::models::sms::SmsSelection;
+
+// A user-defined label that can be put on any number of messages
+#[derive(Debug, Serialize, FromRow, Clone)]
+pub struct Label {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+// Used for both creating and renaming a label
+#[derive(Debug, Deserialize)]
+pub struct LabelPayload {
+    pub name: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LabelListResponse {
+    pub labels: Vec<Label>,
+}
+
+// Messages to label or unlabel: give either `ids` or `filter`. As with
+// `PATCH /sms`, a filter with no criteria must be confirmed with `all`.
+#[derive(Debug, Deserialize)]
+pub struct LabelTargetPayload {
+    pub ids: Option<Vec<Uuid>>,
+    pub filter: Option<SmsSelection>,
+    #[serde(default)]
+    pub all: bool,
+}
+
+// A label as shown on a listed message
+#[derive(Debug, Serialize, FromRow)]
+pub struct SmsLabel {
+    #[serde(skip)]
+    pub sms_id: Uuid,
+    pub id: Uuid,
+    pub name: String,
+}
@@ -1,3 +1,15 @@
 pub mod user;
 pub mod device;
-pub mod sms;
\ No newline at end of file
+pub mod sms;
+pub mod session;
+pub mod webhook;
+pub mod rule;
+pub mod otp;
+pub mod outbound;
+pub mod conversation;
+pub mod attachment;
+pub mod label;
+pub mod retention;
+pub mod transfer;
+pub mod heartbeat;
+pub mod alert;
@@ -0,0 +1,55 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+// A user-defined regex for extracting codes the built-in rules miss.
+// The code is taken from the `code` named group, else the first group.
+#[derive(Debug, Serialize, FromRow, Clone)]
+pub struct OtpPattern {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub pattern: String,
+    pub issuer: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug)]
+pub struct NewOtpPattern<'a> {
+    pub user_id: &'a Uuid,
+    pub pattern: &'a str,
+    pub issuer: Option<&'a str>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OtpPatternPayload {
+    pub pattern: String,
+    pub issuer: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct OtpPatternListResponse {
+    pub patterns: Vec<OtpPattern>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OtpQuery {
+    pub device_id: Option<Uuid>,
+    // National numbers (no country code) are only accepted with device_id
+    pub sender: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+    // Mark the returned code as used so it isn't handed out again
+    #[serde(default)]
+    pub consume: bool,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct OtpResponse {
+    pub sms_id: Uuid,
+    pub device_id: Uuid,
+    pub sender: String,
+    pub code: String,
+    pub issuer: Option<String>,
+    pub received_at: DateTime<Utc>,
+    pub consumed_at: Option<DateTime<Utc>>,
+}
@@ -0,0 +1,131 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+pub const STATUS_QUEUED: &str = "queued";
+pub const STATUS_CLAIMED: &str = "claimed";
+pub const STATUS_SENT: &str = "sent";
+pub const STATUS_DELIVERED: &str = "delivered";
+pub const STATUS_FAILED: &str = "failed";
+// Claimed, but the phone never reported back in time; only re-queued on request
+pub const STATUS_EXPIRED: &str = "expired";
+// Still waiting when its device was retired or transferred; never sent
+pub const STATUS_CANCELLED: &str = "cancelled";
+
+pub const STATUSES: [&str; 7] = [
+    STATUS_QUEUED,
+    STATUS_CLAIMED,
+    STATUS_SENT,
+    STATUS_DELIVERED,
+    STATUS_FAILED,
+    STATUS_EXPIRED,
+    STATUS_CANCELLED,
+];
+
+// Statuses an owner can re-queue a message from
+pub const RETRYABLE_STATUSES: [&str; 2] = [STATUS_FAILED, STATUS_EXPIRED];
+
+// Represents a message queued for a device to send
+#[derive(Debug, Serialize, FromRow, Clone)]
+pub struct OutboundSms {
+    pub id: Uuid,
+    pub device_id: Uuid,
+    pub recipient: String,
+    pub message: String,
+    pub status: String,
+    pub error: Option<String>,
+    pub claimed_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug)]
+pub struct NewOutboundSms<'a> {
+    pub device_id: &'a Uuid,
+    pub recipient: &'a str,
+    pub message: &'a str,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct OutboundSmsEvent {
+    pub status: String,
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OutboundPayload {
+    pub device_id: Uuid,
+    pub recipient: String,
+    pub message: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OutboundQuery {
+    pub device_id: Option<Uuid>,
+    pub status: Option<String>,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+#[derive(Serialize)]
+pub struct OutboundListResponse {
+    pub total: i64,
+    pub data: Vec<OutboundSms>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct OutboundDetailResponse {
+    #[serde(flatten)]
+    pub message: OutboundSms,
+    pub events: Vec<OutboundSmsEvent>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ClaimQuery {
+    pub limit: Option<i64>,
+    // Long-poll for up to this many seconds when nothing is queued
+    pub wait: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ClaimResponse {
+    pub messages: Vec<OutboundSms>,
+}
+
+// Statuses a phone can report after claiming a message
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportedStatus {
+    Sent,
+    Delivered,
+    Failed,
+}
+
+impl ReportedStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReportedStatus::Sent => STATUS_SENT,
+            ReportedStatus::Delivered => STATUS_DELIVERED,
+            ReportedStatus::Failed => STATUS_FAILED,
+        }
+    }
+
+    // Statuses the message may currently be in for this report to apply.
+    // Repeating the current status is allowed so phones can safely retry, and
+    // a late report still lands on a claim that has since expired.
+    pub fn allowed_from(&self) -> &'static [&'static str] {
+        match self {
+            ReportedStatus::Sent => &[STATUS_CLAIMED, STATUS_EXPIRED, STATUS_SENT],
+            ReportedStatus::Delivered => &[STATUS_SENT, STATUS_DELIVERED],
+            ReportedStatus::Failed => &[STATUS_CLAIMED, STATUS_EXPIRED, STATUS_SENT, STATUS_FAILED],
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StatusReportPayload {
+    pub status: ReportedStatus,
+    pub error: Option<String>,
+}
@@ -0,0 +1,80 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+// Limits on how long messages are kept. Unset fields mean no limit, or for
+// a device policy, that the user-wide value applies.
+#[derive(Debug, Serialize, FromRow, Clone)]
+pub struct RetentionPolicy {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    // None for the user-wide policy
+    pub device_id: Option<Uuid>,
+    pub max_age_days: Option<i32>,
+    // Keep at most this many of a device's newest messages
+    pub max_count: Option<i32>,
+    // Shorter lifetime for messages carrying a one-time code
+    pub otp_max_age_hours: Option<i32>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+// Replaces a policy; omitted fields are cleared
+#[derive(Debug, Deserialize)]
+pub struct RetentionPolicyPayload {
+    pub max_age_days: Option<i32>,
+    pub max_count: Option<i32>,
+    pub otp_max_age_hours: Option<i32>,
+}
+
+// The limits that apply to one device after overrides
+#[derive(Debug, FromRow)]
+pub struct EffectiveRetention {
+    pub device_id: Uuid,
+    pub user_id: Uuid,
+    pub max_age_days: Option<i32>,
+    pub max_count: Option<i32>,
+    pub otp_max_age_hours: Option<i32>,
+}
+
+// One bounded delete: how many messages went, and the blobs of their attachments
+#[derive(Debug)]
+pub struct PurgeBatch {
+    pub deleted: i64,
+    pub storage_keys: Vec<String>,
+}
+
+#[derive(Debug, Serialize, FromRow, Clone)]
+pub struct RetentionReport {
+    #[serde(skip)]
+    pub user_id: Uuid,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    pub deleted_by_age: i64,
+    pub deleted_by_count: i64,
+    pub deleted_otp: i64,
+    pub deleted_attachments: i64,
+}
+
+impl RetentionReport {
+    pub fn new(user_id: Uuid) -> Self {
+        let now = Utc::now();
+        RetentionReport {
+            user_id,
+            started_at: now,
+            finished_at: now,
+            deleted_by_age: 0,
+            deleted_by_count: 0,
+            deleted_otp: 0,
+            deleted_attachments: 0,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct RetentionResponse {
+    pub policy: Option<RetentionPolicy>,
+    pub device_policies: Vec<RetentionPolicy>,
+    pub last_purge: Option<RetentionReport>,
+}
@@ -0,0 +1,128 @@
+use chrono::{DateTime, NaiveTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+pub const ACTION_FORWARD: &str = "forward";
+pub const ACTION_TAG: &str = "tag";
+pub const ACTION_LABEL: &str = "label";
+pub const ACTION_DISCARD: &str = "discard";
+pub const ACTION_MARK_SPAM: &str = "mark_spam";
+
+// Represents a routing rule evaluated against every incoming SMS
+#[derive(Debug, Serialize, FromRow, Clone)]
+pub struct Rule {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub name: String,
+    pub priority: i32,
+    pub enabled: bool,
+    pub sender_pattern: Option<String>,
+    pub message_pattern: Option<String>,
+    pub device_id: Option<Uuid>,
+    pub active_from: Option<NaiveTime>,
+    pub active_until: Option<NaiveTime>,
+    pub utc_offset_minutes: i32,
+    pub action: String,
+    pub webhook_id: Option<Uuid>,
+    pub tag: Option<String>,
+    pub label_id: Option<Uuid>,
+    pub stop_processing: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RuleAction {
+    Forward,
+    Tag,
+    Label,
+    Discard,
+    MarkSpam,
+}
+
+impl RuleAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RuleAction::Forward => ACTION_FORWARD,
+            RuleAction::Tag => ACTION_TAG,
+            RuleAction::Label => ACTION_LABEL,
+            RuleAction::Discard => ACTION_DISCARD,
+            RuleAction::MarkSpam => ACTION_MARK_SPAM,
+        }
+    }
+}
+
+// Used for both creating and replacing a rule
+#[derive(Debug, Deserialize)]
+pub struct RulePayload {
+    pub name: String,
+    #[serde(default)]
+    pub priority: i32,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    pub sender_pattern: Option<String>,
+    pub message_pattern: Option<String>,
+    pub device_id: Option<Uuid>,
+    pub active_from: Option<NaiveTime>,
+    pub active_until: Option<NaiveTime>,
+    #[serde(default)]
+    pub utc_offset_minutes: i32,
+    pub action: RuleAction,
+    pub webhook_id: Option<Uuid>,
+    pub tag: Option<String>,
+    pub label_id: Option<Uuid>,
+    #[serde(default)]
+    pub stop_processing: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+#[derive(Debug, Serialize)]
+pub struct RuleListResponse {
+    pub rules: Vec<Rule>,
+}
+
+// The message attributes rules are matched against
+#[derive(Debug)]
+pub struct RuleInput<'a> {
+    pub device_id: Uuid,
+    pub sender: &'a str,
+    pub message: &'a str,
+    pub at: DateTime<Utc>,
+}
+
+// Combined effect of all rules that matched a message
+#[derive(Debug, Default, Serialize)]
+pub struct RuleOutcome {
+    pub matched_rules: Vec<Uuid>,
+    pub forward_to: Vec<Uuid>,
+    pub tags: Vec<String>,
+    pub labels: Vec<Uuid>,
+    pub discard: bool,
+    pub spam: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DryRunPayload {
+    pub device_id: Option<Uuid>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DryRunResult {
+    pub sms_id: Uuid,
+    pub device_id: Uuid,
+    pub sender: String,
+    pub received_at: DateTime<Utc>,
+    #[serde(flatten)]
+    pub outcome: RuleOutcome,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DryRunResponse {
+    pub results: Vec<DryRunResult>,
+}
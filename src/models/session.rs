@@ -0,0 +1,49 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+// Represents a login session, kept alive by rotating refresh tokens
+#[derive(Debug, Serialize, FromRow, Clone)]
+pub struct Session {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub user_agent: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug)]
+pub struct NewSession<'a> {
+    pub user_id: &'a Uuid,
+    pub user_agent: Option<&'a str>,
+    pub refresh_token_hash: &'a str,
+    pub expires_at: DateTime<Utc>,
+}
+
+// Result of presenting a refresh token
+#[derive(Debug)]
+pub enum RefreshOutcome {
+    Rotated(Session),
+    // The token was already used; the whole session has been revoked
+    Reused,
+    Invalid,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RefreshPayload {
+    pub refresh_token: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SessionInfo {
+    #[serde(flatten)]
+    pub session: Session,
+    pub current: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SessionListResponse {
+    pub sessions: Vec<SessionInfo>,
+}
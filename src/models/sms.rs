@@ -3,45 +3,411 @@ use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 use uuid::Uuid;
 
-#[derive(Debug, FromRow, Serialize)]
+use crate::models::attachment::{AttachmentInfo, NewAttachment};
+use crate::models::label::SmsLabel;
+use crate::phone::SenderType;
+
+#[derive(Debug, FromRow, Serialize, Clone)]
 pub struct Sms {
     pub id: Uuid,
     pub device_id: Uuid,
     pub sender: String,
+    // As the phone reported it, before normalization
+    pub raw_sender: String,
+    pub sender_type: String,
+    pub counterpart: String,
     pub message: String,
     pub received_at: DateTime<Utc>,
+    pub tags: Vec<String>,
+    pub is_spam: bool,
+    pub is_read: bool,
+    pub is_starred: bool,
+    pub is_archived: bool,
+    pub otp_code: Option<String>,
+    pub otp_issuer: Option<String>,
+    // When the phone says it received the message, for uploads that report it
+    pub device_received_at: Option<DateTime<Utc>>,
+    // Service-center timestamp from the SMS itself
+    pub smsc_timestamp: Option<DateTime<Utc>>,
+    // Set for messages reassembled from concatenated parts
+    pub part_count: Option<i16>,
+    // True when reassembly timed out before every part arrived
+    pub is_partial: bool,
 }
 
 #[derive(Debug)]
 pub struct NewSms<'a> {
     pub device_id: &'a Uuid,
     pub sender: &'a str,
+    pub raw_sender: &'a str,
+    pub sender_type: &'a str,
+    pub counterpart: &'a str,
     pub message: &'a str,
+    pub otp_code: Option<&'a str>,
+    pub otp_issuer: Option<&'a str>,
+    pub client_message_id: Option<&'a str>,
+    pub device_received_at: Option<DateTime<Utc>>,
+    pub smsc_timestamp: Option<DateTime<Utc>>,
+    pub part_count: Option<i16>,
+    pub is_partial: bool,
+    // Buffered parts the message was assembled from
+    pub part_ids: &'a [Uuid],
+    // MMS content already written to the blob store
+    pub attachments: &'a [NewAttachment<'a>],
+}
+
+// Result of storing an upload: a new message, the id of the one it repeats,
+// the id of a part held for reassembly, or nothing if a rule discarded it
+#[derive(Debug)]
+pub enum StoredSms {
+    Created(Box<Sms>),
+    Duplicate(Uuid),
+    Buffered(Uuid),
+    Discarded,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct SmsPayload {
-    pub device_id: Uuid,
     pub sender: String,
     pub message: String,
+    // Lets the phone retry safely; may also be sent as an Idempotency-Key header
+    pub client_message_id: Option<String>,
+    // When the phone received the message, by its own clock
+    pub received_at: Option<DateTime<Utc>>,
+    pub smsc_timestamp: Option<DateTime<Utc>>,
+    pub concat: Option<ConcatInfo>,
+}
+
+// UDH concatenation header of one part of a long message
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct ConcatInfo {
+    pub reference: i32,
+    pub total: i16,
+    // 1-based position of this part
+    pub sequence: i16,
 }
 
 #[derive(Debug, Serialize)]
 pub struct SmsResponse {
+    // For a buffered part, the id of the part; otherwise the stored message.
+    // Discarded messages have no id.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<Uuid>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub buffered: bool,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub discarded: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<AttachmentInfo>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct SmsPart {
+    pub id: Uuid,
+    pub device_id: Uuid,
+    pub raw_sender: String,
+    pub counterpart: String,
+    pub reference: i32,
+    pub total_parts: i16,
+    pub sequence: i16,
+    pub message: String,
+    pub client_message_id: Option<String>,
+    pub received_at: DateTime<Utc>,
+    pub device_received_at: Option<DateTime<Utc>>,
+    pub smsc_timestamp: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug)]
+pub struct NewSmsPart<'a> {
+    pub device_id: &'a Uuid,
+    pub raw_sender: &'a str,
+    pub counterpart: &'a str,
+    pub concat: &'a ConcatInfo,
+    pub message: &'a str,
+    pub client_message_id: Option<&'a str>,
+    pub device_received_at: Option<DateTime<Utc>>,
+    pub smsc_timestamp: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug)]
+pub enum PartOutcome {
+    // Stored (or already held) while other parts are outstanding
+    Buffered(Uuid),
+    // A retry of a part whose message was already assembled
+    AlreadyAssembled(Uuid),
+    // This part completed the message; the caller now owns the claimed parts
+    Complete(Vec<SmsPart>),
+}
+
+// Parts still waiting for the rest of their message, with what's needed to store them
+#[derive(Debug, FromRow)]
+pub struct StaleSmsPartGroup {
+    pub group_id: Uuid,
+    pub device_id: Uuid,
+    pub user_id: Uuid,
+    pub default_region: Option<String>,
+    pub counterpart: String,
+    pub reference: i32,
+    pub total_parts: i16,
+}
+
+// A message stored before sender normalization, awaiting the backfill
+#[derive(Debug, FromRow)]
+pub struct UnnormalizedSender {
     pub id: Uuid,
+    pub raw_sender: String,
+    pub default_region: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct SmsPartListResponse {
+    pub parts: Vec<SmsPart>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BatchSmsItem {
+    pub sender: String,
+    pub message: String,
+    pub client_message_id: Option<String>,
+    // Device-side receive time of a buffered message
+    pub received_at: Option<DateTime<Utc>>,
+    pub smsc_timestamp: Option<DateTime<Utc>>,
+    pub concat: Option<ConcatInfo>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchItemStatus {
+    Accepted,
+    Duplicate,
+    // A part of a long message, held until the rest arrives
+    Buffered,
+    // Dropped by one of the owner's rules
+    Discarded,
+    Rejected,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BatchItemResult {
+    pub index: usize,
+    pub status: BatchItemStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<Uuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct BatchSmsResponse {
+    pub accepted: usize,
+    pub duplicates: usize,
+    pub buffered: usize,
+    pub discarded: usize,
+    pub rejected: usize,
+    pub results: Vec<BatchItemResult>,
+}
+
+impl BatchSmsResponse {
+    pub fn push(&mut self, index: usize, outcome: Result<StoredSms, String>) {
+        let (status, id, error) = match outcome {
+            Ok(StoredSms::Created(sms)) => {
+                self.accepted += 1;
+                (BatchItemStatus::Accepted, Some(sms.id), None)
+            }
+            Ok(StoredSms::Duplicate(id)) => {
+                self.duplicates += 1;
+                (BatchItemStatus::Duplicate, Some(id), None)
+            }
+            Ok(StoredSms::Buffered(id)) => {
+                self.buffered += 1;
+                (BatchItemStatus::Buffered, Some(id), None)
+            }
+            Ok(StoredSms::Discarded) => {
+                self.discarded += 1;
+                (BatchItemStatus::Discarded, None, None)
+            }
+            Err(reason) => {
+                self.rejected += 1;
+                (BatchItemStatus::Rejected, None, Some(reason))
+            }
+        };
+
+        self.results.push(BatchItemResult { index, status, id, error });
+    }
 }
 
 #[derive(Debug, Deserialize)]
 pub struct SmsQuery {
-    pub device_id: Uuid,
+    // Omit to list messages from all of the caller's devices
+    pub device_id: Option<Uuid>,
     pub limit: Option<i64>,
     pub offset: Option<i64>,
     pub from: Option<chrono::DateTime<chrono::Utc>>,
     pub to: Option<chrono::DateTime<chrono::Utc>>,
+    // Which timestamp `from`, `to` and the ordering refer to; defaults to server
+    pub clock: Option<SmsClock>,
+    // Full-text search; results are ordered by relevance when set
+    pub q: Option<String>,
+    // Matched after normalization, so any formatting of the number works
+    pub sender: Option<String>,
+    pub sender_type: Option<SenderType>,
+    pub is_read: Option<bool>,
+    pub is_starred: Option<bool>,
+    pub is_archived: Option<bool>,
+    pub label: Option<Uuid>,
+}
+
+impl SmsQuery {
+    pub fn selection(&self) -> SmsSelection {
+        SmsSelection {
+            device_id: self.device_id,
+            from: self.from,
+            to: self.to,
+            clock: self.clock,
+            q: self.q.clone(),
+            sender: self.sender.clone(),
+            sender_type: self.sender_type,
+            is_read: self.is_read,
+            is_starred: self.is_starred,
+            is_archived: self.is_archived,
+            label: self.label,
+        }
+    }
+}
+
+// Which messages a listing or bulk update applies to, as the client sends it
+#[derive(Debug, Default, Deserialize)]
+pub struct SmsSelection {
+    pub device_id: Option<Uuid>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub clock: Option<SmsClock>,
+    pub q: Option<String>,
+    // National numbers (no country code) are only accepted with device_id
+    pub sender: Option<String>,
+    pub sender_type: Option<SenderType>,
+    pub is_read: Option<bool>,
+    pub is_starred: Option<bool>,
+    pub is_archived: Option<bool>,
+    // Only messages carrying this label
+    pub label: Option<Uuid>,
+}
+
+impl SmsSelection {
+    // True when nothing narrows the selection down; `clock` only says which
+    // time `from` and `to` refer to
+    pub fn is_empty(&self) -> bool {
+        self.device_id.is_none()
+            && self.from.is_none()
+            && self.to.is_none()
+            && self.q.is_none()
+            && self.sender.is_none()
+            && self.sender_type.is_none()
+            && self.is_read.is_none()
+            && self.is_starred.is_none()
+            && self.is_archived.is_none()
+            && self.label.is_none()
+    }
+}
+
+// A selection resolved for the database: the sender normalized to a
+// counterpart and `q` turned into a `to_tsquery` expression
+#[derive(Debug, Default)]
+pub struct SmsFilter {
+    pub device_id: Option<Uuid>,
+    pub counterpart: Option<String>,
+    pub sender_type: Option<SenderType>,
+    pub clock: SmsClock,
+    pub tsquery: Option<String>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub is_read: Option<bool>,
+    pub is_starred: Option<bool>,
+    pub is_archived: Option<bool>,
+    pub label_id: Option<Uuid>,
+}
+
+// Bulk `PATCH /sms`: give either `ids` or `filter`, and the flags to set.
+// A filter with no criteria must be confirmed with `all`.
+#[derive(Debug, Deserialize)]
+pub struct SmsUpdatePayload {
+    pub ids: Option<Vec<Uuid>>,
+    pub filter: Option<SmsSelection>,
+    #[serde(default)]
+    pub all: bool,
+    pub set: SmsFlags,
+}
+
+// Flags left out are not changed
+#[derive(Debug, Deserialize)]
+pub struct SmsFlags {
+    pub is_read: Option<bool>,
+    pub is_starred: Option<bool>,
+    pub is_archived: Option<bool>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SmsUpdateResponse {
+    // Messages whose flags actually changed
+    pub updated: u64,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SmsClock {
+    // When the relay stored the message
+    #[default]
+    Server,
+    // When the phone received it
+    Device,
+    // When the SMS service center handled it
+    Smsc,
+}
+
+impl SmsClock {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SmsClock::Server => "server",
+            SmsClock::Device => "device",
+            SmsClock::Smsc => "smsc",
+        }
+    }
+}
+
+// A message with download links for its attachments, as returned in threads,
+// pushed to streams and posted to webhooks
+#[derive(Debug, Serialize)]
+pub struct SmsWithAttachments {
+    #[serde(flatten)]
+    pub sms: Sms,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<AttachmentInfo>,
+}
+
+// A listed message, with search relevance and a highlighted excerpt for `q` queries
+#[derive(Debug, Serialize)]
+pub struct SmsListItem {
+    #[serde(flatten)]
+    pub sms: Sms,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rank: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub snippet: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<AttachmentInfo>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub labels: Vec<SmsLabel>,
 }
 
 #[derive(Serialize)]
 pub struct SmsListResponse {
     pub total: i64,
-    pub data: Vec<Sms>,
+    pub data: Vec<SmsListItem>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SmsStreamQuery {
+    pub device_id: Option<Uuid>,
+    // Resume after this message id (SSE clients may send Last-Event-ID instead)
+    pub after: Option<Uuid>,
 }
@@ -0,0 +1,54 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+pub const STATUS_DECLINED: &str = "declined";
+pub const STATUS_CANCELLED: &str = "cancelled";
+
+// An offer to hand a device, and optionally its message history, to another
+// user. `to_user_id` is None when no account has the name it was offered to.
+#[derive(Debug, Serialize, FromRow, Clone)]
+pub struct DeviceTransfer {
+    pub id: Uuid,
+    pub device_id: Uuid,
+    pub device_name: String,
+    pub from_username: String,
+    #[serde(skip)]
+    pub to_user_id: Option<Uuid>,
+    pub to_username: String,
+    pub include_history: bool,
+    pub status: String,
+    pub created_at: DateTime<Utc>,
+    pub resolved_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TransferPayload {
+    // The receiving user
+    pub username: String,
+    // Hand over the device's messages too; otherwise the sender keeps them
+    // on a retired copy of the device
+    #[serde(default)]
+    pub include_history: bool,
+}
+
+// Optional body for accepting a transfer
+#[derive(Debug, Deserialize)]
+pub struct TransferAcceptPayload {
+    // Renames the device, for when the recipient already has one by its name
+    pub device_name: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TransferListResponse {
+    pub incoming: Vec<DeviceTransfer>,
+    pub outgoing: Vec<DeviceTransfer>,
+}
+
+// The device now belongs to the caller; the token is only shown once
+#[derive(Debug, Serialize)]
+pub struct TransferAcceptedResponse {
+    pub transfer: DeviceTransfer,
+    pub ingest_token: String,
+}
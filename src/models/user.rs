@@ -40,6 +40,8 @@ pub struct LoginPayload {
 pub struct LoginResponse {
     pub token: String,
     pub token_type: String,
+    pub expires_in: i64,
+    pub refresh_token: String,
 }
 
 // Represents the authenticated user's info extracted from JWT
@@ -47,4 +49,7 @@ pub struct LoginResponse {
 #[derive(Debug, Clone, Serialize)]
 pub struct AuthenticatedUser {
    pub user_id: Uuid,
+   pub session_id: Uuid,
+   pub token_id: Uuid,
+   pub token_expires_at: DateTime<Utc>,
 }
\ No newline at end of file
@@ -0,0 +1,125 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+use crate::models::sms::SmsWithAttachments;
+
+// Represents a user-configured endpoint that receives stored SMS
+#[derive(Debug, Serialize, FromRow, Clone)]
+pub struct Webhook {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub url: String,
+    pub description: Option<String>,
+    pub enabled: bool,
+    pub forward_all: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug)]
+pub struct NewWebhook<'a> {
+    pub user_id: &'a Uuid,
+    pub url: &'a str,
+    pub description: Option<&'a str>,
+    pub secret: &'a str,
+    pub forward_all: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateWebhookPayload {
+    pub url: String,
+    pub description: Option<String>,
+    // Set to false to only receive messages routed here by a rule
+    pub forward_all: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateWebhookPayload {
+    pub url: Option<String>,
+    pub description: Option<String>,
+    pub enabled: Option<bool>,
+    pub forward_all: Option<bool>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CreateWebhookResponse {
+    #[serde(flatten)]
+    pub webhook: Webhook,
+    pub secret: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WebhookListResponse {
+    pub webhooks: Vec<Webhook>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct WebhookDelivery {
+    pub id: Uuid,
+    pub webhook_id: Uuid,
+    pub sms_id: Uuid,
+    pub status: String,
+    pub attempts: i32,
+    pub next_attempt_at: DateTime<Utc>,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct WebhookDeliveryAttempt {
+    pub id: Uuid,
+    pub delivery_id: Uuid,
+    pub sms_id: Uuid,
+    pub attempt_number: i32,
+    pub response_status: Option<i32>,
+    pub error: Option<String>,
+    pub duration_ms: i32,
+    pub attempted_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WebhookLogQuery {
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+#[derive(Serialize)]
+pub struct WebhookDeliveryListResponse {
+    pub total: i64,
+    pub data: Vec<WebhookDelivery>,
+}
+
+#[derive(Serialize)]
+pub struct WebhookAttemptListResponse {
+    pub total: i64,
+    pub data: Vec<WebhookDeliveryAttempt>,
+}
+
+// A delivery claimed by the dispatcher, with everything needed to send it
+#[derive(Debug)]
+pub struct PendingDelivery {
+    pub id: Uuid,
+    pub attempts: i32,
+    pub url: String,
+    pub secret: String,
+    pub sms_id: Uuid,
+}
+
+// Body POSTed to webhook endpoints
+#[derive(Debug, Serialize)]
+pub struct WebhookEventBody<'a> {
+    pub event: &'static str,
+    pub delivery_id: Uuid,
+    pub sms: &'a SmsWithAttachments,
+}
+
+// Outcome of a single HTTP attempt, as recorded in the attempt log
+#[derive(Debug)]
+pub struct DeliveryAttemptResult {
+    pub response_status: Option<i32>,
+    pub error: Option<String>,
+    pub duration_ms: i32,
+}
@@ -0,0 +1,229 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use lettre::message::{header::ContentType, Mailbox};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+
+use super::{Notifier, NotifyError};
+use crate::config::{ConfigError, SmtpConfig, SmtpTls};
+use crate::models::alert::{AlertChannel, AlertEvent, DeviceAlert, ALERT_HEARTBEAT};
+
+pub const SMTP_TIMEOUT: Duration = Duration::from_secs(30);
+
+// Sends a short plain-text email per alert
+pub struct EmailNotifier {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl EmailNotifier {
+    pub fn new(config: &SmtpConfig) -> Result<Self, ConfigError> {
+        let invalid = |name: &str, e: &dyn std::fmt::Display| ConfigError::InvalidValue(name.to_string(), e.to_string());
+
+        let mut builder = match config.tls {
+            SmtpTls::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&config.host),
+            SmtpTls::StartTls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.host)
+                .map_err(|e| invalid("SMTP_HOST", &e))?,
+            SmtpTls::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(&config.host)
+                .map_err(|e| invalid("SMTP_HOST", &e))?,
+        }
+        .port(config.port)
+        .timeout(Some(SMTP_TIMEOUT));
+
+        if let (Some(username), Some(password)) = (&config.username, &config.password) {
+            builder = builder.credentials(Credentials::new(username.clone(), password.clone()));
+        }
+
+        Ok(EmailNotifier {
+            transport: builder.build(),
+            from: config.from.parse().map_err(|e| invalid("SMTP_FROM", &e))?,
+        })
+    }
+}
+
+#[async_trait]
+impl Notifier for EmailNotifier {
+    async fn notify(&self, channel: &AlertChannel, alert: &DeviceAlert, event: AlertEvent) -> Result<(), NotifyError> {
+        let (subject, body) = compose(alert, event);
+
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(channel.target.parse()?)
+            .subject(subject)
+            .header(ContentType::TEXT_PLAIN)
+            .body(body)?;
+
+        self.transport.send(message).await?;
+
+        Ok(())
+    }
+}
+
+fn compose(alert: &DeviceAlert, event: AlertEvent) -> (String, String) {
+    let name = &alert.device_name;
+    let heartbeat = alert.kind == ALERT_HEARTBEAT;
+    let last_activity = alert
+        .last_activity_at
+        .map_or("never".to_string(), |at| at.format("%Y-%m-%d %H:%M:%S UTC").to_string());
+
+    let (subject, summary) = match (event, heartbeat) {
+        (AlertEvent::Opened, true) => (
+            format!("{} is offline", name),
+            format!("{} has stopped sending heartbeats. Last heartbeat: {}.", name, last_activity),
+        ),
+        (AlertEvent::Opened, false) => (
+            format!("{} has stopped receiving SMS", name),
+            format!("No SMS has arrived from {} recently. Last SMS: {}.", name, last_activity),
+        ),
+        (AlertEvent::Resolved, true) => (
+            format!("{} is back online", name),
+            format!("{} is sending heartbeats again.", name),
+        ),
+        (AlertEvent::Resolved, false) => (
+            format!("{} is receiving SMS again", name),
+            format!("SMS from {} are arriving again.", name),
+        ),
+        (AlertEvent::Test, _) => (
+            "Test alert".to_string(),
+            "This is a test of your relay alert channel.".to_string(),
+        ),
+    };
+
+    let body = format!(
+        "{}\n\nDevice: {} ({})\nAlert: {}\nOpened: {}\n",
+        summary,
+        name,
+        alert.device_id,
+        alert.kind,
+        alert.opened_at.format("%Y-%m-%d %H:%M:%S UTC"),
+    );
+
+    (format!("[relay] {}", subject), body)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+    use uuid::Uuid;
+
+    use super::*;
+
+    #[derive(Debug, Default)]
+    struct Received {
+        mail_from: String,
+        rcpt_to: Vec<String>,
+        data: String,
+    }
+
+    // Accepts one SMTP session on a local port and returns what was sent.
+    // `rcpt_reply` answers every RCPT TO, so recipients can be refused.
+    async fn smtp_server(rcpt_reply: &'static str) -> (u16, tokio::task::JoinHandle<Received>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let session = tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let (reader, mut writer) = socket.into_split();
+            let mut lines = BufReader::new(reader).lines();
+            let mut received = Received::default();
+
+            writer.write_all(b"220 localhost ESMTP\r\n").await.unwrap();
+            while let Some(line) = lines.next_line().await.unwrap() {
+                let command = line.to_ascii_uppercase();
+                let reply = if command.starts_with("EHLO") || command.starts_with("HELO") {
+                    "250 localhost"
+                } else if command.starts_with("MAIL FROM:") {
+                    received.mail_from = line["MAIL FROM:".len()..].to_string();
+                    "250 OK"
+                } else if command.starts_with("RCPT TO:") {
+                    received.rcpt_to.push(line["RCPT TO:".len()..].to_string());
+                    rcpt_reply
+                } else if command == "DATA" {
+                    writer.write_all(b"354 End data with <CR><LF>.<CR><LF>\r\n").await.unwrap();
+                    while let Some(line) = lines.next_line().await.unwrap() {
+                        if line == "." {
+                            break;
+                        }
+                        received.data.push_str(&line);
+                        received.data.push('\n');
+                    }
+                    "250 OK"
+                } else if command == "QUIT" {
+                    writer.write_all(b"221 Bye\r\n").await.unwrap();
+                    break;
+                } else {
+                    "250 OK"
+                };
+                writer.write_all(format!("{}\r\n", reply).as_bytes()).await.unwrap();
+            }
+
+            received
+        });
+
+        (port, session)
+    }
+
+    fn notifier(port: u16) -> EmailNotifier {
+        EmailNotifier::new(&SmtpConfig {
+            host: "127.0.0.1".to_string(),
+            port,
+            username: None,
+            password: None,
+            from: "Relay <alerts@relay.test>".to_string(),
+            tls: SmtpTls::None,
+        })
+        .unwrap()
+    }
+
+    fn channel() -> AlertChannel {
+        AlertChannel {
+            id: Uuid::new_v4(),
+            kind: "email".to_string(),
+            target: "owner@example.com".to_string(),
+            secret: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    fn alert() -> DeviceAlert {
+        DeviceAlert {
+            id: Uuid::new_v4(),
+            device_id: Uuid::new_v4(),
+            device_name: "Kitchen phone".to_string(),
+            kind: ALERT_HEARTBEAT.to_string(),
+            last_activity_at: None,
+            opened_at: Utc::now(),
+            resolved_at: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn delivers_alerts_over_smtp() {
+        let (port, session) = smtp_server("250 OK").await;
+
+        notifier(port)
+            .notify(&channel(), &alert(), AlertEvent::Opened)
+            .await
+            .unwrap();
+
+        let received = session.await.unwrap();
+        assert_eq!(received.mail_from, "<alerts@relay.test>");
+        assert_eq!(received.rcpt_to, vec!["<owner@example.com>"]);
+        assert!(received.data.contains("Subject: [relay] Kitchen phone is offline"));
+        assert!(received.data.contains("Kitchen phone has stopped sending heartbeats. Last heartbeat: never."));
+    }
+
+    #[tokio::test]
+    async fn reports_rejected_recipients() {
+        let (port, session) = smtp_server("550 No such user").await;
+
+        let result = notifier(port).notify(&channel(), &alert(), AlertEvent::Opened).await;
+
+        assert!(matches!(result, Err(NotifyError::Smtp(_))));
+        assert!(session.await.unwrap().data.is_empty());
+    }
+}
@@ -0,0 +1,71 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use thiserror::Error;
+
+use crate::config::{AppConfig, ConfigError};
+use crate::models::alert::{AlertChannel, AlertEvent, DeviceAlert, CHANNEL_EMAIL, CHANNEL_WEBHOOK};
+
+mod email;
+mod webhook;
+
+pub use email::{EmailNotifier, SMTP_TIMEOUT};
+pub use webhook::WebhookNotifier;
+
+#[derive(Debug, Error)]
+pub enum NotifyError {
+    #[error("HTTP error: {0}")]
+    Http(#[from] reqwest::Error),
+
+    #[error("Endpoint responded with {0}")]
+    UnexpectedStatus(reqwest::StatusCode),
+
+    #[error("Invalid email: {0}")]
+    Email(#[from] lettre::error::Error),
+
+    #[error("Invalid email address: {0}")]
+    Address(#[from] lettre::address::AddressError),
+
+    #[error("SMTP error: {0}")]
+    Smtp(#[from] lettre::transport::smtp::Error),
+
+    #[error("No notifier for {0} channels")]
+    Unsupported(String),
+}
+
+// Delivers alerts over one kind of channel
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    async fn notify(&self, channel: &AlertChannel, alert: &DeviceAlert, event: AlertEvent) -> Result<(), NotifyError>;
+}
+
+// The notifiers available on this server, keyed by channel kind
+pub struct Notifiers {
+    by_kind: HashMap<&'static str, Box<dyn Notifier>>,
+}
+
+impl Notifiers {
+    // Email is only available when SMTP is configured
+    pub fn from_config(config: &AppConfig) -> Result<Self, ConfigError> {
+        let mut by_kind: HashMap<&'static str, Box<dyn Notifier>> = HashMap::new();
+        by_kind.insert(CHANNEL_WEBHOOK, Box::new(WebhookNotifier::new(config.webhook_timeout_seconds)));
+        if let Some(smtp) = &config.smtp {
+            by_kind.insert(CHANNEL_EMAIL, Box::new(EmailNotifier::new(smtp)?));
+        }
+
+        Ok(Notifiers { by_kind })
+    }
+
+    pub fn supports(&self, kind: &str) -> bool {
+        self.by_kind.contains_key(kind)
+    }
+
+    pub async fn notify(&self, channel: &AlertChannel, alert: &DeviceAlert, event: AlertEvent) -> Result<(), NotifyError> {
+        let notifier = self
+            .by_kind
+            .get(channel.kind.as_str())
+            .ok_or_else(|| NotifyError::Unsupported(channel.kind.clone()))?;
+
+        notifier.notify(channel, alert, event).await
+    }
+}
@@ -0,0 +1,51 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::Utc;
+use reqwest::{header::CONTENT_TYPE, Client};
+
+use super::{Notifier, NotifyError};
+use crate::models::alert::{AlertChannel, AlertEvent, AlertWebhookBody, DeviceAlert};
+use crate::{http_client, signing};
+
+// POSTs the alert as JSON, signed the same way as SMS webhook deliveries
+pub struct WebhookNotifier {
+    client: Client,
+}
+
+impl WebhookNotifier {
+    pub fn new(timeout_seconds: u64) -> Self {
+        WebhookNotifier { client: http_client::client(Duration::from_secs(timeout_seconds)) }
+    }
+}
+
+#[async_trait]
+impl Notifier for WebhookNotifier {
+    async fn notify(&self, channel: &AlertChannel, alert: &DeviceAlert, event: AlertEvent) -> Result<(), NotifyError> {
+        let event = match event {
+            AlertEvent::Opened => "alert.opened",
+            AlertEvent::Resolved => "alert.resolved",
+            AlertEvent::Test => "alert.test",
+        };
+        let body = serde_json::to_vec(&AlertWebhookBody { event, alert })
+            .expect("alert bodies always serialize");
+
+        let timestamp = Utc::now().timestamp();
+        let mut request = self
+            .client
+            .post(&channel.target)
+            .header(CONTENT_TYPE, "application/json")
+            .header("X-Relay-Event", event)
+            .header("X-Relay-Timestamp", timestamp.to_string());
+        if let Some(secret) = &channel.secret {
+            request = request.header("X-Relay-Signature", format!("sha256={}", signing::sign_webhook(secret, timestamp, &body)));
+        }
+
+        let response = request.body(body).send().await?;
+        if !response.status().is_success() {
+            return Err(NotifyError::UnexpectedStatus(response.status()));
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,330 @@
+use std::sync::LazyLock;
+
+use regex::{Match, Regex};
+use tracing::warn;
+
+use crate::models::otp::OtpPattern;
+
+// Words that introduce a one-time code, across the languages we see most.
+// Matched case-insensitively as whole words, so "pin" doesn't fire on "shipping".
+const KEYWORDS: &[&str] = &[
+    // English
+    "verification", "verify", "one-time", "one time", "passcode", "password", "security code",
+    "login code", "sign-in", "otp", "2fa", "pin", "code",
+    // Spanish / Portuguese
+    "código", "codigo", "clave", "senha", "contraseña",
+    // French
+    "vérification", "mot de passe",
+    // German
+    "bestätigungscode", "sicherheitscode", "kennwort",
+    // Italian
+    "codice",
+    // Dutch / Polish / Turkish / Indonesian
+    "kod", "şifre", "sifre", "kode",
+    // Russian
+    "код", "пароль",
+];
+
+// Chinese / Japanese / Korean keywords. These scripts don't separate words
+// with spaces, so they're matched anywhere.
+const UNDELIMITED_KEYWORDS: &[&str] = &[
+    "验证码", "校验码", "动态码", "驗證碼", "認証コード", "確認コード", "인증번호",
+];
+
+const MIN_CODE_LENGTH: usize = 4;
+const MAX_CODE_LENGTH: usize = 8;
+// Upper bounds matching the sms.otp_code / sms.otp_issuer columns
+const MAX_STORED_CODE_LENGTH: usize = 32;
+pub const MAX_ISSUER_LENGTH: usize = 255;
+// How far (in bytes) a code may sit from its keyword
+const MAX_KEYWORD_DISTANCE: usize = 80;
+
+static KEYWORD_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    let alternatives = |keywords: &[&str]| keywords.iter().map(|k| regex::escape(k)).collect::<Vec<_>>().join("|");
+    Regex::new(&format!(
+        r"(?i)\b(?:{})\b|{}",
+        alternatives(KEYWORDS),
+        alternatives(UNDELIMITED_KEYWORDS)
+    ))
+    .expect("keyword regex is valid")
+});
+
+static TOKEN_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new("[0-9A-Za-z]+").expect("token regex is valid"));
+
+// "[Acme] ..." or "<Acme> ..." at the start of the message
+static BRACKETED_ISSUER_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^\s*[\[<(【]([^\]>)】#]{2,40})[\]>)】]").expect("issuer regex is valid")
+});
+
+// "Your Acme verification code ..."
+static YOUR_ISSUER_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?i:your)\s+([A-Z][A-Za-z0-9&.\-]*(?:\s[A-Z][A-Za-z0-9&.\-]*){0,2})\s+(?i:verification|security|login|sign-in|one-time|authentication|confirmation|access|otp|code|pin)",
+    )
+    .expect("issuer regex is valid")
+});
+
+// A user pattern with its regex compiled, so it isn't rebuilt for every message
+pub struct CompiledOtpPattern {
+    regex: Regex,
+    issuer: Option<String>,
+}
+
+// Compile the user's patterns, keeping their order. Patterns that no longer
+// compile are dropped.
+pub fn compile(patterns: Vec<OtpPattern>) -> Vec<CompiledOtpPattern> {
+    patterns
+        .into_iter()
+        .filter_map(|pattern| match Regex::new(&pattern.pattern) {
+            Ok(regex) => Some(CompiledOtpPattern { regex, issuer: pattern.issuer }),
+            Err(e) => {
+                // Patterns are validated on save, so this only happens if the regex engine changes
+                warn!("Skipping OTP pattern {} with invalid regex: {:?}", pattern.id, e);
+                None
+            }
+        })
+        .collect()
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ExtractedOtp {
+    pub code: String,
+    pub issuer: Option<String>,
+}
+
+// Find the one-time code in an SMS. The user's own patterns win; otherwise
+// the code is the 4-8 character token closest to an OTP keyword.
+pub fn extract(patterns: &[CompiledOtpPattern], sender: &str, message: &str) -> Option<ExtractedOtp> {
+    for pattern in patterns {
+        if let Some(captures) = pattern.regex.captures(message) {
+            let code = captures
+                .name("code")
+                .or_else(|| captures.get(1))
+                .or_else(|| captures.get(0))
+                .map(|m| m.as_str().trim())
+                .filter(|code| !code.is_empty() && code.len() <= MAX_STORED_CODE_LENGTH);
+
+            if let Some(code) = code {
+                return Some(ExtractedOtp {
+                    code: code.to_string(),
+                    issuer: pattern.issuer.clone().or_else(|| detect_issuer(sender, message)),
+                });
+            }
+        }
+    }
+
+    let keywords: Vec<(usize, usize)> = KEYWORD_REGEX
+        .find_iter(message)
+        .map(|m| (m.start(), m.end()))
+        .collect();
+    if keywords.is_empty() {
+        return None;
+    }
+
+    let code = candidates(message)
+        .into_iter()
+        .filter_map(|(start, end, code)| {
+            let distance = keywords
+                .iter()
+                .map(|&(k_start, k_end)| {
+                    if end <= k_start { k_start - end } else { start.saturating_sub(k_end) }
+                })
+                .min()?;
+            (distance <= MAX_KEYWORD_DISTANCE).then_some((distance, code))
+        })
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, code)| code)?;
+
+    Some(ExtractedOtp {
+        code,
+        issuer: detect_issuer(sender, message),
+    })
+}
+
+// Tokens that look like codes, with their byte spans
+fn candidates(message: &str) -> Vec<(usize, usize, String)> {
+    let tokens: Vec<_> = TOKEN_REGEX.find_iter(message).collect();
+    let mut found = Vec::new();
+    let mut i = 0;
+
+    while i < tokens.len() {
+        let token = tokens[i];
+        let text = token.as_str();
+
+        // "123-456" and "123 456" are read as a single six digit code
+        if let Some(next) = tokens.get(i + 1).filter(|next| is_split_code(message, &token, next)) {
+            found.push((token.start(), next.end(), format!("{}{}", text, next.as_str())));
+            i += 2;
+            continue;
+        }
+
+        if looks_like_code(text) && !looks_like_amount(message, token.start(), token.end()) {
+            found.push((token.start(), token.end(), text.to_string()));
+        }
+
+        i += 1;
+    }
+
+    found
+}
+
+fn is_split_code(message: &str, first: &Match<'_>, second: &Match<'_>) -> bool {
+    let gap = &message[first.end()..second.start()];
+    let halves = [first.as_str(), second.as_str()];
+
+    matches!(gap, "-" | " ") && halves.iter().all(|half| half.len() == 3 && is_digits(half))
+}
+
+fn looks_like_code(token: &str) -> bool {
+    if !(MIN_CODE_LENGTH..=MAX_CODE_LENGTH).contains(&token.len()) {
+        return false;
+    }
+
+    if is_digits(token) {
+        return true;
+    }
+
+    // Alphanumeric codes are upper case and mix letters with digits
+    let has_digit = token.chars().any(|c| c.is_ascii_digit());
+    let has_letter = token.chars().any(|c| c.is_ascii_uppercase());
+    let all_upper = token.chars().all(|c| c.is_ascii_digit() || c.is_ascii_uppercase());
+    has_digit && has_letter && all_upper
+}
+
+// Skip numbers like "$1500" or "1500.00"
+fn looks_like_amount(message: &str, start: usize, end: usize) -> bool {
+    let before = message[..start].chars().next_back();
+    let mut after = message[end..].chars();
+
+    let currency_before = before.is_some_and(|c| "$€£¥₹₽".contains(c));
+    let decimal_after = matches!(after.next(), Some('.') | Some(','))
+        && after.next().is_some_and(|c| c.is_ascii_digit());
+
+    currency_before || decimal_after
+}
+
+fn is_digits(token: &str) -> bool {
+    token.chars().all(|c| c.is_ascii_digit())
+}
+
+fn detect_issuer(sender: &str, message: &str) -> Option<String> {
+    let bracketed = BRACKETED_ISSUER_REGEX
+        .captures(message)
+        .map(|captures| captures[1].trim().to_string());
+
+    let named = || {
+        YOUR_ISSUER_REGEX
+            .captures(message)
+            .map(|captures| captures[1].trim().to_string())
+            .filter(|name| !KEYWORD_REGEX.is_match(name))
+    };
+
+    // Alphanumeric sender IDs ("GOOGLE", "VM-HDFCBK") usually name the issuer
+    let from_sender = || {
+        sender
+            .chars()
+            .any(|c| c.is_alphabetic())
+            .then(|| sender.trim().to_string())
+    };
+
+    bracketed
+        .or_else(named)
+        .or_else(from_sender)
+        .map(|issuer| issuer.chars().take(MAX_ISSUER_LENGTH).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use uuid::Uuid;
+
+    use super::*;
+
+    fn code(message: &str) -> Option<String> {
+        extract(&[], "+15551234567", message).map(|otp| otp.code)
+    }
+
+    fn issuer(sender: &str, message: &str) -> Option<String> {
+        extract(&[], sender, message).and_then(|otp| otp.issuer)
+    }
+
+    #[test]
+    fn finds_code_next_to_keyword() {
+        assert_eq!(code("Your verification code is 482913").as_deref(), Some("482913"));
+        assert_eq!(code("482913 is your login code").as_deref(), Some("482913"));
+        assert_eq!(code("Tu código es 5521").as_deref(), Some("5521"));
+        assert_eq!(code("Ваш код: 7310").as_deref(), Some("7310"));
+    }
+
+    #[test]
+    fn keywords_match_whole_words_only() {
+        // "pin" inside "shipping" and "code" inside "barcode" aren't keywords
+        assert_eq!(code("Your order is shipping, tracking number 58392231"), None);
+        assert_eq!(code("Scan the barcode 4417 at the counter"), None);
+        assert!(!KEYWORD_REGEX.is_match("shipping"));
+        assert!(KEYWORD_REGEX.is_match("Your PIN: 1234"));
+    }
+
+    #[test]
+    fn matches_cjk_keywords_inside_words() {
+        assert_eq!(code("您的验证码是839201，5分钟内有效").as_deref(), Some("839201"));
+    }
+
+    #[test]
+    fn ignores_messages_without_keywords() {
+        assert_eq!(code("See you at 1830 tomorrow"), None);
+    }
+
+    #[test]
+    fn ignores_codes_far_from_keyword() {
+        let message = format!("Your code is below.{} 4821", " filler".repeat(20));
+        assert_eq!(code(&message), None);
+    }
+
+    #[test]
+    fn joins_split_codes() {
+        assert_eq!(code("Your code is 123-456").as_deref(), Some("123456"));
+        assert_eq!(code("Your code is 123 456").as_deref(), Some("123456"));
+    }
+
+    #[test]
+    fn accepts_upper_case_alphanumeric_codes() {
+        assert_eq!(code("Use code AB12CD to sign in").as_deref(), Some("AB12CD"));
+        assert_eq!(code("Use code Hello to sign in"), None);
+    }
+
+    #[test]
+    fn skips_amounts() {
+        assert_eq!(code("Your code is 7731. You spent $1500").as_deref(), Some("7731"));
+        assert_eq!(code("Your code for the 1500.00 payment is 7731").as_deref(), Some("7731"));
+    }
+
+    #[test]
+    fn detects_issuer() {
+        assert_eq!(issuer("+15551234567", "[Acme] Your code is 1234").as_deref(), Some("Acme"));
+        assert_eq!(issuer("+15551234567", "【腾讯】您的验证码是839201").as_deref(), Some("腾讯"));
+        assert_eq!(issuer("+15551234567", "Your Acme Bank verification code is 123456").as_deref(), Some("Acme Bank"));
+        assert_eq!(issuer("GOOGLE", "G-123456 is your verification code").as_deref(), Some("GOOGLE"));
+        assert_eq!(issuer("+15551234567", "Your verification code is 123456"), None);
+    }
+
+    #[test]
+    fn user_patterns_take_precedence() {
+        let pattern = |pattern: &str, issuer: Option<&str>| OtpPattern {
+            id: Uuid::new_v4(),
+            user_id: Uuid::new_v4(),
+            pattern: pattern.to_string(),
+            issuer: issuer.map(str::to_string),
+            created_at: Utc::now(),
+        };
+
+        let patterns = compile(vec![pattern(r"ref (?P<code>[a-z]{5})", Some("Intranet")), pattern(r"token (\d+)", None)]);
+
+        let extracted = extract(&patterns, "+15551234567", "Login ref abcde, code 1234");
+        assert_eq!(extracted, Some(ExtractedOtp { code: "abcde".to_string(), issuer: Some("Intranet".to_string()) }));
+
+        let extracted = extract(&patterns, "+15551234567", "Your token 99");
+        assert_eq!(extracted.map(|otp| otp.code).as_deref(), Some("99"));
+    }
+}
@@ -0,0 +1,172 @@
+use phonenumber::{country, Mode};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SenderType {
+    Phone,
+    ShortCode,
+    Alphanumeric,
+}
+
+impl SenderType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SenderType::Phone => "phone",
+            SenderType::ShortCode => "short_code",
+            SenderType::Alphanumeric => "alphanumeric",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct NormalizedSender {
+    // E.164 for phone numbers, digits for short codes, tidied text for sender IDs
+    pub sender: String,
+    pub sender_type: SenderType,
+    // Key used to group a sender's messages into one conversation
+    pub counterpart: String,
+}
+
+// Canonical form of a region code, if it's one we have numbering data for
+pub fn parse_region(region: &str) -> Option<String> {
+    let region = region.trim().to_ascii_uppercase();
+    region.parse::<country::Id>().ok().map(|_| region)
+}
+
+// Classify a raw sender and rewrite phone numbers to E.164. Numbers without a
+// country code are read in `default_region`; `00` is treated as `+`.
+pub fn normalize_sender(raw: &str, default_region: Option<&str>) -> NormalizedSender {
+    let trimmed = raw.trim();
+
+    if !is_phone_number(trimmed) {
+        let sender = trimmed.split_whitespace().collect::<Vec<_>>().join(" ");
+        return NormalizedSender {
+            counterpart: sender.to_lowercase(),
+            sender,
+            sender_type: SenderType::Alphanumeric,
+        };
+    }
+
+    let digits: String = trimmed.chars().filter(char::is_ascii_digit).collect();
+    let international = international_digits(trimmed, &digits);
+
+    if international.is_none() && is_short_code(&digits) {
+        return NormalizedSender {
+            sender: digits.clone(),
+            sender_type: SenderType::ShortCode,
+            counterpart: digits,
+        };
+    }
+
+    let region = default_region.and_then(|r| r.parse::<country::Id>().ok());
+    let parsed = match &international {
+        Some(number) => phonenumber::parse(None, format!("+{}", number)),
+        None => phonenumber::parse(region, trimmed),
+    };
+
+    // Without a region we can't place a national number; keep its digits
+    let sender = match parsed {
+        Ok(number) => number.format().mode(Mode::E164).to_string(),
+        Err(_) => match international {
+            Some(number) => format!("+{}", number),
+            None => digits,
+        },
+    };
+
+    NormalizedSender {
+        counterpart: sender.clone(),
+        sender,
+        sender_type: SenderType::Phone,
+    }
+}
+
+// True for a phone number without a country code, whose E.164 form depends
+// on the region it is read in
+pub fn is_national_number(raw: &str) -> bool {
+    let trimmed = raw.trim();
+    let digits: String = trimmed.chars().filter(char::is_ascii_digit).collect();
+
+    is_phone_number(trimmed) && international_digits(trimmed, &digits).is_none() && !is_short_code(&digits)
+}
+
+// The digits after the country code prefix, `+` or `00`
+fn international_digits(trimmed: &str, digits: &str) -> Option<String> {
+    match (trimmed.starts_with('+'), digits.strip_prefix("00")) {
+        (true, _) => Some(digits.to_string()),
+        (false, Some(rest)) => Some(rest.to_string()),
+        (false, None) => None,
+    }
+}
+
+fn is_short_code(digits: &str) -> bool {
+    (3..=6).contains(&digits.len())
+}
+
+fn is_phone_number(value: &str) -> bool {
+    value.chars().any(|c| c.is_ascii_digit())
+        && value
+            .chars()
+            .all(|c| c.is_ascii_digit() || matches!(c, '+' | ' ' | '(' | ')' | '.' | '-'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn normalized(raw: &str, region: Option<&str>) -> (String, SenderType, String) {
+        let sender = normalize_sender(raw, region);
+        (sender.sender, sender.sender_type, sender.counterpart)
+    }
+
+    #[test]
+    fn keeps_e164_numbers() {
+        let expected = ("+14155550147".to_string(), SenderType::Phone, "+14155550147".to_string());
+        assert_eq!(normalized("+1 (415) 555-0147", None), expected);
+        assert_eq!(normalized("+14155550147", Some("GB")), expected);
+        assert_eq!(normalized("0014155550147", None), expected);
+    }
+
+    #[test]
+    fn reads_national_numbers_in_the_region() {
+        assert_eq!(normalized("(415) 555-0147", Some("US")).0, "+14155550147");
+        assert_eq!(normalized("020 7946 0018", Some("GB")).0, "+442079460018");
+    }
+
+    #[test]
+    fn keeps_digits_of_national_numbers_without_a_region() {
+        assert_eq!(normalized("(415) 555-0147", None).0, "4155550147");
+        assert_eq!(normalized("(415) 555-0147", Some("XX")).0, "4155550147");
+    }
+
+    #[test]
+    fn recognizes_short_codes() {
+        assert_eq!(normalized(" 72 975 ", Some("US")), ("72975".to_string(), SenderType::ShortCode, "72975".to_string()));
+        assert_eq!(normalized("123", None).1, SenderType::ShortCode);
+    }
+
+    #[test]
+    fn tidies_alphanumeric_sender_ids() {
+        assert_eq!(
+            normalized("  VM-HDFC  Bank ", Some("IN")),
+            ("VM-HDFC Bank".to_string(), SenderType::Alphanumeric, "vm-hdfc bank".to_string())
+        );
+        assert_eq!(normalized("GOOGLE", None).1, SenderType::Alphanumeric);
+    }
+
+    #[test]
+    fn tells_national_numbers_apart() {
+        assert!(is_national_number("2025550147"));
+        assert!(is_national_number("(202) 555-0147"));
+        assert!(!is_national_number("+12025550147"));
+        assert!(!is_national_number("0012025550147"));
+        assert!(!is_national_number("72975"));
+        assert!(!is_national_number("GOOGLE"));
+    }
+
+    #[test]
+    fn parses_regions() {
+        assert_eq!(parse_region(" us ").as_deref(), Some("US"));
+        assert_eq!(parse_region("XX"), None);
+    }
+}
@@ -0,0 +1,266 @@
+use chrono::{Duration, NaiveTime};
+use regex::Regex;
+use tracing::warn;
+
+use crate::models::rule::{
+    Rule, RuleInput, RuleOutcome, ACTION_DISCARD, ACTION_FORWARD, ACTION_LABEL, ACTION_MARK_SPAM, ACTION_TAG,
+};
+
+// A rule with its patterns compiled, so they aren't rebuilt for every message
+pub struct CompiledRule {
+    rule: Rule,
+    sender: Option<Regex>,
+    message: Option<Regex>,
+}
+
+// Compile the enabled rules, keeping their priority order. Rules whose
+// patterns no longer compile are dropped.
+pub fn compile(rules: Vec<Rule>) -> Vec<CompiledRule> {
+    rules
+        .into_iter()
+        .filter(|rule| rule.enabled)
+        .filter_map(|rule| {
+            let sender = compile_pattern(&rule, rule.sender_pattern.as_deref())?;
+            let message = compile_pattern(&rule, rule.message_pattern.as_deref())?;
+            Some(CompiledRule { rule, sender, message })
+        })
+        .collect()
+}
+
+fn compile_pattern(rule: &Rule, pattern: Option<&str>) -> Option<Option<Regex>> {
+    let Some(pattern) = pattern else {
+        return Some(None);
+    };
+
+    match Regex::new(pattern) {
+        Ok(regex) => Some(Some(regex)),
+        Err(e) => {
+            // Patterns are validated on save, so this only happens if the regex engine changes
+            warn!("Skipping rule {} with invalid pattern: {:?}", rule.id, e);
+            None
+        }
+    }
+}
+
+// Run `rules` (already sorted by priority) against a message. Every matching
+// rule contributes its action until one discards or stops processing.
+pub fn evaluate(rules: &[CompiledRule], input: &RuleInput<'_>) -> RuleOutcome {
+    let mut outcome = RuleOutcome::default();
+
+    for compiled in rules {
+        if !matches(compiled, input) {
+            continue;
+        }
+
+        let rule = &compiled.rule;
+
+        outcome.matched_rules.push(rule.id);
+
+        match rule.action.as_str() {
+            ACTION_FORWARD => {
+                if let Some(webhook_id) = rule.webhook_id.filter(|id| !outcome.forward_to.contains(id)) {
+                    outcome.forward_to.push(webhook_id);
+                }
+            }
+            ACTION_TAG => {
+                if let Some(tag) = rule.tag.as_ref().filter(|tag| !outcome.tags.contains(tag)) {
+                    outcome.tags.push(tag.clone());
+                }
+            }
+            ACTION_LABEL => {
+                if let Some(label_id) = rule.label_id.filter(|id| !outcome.labels.contains(id)) {
+                    outcome.labels.push(label_id);
+                }
+            }
+            ACTION_MARK_SPAM => outcome.spam = true,
+            ACTION_DISCARD => {
+                outcome.discard = true;
+                outcome.forward_to.clear();
+                break;
+            }
+            other => warn!("Rule {} has unknown action {}", rule.id, other),
+        }
+
+        if rule.stop_processing {
+            break;
+        }
+    }
+
+    outcome
+}
+
+fn matches(compiled: &CompiledRule, input: &RuleInput<'_>) -> bool {
+    let rule = &compiled.rule;
+
+    if rule.device_id.is_some_and(|device_id| device_id != input.device_id) {
+        return false;
+    }
+
+    let local_time = (input.at + Duration::minutes(rule.utc_offset_minutes as i64)).time();
+    if !in_window(rule.active_from, rule.active_until, local_time) {
+        return false;
+    }
+
+    compiled.sender.as_ref().is_none_or(|regex| regex.is_match(input.sender))
+        && compiled.message.as_ref().is_none_or(|regex| regex.is_match(input.message))
+}
+
+fn in_window(from: Option<NaiveTime>, until: Option<NaiveTime>, time: NaiveTime) -> bool {
+    match (from, until) {
+        (Some(from), Some(until)) if from <= until => time >= from && time < until,
+        // The window wraps past midnight, e.g. 22:00-06:00
+        (Some(from), Some(until)) => time >= from || time < until,
+        (Some(from), None) => time >= from,
+        (None, Some(until)) => time < until,
+        (None, None) => true,
+    }
+}
+
+// Reject patterns that won't compile before they're stored
+pub fn validate_pattern(pattern: &str) -> Result<(), String> {
+    Regex::new(pattern).map(|_| ()).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use uuid::Uuid;
+
+    use super::*;
+
+    fn time(hour: u32, minute: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(hour, minute, 0).unwrap()
+    }
+
+    fn rule(priority: i32, action: &str) -> Rule {
+        Rule {
+            id: Uuid::new_v4(),
+            user_id: Uuid::new_v4(),
+            name: format!("rule {}", priority),
+            priority,
+            enabled: true,
+            sender_pattern: None,
+            message_pattern: None,
+            device_id: None,
+            active_from: None,
+            active_until: None,
+            utc_offset_minutes: 0,
+            action: action.to_string(),
+            webhook_id: None,
+            tag: None,
+            label_id: None,
+            stop_processing: false,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    fn tag_rule(priority: i32, tag: &str) -> Rule {
+        Rule { tag: Some(tag.to_string()), ..rule(priority, ACTION_TAG) }
+    }
+
+    fn input(device_id: Uuid, hour: u32, minute: u32) -> RuleInput<'static> {
+        RuleInput {
+            device_id,
+            sender: "+15551234567",
+            message: "Your code is 4821",
+            at: Utc.with_ymd_and_hms(2025, 6, 1, hour, minute, 0).unwrap(),
+        }
+    }
+
+    #[test]
+    fn windows_include_start_and_exclude_end() {
+        assert!(in_window(Some(time(9, 0)), Some(time(17, 0)), time(9, 0)));
+        assert!(in_window(Some(time(9, 0)), Some(time(17, 0)), time(16, 59)));
+        assert!(!in_window(Some(time(9, 0)), Some(time(17, 0)), time(17, 0)));
+        assert!(!in_window(Some(time(9, 0)), Some(time(17, 0)), time(8, 59)));
+    }
+
+    #[test]
+    fn overnight_windows_wrap_past_midnight() {
+        let (from, until) = (Some(time(22, 0)), Some(time(6, 0)));
+        assert!(in_window(from, until, time(23, 30)));
+        assert!(in_window(from, until, time(0, 0)));
+        assert!(in_window(from, until, time(5, 59)));
+        assert!(!in_window(from, until, time(6, 0)));
+        assert!(!in_window(from, until, time(12, 0)));
+    }
+
+    #[test]
+    fn open_ended_windows() {
+        assert!(in_window(Some(time(22, 0)), None, time(23, 0)));
+        assert!(!in_window(Some(time(22, 0)), None, time(21, 0)));
+        assert!(in_window(None, Some(time(6, 0)), time(5, 0)));
+        assert!(!in_window(None, Some(time(6, 0)), time(7, 0)));
+        assert!(in_window(None, None, time(12, 0)));
+    }
+
+    #[test]
+    fn windows_use_the_rule_offset() {
+        // 23:00 UTC is 08:00 at UTC+9, inside a 07:00-09:00 window
+        let rule = Rule {
+            active_from: Some(time(7, 0)),
+            active_until: Some(time(9, 0)),
+            utc_offset_minutes: 9 * 60,
+            ..tag_rule(1, "morning")
+        };
+        let rules = compile(vec![rule]);
+
+        assert_eq!(evaluate(&rules, &input(Uuid::new_v4(), 23, 0)).tags, vec!["morning"]);
+        assert!(evaluate(&rules, &input(Uuid::new_v4(), 8, 0)).tags.is_empty());
+    }
+
+    #[test]
+    fn applies_matching_rules_in_priority_order() {
+        let device_id = Uuid::new_v4();
+        let rules = compile(vec![
+            tag_rule(1, "first"),
+            Rule { message_pattern: Some("invoice".to_string()), ..tag_rule(2, "skipped") },
+            Rule { device_id: Some(Uuid::new_v4()), ..tag_rule(3, "other device") },
+            tag_rule(4, "second"),
+            rule(5, ACTION_MARK_SPAM),
+        ]);
+
+        let outcome = evaluate(&rules, &input(device_id, 12, 0));
+        assert_eq!(outcome.tags, vec!["first", "second"]);
+        assert!(outcome.spam);
+        assert_eq!(outcome.matched_rules.len(), 3);
+    }
+
+    #[test]
+    fn stop_processing_ends_evaluation() {
+        let rules = compile(vec![
+            tag_rule(1, "first"),
+            Rule { stop_processing: true, ..tag_rule(2, "last") },
+            tag_rule(3, "never"),
+        ]);
+
+        assert_eq!(evaluate(&rules, &input(Uuid::new_v4(), 12, 0)).tags, vec!["first", "last"]);
+    }
+
+    #[test]
+    fn discard_drops_forwards_and_stops() {
+        let webhook_id = Uuid::new_v4();
+        let rules = compile(vec![
+            Rule { webhook_id: Some(webhook_id), ..rule(1, ACTION_FORWARD) },
+            rule(2, ACTION_DISCARD),
+            tag_rule(3, "never"),
+        ]);
+
+        let outcome = evaluate(&rules, &input(Uuid::new_v4(), 12, 0));
+        assert!(outcome.discard);
+        assert!(outcome.forward_to.is_empty());
+        assert!(outcome.tags.is_empty());
+    }
+
+    #[test]
+    fn skips_disabled_rules_and_invalid_patterns() {
+        let rules = compile(vec![
+            Rule { enabled: false, ..tag_rule(1, "disabled") },
+            Rule { sender_pattern: Some("(".to_string()), ..tag_rule(2, "invalid") },
+            tag_rule(3, "kept"),
+        ]);
+
+        assert_eq!(evaluate(&rules, &input(Uuid::new_v4(), 12, 0)).tags, vec!["kept"]);
+    }
+}
@@ -0,0 +1,147 @@
+// Turns a user-facing search string into a Postgres `to_tsquery` expression.
+//
+//   otp bank        both words
+//   otp OR pin      either word
+//   "your code is"  exact phrase
+//   verif*          prefix
+//   -promo          exclude
+//
+// Words are split on punctuation the same way the `sms.search_vector` column
+// is built, so user input can never produce tsquery syntax errors.
+pub fn build_tsquery(input: &str) -> Option<String> {
+    let mut expression = String::new();
+    let mut pending_or = false;
+
+    for term in split_terms(input) {
+        if term == "OR" {
+            pending_or = !expression.is_empty();
+            continue;
+        }
+
+        let Some(clause) = term_clause(&term) else {
+            continue;
+        };
+
+        if !expression.is_empty() {
+            expression.push_str(if pending_or { " | " } else { " & " });
+        }
+        expression.push_str(&clause);
+        pending_or = false;
+    }
+
+    (!expression.is_empty()).then_some(expression)
+}
+
+// Split on whitespace, keeping quoted phrases (with their quotes) together
+fn split_terms(input: &str) -> Vec<String> {
+    let mut terms = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+
+    for c in input.chars() {
+        match c {
+            '"' => {
+                current.push(c);
+                in_quotes = !in_quotes;
+            }
+            c if c.is_whitespace() && !in_quotes => {
+                if !current.is_empty() {
+                    terms.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+
+    if !current.is_empty() {
+        terms.push(current);
+    }
+
+    terms
+}
+
+fn term_clause(term: &str) -> Option<String> {
+    let (negated, term) = match term.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, term),
+    };
+
+    let phrase = term.starts_with('"');
+    let prefix = !phrase && term.ends_with('*');
+
+    let words: Vec<&str> = term
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect();
+    if words.is_empty() {
+        return None;
+    }
+
+    let mut clause = words.join(" <-> ");
+    if prefix {
+        clause.push_str(":*");
+    }
+    if words.len() > 1 {
+        clause = format!("({})", clause);
+    }
+    if negated {
+        clause = format!("!{}", clause);
+    }
+
+    Some(clause)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn joins_words_with_and() {
+        assert_eq!(build_tsquery("otp bank").as_deref(), Some("otp & bank"));
+        assert_eq!(build_tsquery("  otp   bank ").as_deref(), Some("otp & bank"));
+    }
+
+    #[test]
+    fn quoted_phrases_keep_word_order() {
+        assert_eq!(build_tsquery("\"your code is\"").as_deref(), Some("(your <-> code <-> is)"));
+        assert_eq!(build_tsquery("bank \"one-time pin\"").as_deref(), Some("bank & (one <-> time <-> pin)"));
+        // An unclosed quote runs to the end of the input
+        assert_eq!(build_tsquery("\"your code").as_deref(), Some("(your <-> code)"));
+    }
+
+    #[test]
+    fn trailing_star_matches_prefixes() {
+        assert_eq!(build_tsquery("verif*").as_deref(), Some("verif:*"));
+        assert_eq!(build_tsquery("\"verif*\"").as_deref(), Some("verif"));
+    }
+
+    #[test]
+    fn or_joins_its_neighbours() {
+        assert_eq!(build_tsquery("otp OR pin").as_deref(), Some("otp | pin"));
+        assert_eq!(build_tsquery("bank otp OR pin").as_deref(), Some("bank & otp | pin"));
+        // Only upper case OR is an operator, and a dangling one is dropped
+        assert_eq!(build_tsquery("otp or pin").as_deref(), Some("otp & or & pin"));
+        assert_eq!(build_tsquery("OR otp OR").as_deref(), Some("otp"));
+    }
+
+    #[test]
+    fn minus_excludes_terms() {
+        assert_eq!(build_tsquery("bank -promo").as_deref(), Some("bank & !promo"));
+        assert_eq!(build_tsquery("-promo").as_deref(), Some("!promo"));
+        assert_eq!(build_tsquery("-\"act now\" -verif*").as_deref(), Some("!(act <-> now) & !verif:*"));
+    }
+
+    #[test]
+    fn splits_words_on_punctuation() {
+        assert_eq!(build_tsquery("e-mail").as_deref(), Some("(e <-> mail)"));
+        assert_eq!(build_tsquery("a&b|c:*").as_deref(), Some("(a <-> b <-> c:*)"));
+    }
+
+    #[test]
+    fn nothing_searchable_gives_no_query() {
+        assert_eq!(build_tsquery(""), None);
+        assert_eq!(build_tsquery("   "), None);
+        assert_eq!(build_tsquery("!!! & | ( ) :* \"\" - *"), None);
+        assert_eq!(build_tsquery("OR"), None);
+    }
+}
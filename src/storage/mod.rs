@@ -0,0 +1,44 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use thiserror::Error;
+
+use crate::config::BlobStoreConfig;
+
+mod local;
+mod s3;
+
+pub use local::LocalBlobStore;
+pub use s3::S3BlobStore;
+
+#[derive(Debug, Error)]
+pub enum StorageError {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("HTTP error: {0}")]
+    Http(#[from] reqwest::Error),
+
+    #[error("Object store returned {0}")]
+    UnexpectedStatus(reqwest::StatusCode),
+}
+
+// Keeps attachment bytes outside the database. Keys are generated by us and
+// contain only [A-Za-z0-9-/], so backends can use them as paths unescaped.
+#[async_trait]
+pub trait BlobStore: Send + Sync {
+    async fn put(&self, key: &str, content_type: &str, data: &[u8]) -> Result<(), StorageError>;
+
+    // None if nothing is stored under the key
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, StorageError>;
+
+    // Deleting a missing key is not an error
+    async fn delete(&self, key: &str) -> Result<(), StorageError>;
+}
+
+pub fn from_config(config: &BlobStoreConfig) -> Arc<dyn BlobStore> {
+    match config {
+        BlobStoreConfig::Local { path } => Arc::new(LocalBlobStore::new(path.clone())),
+        BlobStoreConfig::S3(s3) => Arc::new(S3BlobStore::new(s3.clone())),
+    }
+}
@@ -0,0 +1,279 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::Utc;
+use hmac::Mac;
+use reqwest::{Method, StatusCode};
+use sha2::{Digest, Sha256};
+
+use super::{BlobStore, StorageError};
+use crate::config::S3Config;
+use crate::{http_client, signing};
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+// Talks to an S3-compatible object store (AWS, MinIO, ...) with SigV4-signed
+// path-style requests
+pub struct S3BlobStore {
+    config: S3Config,
+    client: reqwest::Client,
+}
+
+impl S3BlobStore {
+    pub fn new(config: S3Config) -> Self {
+        S3BlobStore { config, client: http_client::client(REQUEST_TIMEOUT) }
+    }
+
+    async fn send(&self, method: Method, key: &str, body: Option<(&str, &[u8])>) -> Result<reqwest::Response, StorageError> {
+        let path = format!("/{}/{}", self.config.bucket, key);
+        let url = format!("{}{}", self.config.endpoint, path);
+        let host = url
+            .split("://")
+            .nth(1)
+            .and_then(|rest| rest.split('/').next())
+            .unwrap_or_default()
+            .to_string();
+
+        let payload = body.map_or(&[][..], |(_, data)| data);
+        let payload_hash = hex::encode(Sha256::digest(payload));
+
+        let now = Utc::now();
+        let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let date = now.format("%Y%m%d").to_string();
+
+        let authorization = self.authorization(method.as_str(), &path, &host, &payload_hash, &amz_date, &date);
+
+        let mut request = self
+            .client
+            .request(method, &url)
+            .header("host", host)
+            .header("x-amz-date", amz_date)
+            .header("x-amz-content-sha256", payload_hash)
+            .header("authorization", authorization);
+
+        if let Some((content_type, data)) = body {
+            request = request.header("content-type", content_type).body(data.to_vec());
+        }
+
+        Ok(request.send().await?)
+    }
+
+    fn authorization(&self, method: &str, path: &str, host: &str, payload_hash: &str, amz_date: &str, date: &str) -> String {
+        let signed_headers = "host;x-amz-content-sha256;x-amz-date";
+        let canonical_request = format!(
+            "{}\n{}\n\nhost:{}\nx-amz-content-sha256:{}\nx-amz-date:{}\n\n{}\n{}",
+            method, path, host, payload_hash, amz_date, signed_headers, payload_hash
+        );
+
+        let scope = format!("{}/{}/s3/aws4_request", date, self.config.region);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            amz_date,
+            scope,
+            hex::encode(Sha256::digest(canonical_request.as_bytes()))
+        );
+
+        let secret = format!("AWS4{}", self.config.secret_access_key);
+        let mut key = hmac(secret.as_bytes(), date.as_bytes());
+        for part in [self.config.region.as_str(), "s3", "aws4_request"] {
+            key = hmac(&key, part.as_bytes());
+        }
+        let signature = hex::encode(hmac(&key, string_to_sign.as_bytes()));
+
+        format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+            self.config.access_key_id, scope, signed_headers, signature
+        )
+    }
+}
+
+#[async_trait]
+impl BlobStore for S3BlobStore {
+    async fn put(&self, key: &str, content_type: &str, data: &[u8]) -> Result<(), StorageError> {
+        let response = self.send(Method::PUT, key, Some((content_type, data))).await?;
+
+        if !response.status().is_success() {
+            return Err(StorageError::UnexpectedStatus(response.status()));
+        }
+
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, StorageError> {
+        let response = self.send(Method::GET, key, None).await?;
+
+        match response.status() {
+            StatusCode::NOT_FOUND => Ok(None),
+            status if status.is_success() => Ok(Some(response.bytes().await?.to_vec())),
+            status => Err(StorageError::UnexpectedStatus(status)),
+        }
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), StorageError> {
+        let response = self.send(Method::DELETE, key, None).await?;
+
+        match response.status() {
+            StatusCode::NOT_FOUND => Ok(()),
+            status if status.is_success() => Ok(()),
+            status => Err(StorageError::UnexpectedStatus(status)),
+        }
+    }
+}
+
+fn hmac(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = signing::hmac(key);
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+
+    use axum::{
+        body::Bytes,
+        extract::{Path, State},
+        http::{HeaderMap, Method, StatusCode},
+        routing::any,
+        Router,
+    };
+
+    use super::*;
+
+    type Objects = Arc<Mutex<HashMap<String, Vec<u8>>>>;
+
+    fn config(endpoint: String) -> S3Config {
+        S3Config {
+            endpoint,
+            bucket: "relay".to_string(),
+            region: "us-east-1".to_string(),
+            access_key_id: "AKIDEXAMPLE".to_string(),
+            secret_access_key: "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY".to_string(),
+        }
+    }
+
+    // An in-memory bucket that refuses requests whose signing headers don't
+    // describe what was sent
+    async fn stub(
+        State(objects): State<Objects>,
+        method: Method,
+        Path(path): Path<String>,
+        headers: HeaderMap,
+        body: Bytes,
+    ) -> (StatusCode, Vec<u8>) {
+        let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok()).unwrap_or_default();
+        let date = header("x-amz-date").get(..8).unwrap_or_default();
+        let credential = format!("Credential=AKIDEXAMPLE/{}/us-east-1/s3/aws4_request,", date);
+
+        if !header("authorization").starts_with("AWS4-HMAC-SHA256 ")
+            || !header("authorization").contains(&credential)
+            || header("x-amz-content-sha256") != hex::encode(Sha256::digest(&body))
+        {
+            return (StatusCode::FORBIDDEN, Vec::new());
+        }
+
+        let mut objects = objects.lock().unwrap();
+        match method {
+            Method::PUT => {
+                objects.insert(path, body.to_vec());
+                (StatusCode::OK, Vec::new())
+            }
+            Method::GET => match objects.get(&path) {
+                Some(data) => (StatusCode::OK, data.clone()),
+                None => (StatusCode::NOT_FOUND, Vec::new()),
+            },
+            Method::DELETE => {
+                objects.remove(&path);
+                (StatusCode::NO_CONTENT, Vec::new())
+            }
+            _ => (StatusCode::METHOD_NOT_ALLOWED, Vec::new()),
+        }
+    }
+
+    async fn serve_stub() -> (String, Objects) {
+        let objects = Objects::default();
+        let app = Router::new().route("/{*path}", any(stub)).with_state(objects.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (endpoint, objects)
+    }
+
+    async fn round_trip(store: &S3BlobStore) {
+        let key = format!("attachments/{}/{}", uuid::Uuid::new_v4(), uuid::Uuid::new_v4());
+
+        assert_eq!(store.get(&key).await.unwrap(), None);
+
+        store.put(&key, "image/png", b"\x89PNG picture").await.unwrap();
+        assert_eq!(store.get(&key).await.unwrap().as_deref(), Some(&b"\x89PNG picture"[..]));
+
+        store.delete(&key).await.unwrap();
+        assert_eq!(store.get(&key).await.unwrap(), None);
+        store.delete(&key).await.unwrap();
+    }
+
+    #[test]
+    fn signs_requests_with_sigv4() {
+        let store = S3BlobStore::new(config("http://127.0.0.1:9000".to_string()));
+        let payload_hash = hex::encode(Sha256::digest(b"hello"));
+
+        let authorization = store.authorization(
+            "PUT",
+            "/relay/attachments/x",
+            "127.0.0.1:9000",
+            &payload_hash,
+            "20250101T000000Z",
+            "20250101",
+        );
+
+        assert_eq!(
+            authorization,
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20250101/us-east-1/s3/aws4_request, \
+             SignedHeaders=host;x-amz-content-sha256;x-amz-date, \
+             Signature=d7b761e2227c11d766cd61016756ac39a81439052c4f41a45d639e94bbcd21bc"
+        );
+    }
+
+    #[tokio::test]
+    async fn stores_objects_under_bucket_paths() {
+        let (endpoint, objects) = serve_stub().await;
+        let store = S3BlobStore::new(config(endpoint));
+
+        round_trip(&store).await;
+
+        store.put("attachments/a/b", "text/vcard", b"BEGIN:VCARD").await.unwrap();
+        assert!(objects.lock().unwrap().contains_key("relay/attachments/a/b"));
+    }
+
+    #[tokio::test]
+    async fn reports_rejected_requests() {
+        let (endpoint, _) = serve_stub().await;
+        let mut config = config(endpoint);
+        config.access_key_id = "SOMEONEELSE".to_string();
+        let store = S3BlobStore::new(config);
+
+        let result = store.put("attachments/a/b", "image/png", b"data").await;
+
+        assert!(matches!(result, Err(StorageError::UnexpectedStatus(StatusCode::FORBIDDEN))));
+    }
+
+    // Against a real S3-compatible service, e.g. a local MinIO with an existing
+    // bucket, configured through the S3_TEST_* variables
+    #[tokio::test]
+    #[ignore = "requires S3_TEST_ENDPOINT"]
+    async fn round_trips_against_s3_service() {
+        let endpoint = std::env::var("S3_TEST_ENDPOINT").expect("S3_TEST_ENDPOINT is set");
+        let var = |name: &str| std::env::var(name).unwrap_or_else(|_| panic!("{} is set", name));
+
+        let store = S3BlobStore::new(S3Config {
+            endpoint: endpoint.trim_end_matches('/').to_string(),
+            bucket: var("S3_TEST_BUCKET"),
+            region: std::env::var("S3_TEST_REGION").unwrap_or_else(|_| "us-east-1".to_string()),
+            access_key_id: var("S3_TEST_ACCESS_KEY_ID"),
+            secret_access_key: var("S3_TEST_SECRET_ACCESS_KEY"),
+        });
+
+        round_trip(&store).await;
+    }
+}